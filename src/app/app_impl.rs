@@ -1,11 +1,23 @@
 use eframe::egui;
 
-use super::{App, LibraryCommand};
+use super::{App, Panel};
 use crate::app::components::{
-    footer::Footer, library_component::LibraryComponent, menu_bar::MenuBar,
-    player_component::PlayerComponent, playlist_table::PlaylistTable, playlist_tabs::PlaylistTabs,
-    scope_component::ScopeComponent, AppComponent,
+    bookmarks_window::BookmarksWindow, cover_art_component::CoverArtComponent,
+    eq_component::EqComponent, export_window::ExportWindow, footer::Footer,
+    library_component::LibraryComponent, library_config_window::LibraryConfigWindow,
+    loudness_component::LoudnessComponent, menu_bar::MenuBar,
+    now_playing_component::NowPlayingComponent, player_component::PlayerComponent,
+    playlist_table::PlaylistTable, playlist_tabs::PlaylistTabs,
+    preferences_window::PreferencesWindow, scope_component::ScopeComponent, search_box,
+    shortcuts_window::ShortcutsWindow, AppComponent,
 };
+use crate::app::keybindings::Action;
+use crate::app::player::TrackState;
+use crate::app::window_geometry::WindowGeometry;
+use crate::media_controls::{MediaCommand, MediaState, MediaStatus, MediaTrack};
+use crate::taskbar::TaskbarState;
+use crate::tray::TrayCommand;
+use symphonia::core::units::Time;
 
 impl eframe::App for App {
     fn on_exit(&mut self, _ctx: Option<&eframe::glow::Context>) {
@@ -13,25 +25,28 @@ impl eframe::App for App {
         self.save_state();
     }
 
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
         if self.quit {
             ctx.send_viewport_cmd(egui::ViewportCommand::Close);
         }
 
         ctx.request_repaint();
 
-        if let Some(lib_cmd_rx) = &self.library_cmd_rx {
-            if let Ok(lib_cmd) = lib_cmd_rx.try_recv() {
-                match lib_cmd {
-                    LibraryCommand::AddItem(lib_item) => self.library.add_item(lib_item),
-                    LibraryCommand::AddView(lib_view) => self.library.add_view(lib_view),
-                    LibraryCommand::AddPathId(path_id) => {
-                        self.library.set_path_to_imported(path_id)
-                    }
-                }
-            }
+        if self.settings.watch_config_file && self.config_watcher.poll() {
+            self.reload_settings();
         }
 
+        self.handle_key_events(ctx);
+        self.handle_dropped_files(ctx);
+        self.track_window(ctx);
+        self.route_output();
+        self.announce_next_track();
+        self.update_taskbar(frame);
+        self.update_media_controls();
+        self.update_tray(ctx);
+
+        self.handle_library_commands();
+
         if let Some(selected_track) = &self.player.as_mut().unwrap().selected_track {
             let display = format!(
                 "{} - {} [ Music Player ]",
@@ -48,11 +63,22 @@ impl eframe::App for App {
 
         egui::TopBottomPanel::top("MusicPlayer").show(ctx, |ui| {
             MenuBar::add(self, ui);
+            PreferencesWindow::add(self, ui);
+            ExportWindow::add(self, ui);
+            BookmarksWindow::add(self, ui);
+            LibraryConfigWindow::add(self, ui);
+            EqComponent::add(self, ui);
+            ShortcutsWindow::add(self, ui);
         });
 
         egui::TopBottomPanel::top("Player").show(ctx, |ui| {
-            PlayerComponent::add(self, ui);
+            ui.horizontal_top(|ui| {
+                ui.vertical(|ui| PlayerComponent::add(self, ui));
+                ui.separator();
+                NowPlayingComponent::add(self, ui);
+            });
             ScopeComponent::add(self, ui);
+            LoudnessComponent::add(self, ui);
         });
 
         egui::TopBottomPanel::bottom("Footer").show(ctx, |ui| {
@@ -63,6 +89,11 @@ impl eframe::App for App {
             egui::SidePanel::left("Library Window")
                 .default_width(350.0)
                 .show(ctx, |ui| {
+                    if ui.ui_contains_pointer() {
+                        self.active_panel = Panel::Library;
+                    }
+
+                    CoverArtComponent::add(self, ui);
                     LibraryComponent::add(self, ui);
                 });
         });
@@ -73,12 +104,317 @@ impl eframe::App for App {
             });
 
             egui::CentralPanel::default().show(ctx, |ui| {
-                if let Some(_current_playlist_idx) = &mut self.current_playlist_idx {
-                    egui::ScrollArea::both().show(ui, |ui| {
+                if ui.ui_contains_pointer() {
+                    self.active_panel = Panel::Playlist;
+                }
+
+                search_box(ui, &mut self.playlist_search, Panel::Playlist);
+
+                if let Some(current_playlist_idx) = self.current_playlist_idx {
+                    let playlist = &self.playlists[current_playlist_idx];
+                    let mut scroll_area = egui::ScrollArea::both().id_source(playlist.id());
+
+                    // Put the view back where it was left when switching to this playlist.
+                    if self.shown_playlist_id != Some(playlist.id()) {
+                        self.shown_playlist_id = Some(playlist.id());
+                        let [x, y] = playlist.scroll_offset;
+                        scroll_area = scroll_area.scroll_offset(egui::vec2(x, y));
+                    }
+
+                    let output = scroll_area.show(ui, |ui| {
                         PlaylistTable::add(self, ui);
                     });
+
+                    if let Some(playlist) = self.playlists.get_mut(current_playlist_idx) {
+                        // Moved by the wheel or by dragging the scroll bars, rather than to
+                        // follow the playing track.
+                        let is_scrolled_by_hand = ui.rect_contains_pointer(output.inner_rect)
+                            && ui.input(|i| {
+                                i.raw_scroll_delta != egui::Vec2::ZERO || i.pointer.any_down()
+                            })
+                            && playlist.scroll_offset != <[f32; 2]>::from(output.state.offset);
+                        if is_scrolled_by_hand {
+                            self.playlist_scrolled_at = Some(std::time::Instant::now());
+                        }
+                        playlist.scroll_offset = output.state.offset.into();
+                    }
                 }
             });
         });
     }
 }
+
+// How far the seek shortcuts jump.
+const SEEK_STEP_SECS: f64 = 5.0;
+// How much the volume shortcuts change the volume.
+const VOLUME_STEP: f32 = 0.05;
+
+impl App {
+    // Keeps the window geometry to save. Right after opening, a window restored onto a monitor
+    // that's gone is brought back onto the one it ended up on.
+    fn track_window(&mut self, ctx: &egui::Context) {
+        let Some(geometry) = WindowGeometry::of_window(ctx) else {
+            return;
+        };
+
+        if !self.is_window_checked {
+            if let Some(monitor_size) = ctx.input(|i| i.viewport().monitor_size) {
+                self.is_window_checked = true;
+                let clamped = geometry.clamped_to(monitor_size.into());
+                if clamped != geometry {
+                    tracing::info!("Moving the window back on screen to {:?}", clamped);
+                    ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(
+                        clamped.position.into(),
+                    ));
+                    ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(clamped.size.into()));
+                }
+            }
+        }
+
+        self.window = Some(geometry);
+    }
+
+    fn update_taskbar(&mut self, frame: &eframe::Frame) {
+        let player = self.player.as_ref().unwrap();
+        let progress = if player.duration > 0 {
+            player.seek_to_timestamp as f32 / player.duration as f32
+        } else {
+            0.0
+        };
+
+        let state = match player.track_state {
+            TrackState::Playing => TaskbarState::Playing(progress),
+            TrackState::Paused => TaskbarState::Paused(progress),
+            _ => TaskbarState::Hidden,
+        };
+        self.taskbar.update(frame, state);
+    }
+
+    fn update_media_controls(&mut self) {
+        let player = self.player.as_ref().unwrap();
+        let to_us = |timestamp| {
+            player.stream_info.map(|stream_info| {
+                let time = stream_info.time_base.calc_time(timestamp);
+                ((time.seconds as f64 + time.frac) * 1e6) as i64
+            })
+        };
+
+        let state = MediaState {
+            status: match player.track_state {
+                TrackState::Playing => MediaStatus::Playing,
+                TrackState::Paused => MediaStatus::Paused,
+                _ => MediaStatus::Stopped,
+            },
+            track: player.selected_track.as_ref().map(|track| MediaTrack {
+                path: track.path(),
+                title: track.title(),
+                artist: track.artist(),
+                album: track.album(),
+                length_us: (player.duration > 0)
+                    .then(|| to_us(player.duration))
+                    .flatten(),
+            }),
+            position_us: to_us(player.seek_to_timestamp).unwrap_or(0),
+        };
+
+        for command in self.media_controls.update(state) {
+            self.run_media_command(command);
+        }
+    }
+
+    // Commands from the desktop's media controls, run like the matching shortcuts.
+    fn run_media_command(&mut self, command: MediaCommand) {
+        let player = self.player.as_mut().unwrap();
+        let is_playing = matches!(player.track_state, TrackState::Playing);
+        match command {
+            MediaCommand::Play if !is_playing => self.run_action(Action::PlayPause),
+            MediaCommand::Pause if is_playing => self.run_action(Action::PlayPause),
+            MediaCommand::Play | MediaCommand::Pause => {}
+            MediaCommand::PlayPause => self.run_action(Action::PlayPause),
+            MediaCommand::Stop => self.run_action(Action::Stop),
+            MediaCommand::Next => self.run_action(Action::Next),
+            MediaCommand::Previous => self.run_action(Action::Previous),
+            MediaCommand::SeekBy(secs) => {
+                let result = player.seek_by(secs);
+                self.check_player(result);
+            }
+            MediaCommand::SeekTo(secs) => {
+                let Some(stream_info) = player.stream_info else {
+                    return;
+                };
+                let timestamp = stream_info.time_base.calc_timestamp(Time::from(secs));
+                let result = player.seek_to(timestamp.min(player.duration));
+                self.check_player(result);
+            }
+        }
+    }
+
+    // Runs what was picked from the tray menu. With a tray icon to bring it back, closing the
+    // window may only hide it, the audio thread carries on as long as the app runs.
+    fn update_tray(&mut self, ctx: &egui::Context) {
+        if self.settings.close_to_tray
+            && self.tray.is_active()
+            && !self.quit
+            && ctx.input(|i| i.viewport().close_requested())
+        {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            self.set_window_hidden(ctx, true);
+        }
+
+        let is_playing = matches!(
+            self.player.as_ref().unwrap().track_state,
+            TrackState::Playing
+        );
+        for command in self.tray.update(is_playing) {
+            match command {
+                TrayCommand::PlayPause => self.run_action(Action::PlayPause),
+                TrayCommand::Next => self.run_action(Action::Next),
+                TrayCommand::Previous => self.run_action(Action::Previous),
+                TrayCommand::ToggleWindow => self.set_window_hidden(ctx, !self.is_window_hidden),
+                TrayCommand::Quit => self.quit = true,
+            }
+        }
+    }
+
+    fn set_window_hidden(&mut self, ctx: &egui::Context, is_hidden: bool) {
+        self.is_window_hidden = is_hidden;
+        ctx.send_viewport_cmd(egui::ViewportCommand::Visible(!is_hidden));
+        if !is_hidden {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+        }
+    }
+
+    // Files dropped on the window go into the current playlist, folders into the library. While
+    // they're dragged over it, the window says so.
+    fn handle_dropped_files(&mut self, ctx: &egui::Context) {
+        let (is_hovering, dropped) = ctx.input(|i| {
+            (
+                !i.raw.hovered_files.is_empty(),
+                i.raw
+                    .dropped_files
+                    .iter()
+                    .filter_map(|file| file.path.clone())
+                    .collect::<Vec<_>>(),
+            )
+        });
+
+        if !dropped.is_empty() {
+            self.add_paths(dropped);
+        }
+
+        if is_hovering {
+            let painter = ctx.layer_painter(egui::LayerId::new(
+                egui::Order::Foreground,
+                egui::Id::new("file_drop_target"),
+            ));
+            let screen_rect = ctx.screen_rect();
+            painter.rect_filled(screen_rect, 0.0, egui::Color32::from_black_alpha(192));
+            painter.text(
+                screen_rect.center(),
+                egui::Align2::CENTER_CENTER,
+                "Drop files to add them to the playlist, folders to add them to the library",
+                egui::TextStyle::Heading.resolve(&ctx.style()),
+                egui::Color32::WHITE,
+            );
+        }
+    }
+
+    fn handle_key_events(&mut self, ctx: &egui::Context) {
+        // Works from inside text fields too, to jump between the two search boxes.
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::F)) {
+            ctx.memory_mut(|memory| memory.request_focus(self.active_panel.search_id()));
+        }
+
+        // Don't steal keys from text fields, or from the preferences while a new shortcut is
+        // being recorded.
+        if ctx.wants_keyboard_input() || self.capturing_keybinding.is_some() {
+            return;
+        }
+
+        // Typed as text so it works whatever key produces "?" on the keyboard layout.
+        let is_help_requested = ctx.input(|i| {
+            i.events
+                .iter()
+                .any(|event| matches!(event, egui::Event::Text(text) if text == "?"))
+        });
+        if is_help_requested {
+            self.is_shortcuts_open = !self.is_shortcuts_open;
+        }
+
+        // Playback shortcuts stay off while the cheat sheet covers the window.
+        if self.is_shortcuts_open {
+            return;
+        }
+
+        let bindings = self.settings.keybindings.by_specificity();
+        let actions = ctx.input_mut(|i| {
+            bindings
+                .into_iter()
+                .filter(|(_, binding)| i.consume_shortcut(&binding.shortcut()))
+                .map(|(action, _)| action)
+                .collect::<Vec<_>>()
+        });
+
+        for action in actions {
+            self.run_action(action);
+        }
+    }
+
+    /// Runs a playback action the way its keyboard shortcut does. Actions that need a playlist do
+    /// nothing without one.
+    pub fn run_action(&mut self, action: Action) {
+        let player = self.player.as_mut().unwrap();
+        if player.selected_track.is_none() {
+            if action == Action::PlayPause {
+                self.play();
+            }
+            return;
+        }
+
+        let playlist = self.current_playlist_idx.map(|idx| &self.playlists[idx]);
+
+        let result = match action {
+            Action::PlayPause => match player.track_state {
+                TrackState::Playing | TrackState::Paused => player.pause(),
+                _ => player.play(),
+            },
+            Action::Stop => player.stop(),
+            Action::Next => match playlist {
+                Some(playlist) => player.next(playlist, &self.history).map(|_| ()),
+                None => Ok(()),
+            },
+            Action::Previous => match playlist {
+                Some(playlist) => player.previous(playlist),
+                None => Ok(()),
+            },
+            Action::SeekForward => player.seek_by(SEEK_STEP_SECS),
+            Action::SeekBackward => player.seek_by(-SEEK_STEP_SECS),
+            Action::SkipForward => player.seek_by(self.settings.skip_forward_secs as f64),
+            Action::SkipBackward => player.seek_by(-(self.settings.skip_backward_secs as f64)),
+            Action::VolumeUp | Action::VolumeDown => {
+                let step = if action == Action::VolumeUp {
+                    VOLUME_STEP
+                } else {
+                    -VOLUME_STEP
+                };
+                let volume = (player.volume + step).clamp(0.0, 1.0);
+
+                match &self.is_processing_ui_change {
+                    Some(is_processing_ui_change) => {
+                        player.set_volume(volume, is_processing_ui_change)
+                    }
+                    None => Ok(()),
+                }
+            }
+            Action::NextChapter => match playlist {
+                Some(playlist) => player.next_chapter(playlist, &self.history),
+                None => Ok(()),
+            },
+            Action::PreviousChapter => match playlist {
+                Some(playlist) => player.previous_chapter(playlist),
+                None => Ok(()),
+            },
+        };
+        self.check_player(result);
+    }
+}
@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Named positions inside tracks, for coming back to a spot in audiobooks, lectures and long
+/// mixes. Kept per file path rather than on `LibraryItem` so playlist copies of a track stay
+/// equal to the library's.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bookmarks {
+    by_track: HashMap<PathBuf, Vec<Bookmark>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub name: String,
+    pub timestamp: u64,
+}
+
+impl Bookmarks {
+    /// Adds a bookmark, keeping the track's bookmarks in playback order.
+    pub fn add(&mut self, path: PathBuf, bookmark: Bookmark) {
+        let bookmarks = self.by_track.entry(path).or_default();
+        let idx = bookmarks.partition_point(|other| other.timestamp <= bookmark.timestamp);
+        bookmarks.insert(idx, bookmark);
+    }
+
+    pub fn for_track(&self, path: &Path) -> &[Bookmark] {
+        self.by_track
+            .get(path)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn rename(&mut self, path: &Path, idx: usize, name: String) {
+        if let Some(bookmark) = self.by_track.get_mut(path).and_then(|b| b.get_mut(idx)) {
+            bookmark.name = name;
+        }
+    }
+
+    pub fn remove(&mut self, path: &Path, idx: usize) {
+        if let Some(bookmarks) = self.by_track.get_mut(path) {
+            if idx < bookmarks.len() {
+                bookmarks.remove(idx);
+            }
+
+            if bookmarks.is_empty() {
+                self.by_track.remove(path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmark(name: &str, timestamp: u64) -> Bookmark {
+        Bookmark {
+            name: name.to_string(),
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn bookmarks_stay_in_playback_order() {
+        let path = PathBuf::from("book.mp3");
+        let mut bookmarks = Bookmarks::default();
+
+        bookmarks.add(path.clone(), bookmark("end", 300));
+        bookmarks.add(path.clone(), bookmark("start", 10));
+        bookmarks.add(path.clone(), bookmark("middle", 150));
+
+        let names = bookmarks
+            .for_track(&path)
+            .iter()
+            .map(|b| b.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["start", "middle", "end"]);
+
+        bookmarks.remove(&path, 1);
+        assert_eq!(bookmarks.for_track(&path).len(), 2);
+        assert!(bookmarks.for_track(Path::new("other.mp3")).is_empty());
+    }
+}
@@ -0,0 +1,78 @@
+use super::AppComponent;
+use crate::app::App;
+
+pub struct BookmarksWindow;
+
+impl AppComponent for BookmarksWindow {
+    type Context = App;
+
+    fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        if !ctx.is_bookmarks_open {
+            return;
+        }
+
+        let mut is_open = ctx.is_bookmarks_open;
+
+        eframe::egui::Window::new("Bookmarks")
+            .open(&mut is_open)
+            .default_width(320.0)
+            .resizable(true)
+            .show(ui.ctx(), |ui| {
+                let player = ctx.player.as_mut().unwrap();
+                let Some(path) = player.selected_track.as_ref().map(|track| track.path()) else {
+                    ui.label("No track selected.");
+                    return;
+                };
+
+                if ctx.bookmarks.for_track(&path).is_empty() {
+                    ui.label("No bookmarks for this track yet.");
+                    return;
+                }
+
+                let mut seek_to = None;
+                let mut rename = None;
+                let mut remove = None;
+
+                eframe::egui::Grid::new("bookmarks")
+                    .striped(true)
+                    .num_columns(3)
+                    .show(ui, |ui| {
+                        for (idx, bookmark) in ctx.bookmarks.for_track(&path).iter().enumerate() {
+                            if ui
+                                .button(player.format_timestamp(bookmark.timestamp))
+                                .on_hover_text("Jump to this bookmark")
+                                .clicked()
+                            {
+                                seek_to = Some(bookmark.timestamp);
+                            }
+
+                            let mut name = bookmark.name.clone();
+                            if ui.text_edit_singleline(&mut name).changed() {
+                                rename = Some((idx, name));
+                            }
+
+                            if ui.button("🗑").on_hover_text("Delete").clicked() {
+                                remove = Some(idx);
+                            }
+
+                            ui.end_row();
+                        }
+                    });
+
+                if let Some(timestamp) = seek_to {
+                    let result = player.seek_to(timestamp);
+                    ctx.check_player(result);
+                }
+
+                if let Some((idx, name)) = rename {
+                    ctx.bookmarks.rename(&path, idx, name);
+                }
+
+                if let Some(idx) = remove {
+                    ctx.bookmarks.remove(&path, idx);
+                }
+            });
+
+        ctx.is_bookmarks_open = is_open;
+    }
+}
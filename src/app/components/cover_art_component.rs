@@ -0,0 +1,29 @@
+use super::AppComponent;
+use crate::app::App;
+
+// Largest the cover is drawn, it shrinks with the library panel.
+const MAX_COVER_SIZE: f32 = 200.0;
+
+pub struct CoverArtComponent;
+
+impl AppComponent for CoverArtComponent {
+    type Context = App;
+
+    fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        let track = ctx.player.as_ref().unwrap().selected_track.as_ref();
+        let Some(texture) =
+            ctx.cover_art
+                .texture(ui.ctx(), track, ctx.settings.default_cover.as_deref())
+        else {
+            return;
+        };
+
+        let size = ui.available_width().min(MAX_COVER_SIZE);
+        ui.vertical_centered(|ui| {
+            ui.add(
+                eframe::egui::Image::new(texture).fit_to_exact_size(eframe::egui::vec2(size, size)),
+            );
+        });
+        ui.separator();
+    }
+}
@@ -0,0 +1,71 @@
+use super::AppComponent;
+use crate::app::App;
+use crate::equalizer::{BAND_COUNT, BAND_HZ, MAX_GAIN_DB};
+
+pub struct EqComponent;
+
+impl AppComponent for EqComponent {
+    type Context = App;
+
+    fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        if !ctx.is_eq_open {
+            return;
+        }
+
+        let mut is_open = ctx.is_eq_open;
+        let mut changed_bands = Vec::new();
+
+        eframe::egui::Window::new("Equalizer")
+            .open(&mut is_open)
+            .resizable(false)
+            .show(ui.ctx(), |ui| {
+                ui.horizontal(|ui| {
+                    for (band, (gain_db, center_hz)) in
+                        ctx.settings.eq_gains_db.iter_mut().zip(BAND_HZ).enumerate()
+                    {
+                        ui.vertical(|ui| {
+                            let slider = ui
+                                .add(
+                                    eframe::egui::Slider::new(gain_db, -MAX_GAIN_DB..=MAX_GAIN_DB)
+                                        .vertical()
+                                        .step_by(0.5)
+                                        .show_value(false),
+                                )
+                                .on_hover_text(format!("{gain_db:+.1}dB (double-click to reset)"));
+
+                            if slider.double_clicked() {
+                                *gain_db = 0.0;
+                                changed_bands.push(band);
+                            } else if slider.changed() {
+                                changed_bands.push(band);
+                            }
+
+                            ui.small(band_label(center_hz));
+                        });
+                    }
+                });
+
+                if ui.button("Reset").clicked() {
+                    ctx.settings.eq_gains_db = [0.0; BAND_COUNT];
+                    changed_bands.extend(0..BAND_COUNT);
+                }
+            });
+
+        // Saved with the rest of the settings when the app closes.
+        let player = ctx.player.as_mut().unwrap();
+        let result = changed_bands
+            .into_iter()
+            .try_for_each(|band| player.set_eq_band(band, ctx.settings.eq_gains_db[band]));
+        ctx.check_player(result);
+
+        ctx.is_eq_open = is_open;
+    }
+}
+
+fn band_label(hz: f32) -> String {
+    if hz >= 1000.0 {
+        format!("{}k", hz / 1000.0)
+    } else {
+        format!("{hz}")
+    }
+}
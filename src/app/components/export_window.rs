@@ -0,0 +1,100 @@
+use super::AppComponent;
+use crate::app::export::Export;
+use crate::app::App;
+
+pub struct ExportWindow;
+
+impl AppComponent for ExportWindow {
+    type Context = App;
+
+    fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        if !ctx.is_export_open {
+            return;
+        }
+
+        if let Some(export) = ctx.export.as_mut() {
+            export.poll();
+        }
+
+        let mut is_open = ctx.is_export_open;
+
+        eframe::egui::Window::new("Export Playlist Files")
+            .open(&mut is_open)
+            .default_width(420.0)
+            .resizable(true)
+            .show(ui.ctx(), |ui| match ctx.export.as_ref() {
+                None => {
+                    let Some(current_playlist_idx) = ctx.current_playlist_idx else {
+                        ui.label("Select a playlist to export.");
+                        return;
+                    };
+                    let playlist = &ctx.playlists[current_playlist_idx];
+
+                    ui.label(format!(
+                        "Copy the {} files of \"{}\" to a folder or device.",
+                        playlist.tracks.len(),
+                        playlist.get_name().unwrap_or_default()
+                    ));
+                    ui.checkbox(
+                        &mut ctx.export_options.renumber,
+                        "Number files by playlist order",
+                    )
+                    .on_hover_text("Prefixes file names with \"01 - \", \"02 - \", ...");
+
+                    if ui.button("Choose destination and export").clicked() {
+                        if let Some(destination) = rfd::FileDialog::new().pick_folder() {
+                            let tracks = playlist.tracks.iter().map(|track| track.path()).collect();
+                            ctx.export =
+                                Some(Export::start(tracks, destination, ctx.export_options));
+                        }
+                    }
+                }
+                Some(export) => {
+                    ui.label(format!("Exporting to {}", export.destination.display()));
+
+                    let progress = if export.total == 0 {
+                        1.0
+                    } else {
+                        export.processed() as f32 / export.total as f32
+                    };
+                    ui.add(eframe::egui::ProgressBar::new(progress).text(format!(
+                        "{} / {}",
+                        export.processed(),
+                        export.total
+                    )));
+
+                    if export.is_finished {
+                        let summary = match &export.stopped_early {
+                            Some(reason) => format!("{reason}. Copied {} files.", export.copied),
+                            None => format!("Done. Copied {} files.", export.copied),
+                        };
+                        ui.label(summary);
+                    }
+
+                    if !export.errors.is_empty() {
+                        ui.collapsing(format!("{} files failed", export.errors.len()), |ui| {
+                            eframe::egui::ScrollArea::vertical()
+                                .max_height(200.0)
+                                .show(ui, |ui| {
+                                    for (path, error) in export.errors.iter() {
+                                        ui.label(format!("{}: {}", path.display(), error));
+                                    }
+                                });
+                        });
+                    }
+
+                    if !export.is_finished && ui.button("Cancel").clicked() {
+                        export.cancel();
+                    }
+                }
+            });
+
+        // Closing the window forgets a finished export so the next one starts fresh. A running
+        // export keeps going in the background and shows up again when the window is reopened.
+        if !is_open && ctx.export.as_ref().is_some_and(|export| export.is_finished) {
+            ctx.export = None;
+        }
+
+        ctx.is_export_open = is_open;
+    }
+}
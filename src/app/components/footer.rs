@@ -8,7 +8,12 @@ impl AppComponent for Footer {
 
     fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
         ui.horizontal(|ui| {
-            if ctx.player.as_ref().unwrap().is_stopped() {
+            if let (None, Some(status_hint)) = (
+                &ctx.player.as_ref().unwrap().selected_track,
+                &ctx.status_hint,
+            ) {
+                ui.weak(status_hint);
+            } else if ctx.player.as_ref().unwrap().is_stopped() {
                 ui.label("Stopped");
             } else if let Some(selected_track) = &ctx.player.as_ref().unwrap().selected_track {
                 ui.monospace(eframe::egui::RichText::new(
@@ -26,6 +31,54 @@ impl AppComponent for Footer {
                         .unwrap(),
                 ));
             }
+
+            if !ctx.import_progress.is_empty() {
+                let (done, total) = ctx
+                    .import_progress
+                    .values()
+                    .fold((0, 0), |(done, total), progress| {
+                        (done + progress.0, total + progress.1)
+                    });
+                ui.separator();
+                ui.spinner();
+                ui.label(format!("Importing library: {done}/{total}"));
+            }
+
+            let queue_len = ctx.player.as_ref().unwrap().queue.len();
+            if queue_len > 0 {
+                ui.separator();
+                ui.label(format!("Queue: {queue_len}"));
+            }
+
+            if let Some(audio_check) = &mut ctx.audio_check {
+                ui.separator();
+                match audio_check.result() {
+                    None => {
+                        ui.label("Testing audio output…");
+                    }
+                    Some(Ok(output)) => {
+                        ui.label(format!("✔ Audio output works: {output}"));
+                    }
+                    Some(Err(err)) => {
+                        ui.colored_label(
+                            eframe::egui::Color32::RED,
+                            format!("✖ Audio output failed: {err}"),
+                        );
+                    }
+                }
+
+                if audio_check.result().is_some() && ui.small_button("✖").clicked() {
+                    ctx.audio_check = None;
+                }
+            }
+
+            if let Some(playback_error) = &ctx.playback_error {
+                ui.separator();
+                ui.colored_label(eframe::egui::Color32::RED, format!("✖ {playback_error}"));
+                if ui.small_button("✖").clicked() {
+                    ctx.playback_error = None;
+                }
+            }
         });
     }
 }
@@ -1,5 +1,12 @@
 use super::AppComponent;
-use crate::app::App;
+use crate::app::library::{LibraryItem, ViewType};
+use crate::app::player::Player;
+use crate::app::playlist::Playlist;
+use crate::app::settings::AlbumAction;
+use crate::app::{unix_timestamp, App, Panel};
+
+// How far back the "Recently Added" view looks unless all items are shown.
+const RECENTLY_ADDED_SECS: u64 = 30 * 24 * 60 * 60;
 
 pub struct LibraryComponent;
 
@@ -7,58 +14,236 @@ impl AppComponent for LibraryComponent {
     type Context = App;
 
     fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
-        eframe::egui::ScrollArea::both().show(ui, |ui| {
-            eframe::egui::CollapsingHeader::new(eframe::egui::RichText::new("All Music"))
-                .default_open(true)
-                .show(ui, |ui| {
-                    for container in &ctx.library.view().containers {
-                        let items = &container.items;
-                        // todo: correct the name to remove this patch
-                        let album_name = if container.name.is_empty() || container.name == "<?>" {
-                            "unknown album".to_string()
-                        } else {
-                            container.name.clone()
-                        };
+        ui.horizontal(|ui| {
+            ui.label("View");
+            eframe::egui::ComboBox::from_id_source("library_view_type")
+                .selected_text(ctx.library_view_type.to_string())
+                .show_ui(ui, |ui| {
+                    for view_type in [
+                        ViewType::Album,
+                        ViewType::Artist,
+                        ViewType::Genre,
+                        ViewType::Year,
+                        ViewType::RecentlyAdded,
+                    ] {
+                        ui.selectable_value(
+                            &mut ctx.library_view_type,
+                            view_type,
+                            view_type.to_string(),
+                        );
+                    }
+                });
+        });
 
-                        let library_group = eframe::egui::CollapsingHeader::new(
-                            eframe::egui::RichText::new(album_name),
-                        )
-                        .default_open(false)
-                        .show(ui, |ui: &mut eframe::egui::Ui| {
-                            for item in &container.items {
-                                let item_label = ui.add(
-                                    eframe::egui::Label::new(eframe::egui::RichText::new(
-                                        item.title().unwrap_or("unknown title".to_string()),
-                                    ))
-                                    .sense(eframe::egui::Sense::click()),
-                                );
-
-                                if item_label.double_clicked() {
-                                    if let Some(current_playlist_idx) = &ctx.current_playlist_idx {
-                                        let current_playlist =
-                                            &mut ctx.playlists[*current_playlist_idx];
-
-                                        if !current_playlist.tracks.contains(item) {
-                                            current_playlist.add(item.clone());
-                                        }
-                                    }
-                                }
-                            }
-                        });
+        ui.horizontal(|ui| {
+            ui.label("Format");
+            eframe::egui::ComboBox::from_id_source("library_format_filter")
+                .selected_text(ctx.library_format_filter.as_deref().unwrap_or("All"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut ctx.library_format_filter, None, "All");
+                    for format in ctx.library.formats() {
+                        ui.selectable_value(
+                            &mut ctx.library_format_filter,
+                            Some(format.clone()),
+                            format,
+                        );
+                    }
+                });
+        });
 
-                        if let Some(current_playlist_idx) = &ctx.current_playlist_idx {
-                            let current_playlist = &mut ctx.playlists[*current_playlist_idx];
+        let is_search_changed = super::search_box(ui, &mut ctx.library_search, Panel::Library);
 
-                            if library_group.header_response.double_clicked() {
-                                for item in items {
-                                    if !current_playlist.tracks.contains(item) {
-                                        current_playlist.add(item.clone());
+        eframe::egui::ScrollArea::both().show(ui, |ui| match ctx.library_view_type {
+            ViewType::RecentlyAdded => Self::add_recently_added(ctx, ui),
+            _ => Self::add_groups(ctx, ui, is_search_changed),
+        });
+    }
+}
+
+impl LibraryComponent {
+    // Groups with matches open up as a search is typed, and close again once it's cleared. In
+    // between they can be opened and closed by hand as usual.
+    fn add_groups(ctx: &mut App, ui: &mut eframe::egui::Ui, is_search_changed: bool) {
+        let mut album_to_add = None;
+        let is_searching = !ctx.library_search.trim().is_empty();
+
+        eframe::egui::CollapsingHeader::new(eframe::egui::RichText::new("All Music"))
+            .default_open(true)
+            .show(ui, |ui| {
+                let containers = ctx
+                    .library
+                    .view(ctx.library_view_type)
+                    .map_or(&[][..], |view| &view.containers);
+                for container in containers {
+                    let items = container
+                        .items
+                        .iter()
+                        .filter(|item| matches_format(item, &ctx.library_format_filter))
+                        .filter(|item| item.matches_search(&ctx.library_search))
+                        .collect::<Vec<_>>();
+                    if items.is_empty() {
+                        continue;
+                    }
+
+                    // todo: correct the name to remove this patch
+                    let album_name = if container.name.is_empty() || container.name == "<?>" {
+                        "unknown album".to_string()
+                    } else {
+                        container.name.clone()
+                    };
+
+                    let library_group = eframe::egui::CollapsingHeader::new(
+                        eframe::egui::RichText::new(album_name),
+                    )
+                    // Albums, artists and genres can share a name, each keeps its own open state.
+                    .id_source((ctx.library_view_type, &container.name))
+                    .default_open(false)
+                    .open(is_search_changed.then_some(is_searching))
+                    .show(ui, |ui: &mut eframe::egui::Ui| {
+                        for item in &items {
+                            let item_label = ui
+                                .horizontal(|ui| {
+                                    let mut title =
+                                        item.title().unwrap_or("unknown title".to_string());
+                                    // The artist changes from track to track on compilations.
+                                    if item.is_compilation() {
+                                        if let Some(artist) = item.artist() {
+                                            title = format!("{title} - {artist}");
+                                        }
                                     }
-                                }
-                            }
+                                    let item_label = ui.add(
+                                        eframe::egui::Label::new(eframe::egui::RichText::new(
+                                            title,
+                                        ))
+                                        .sense(eframe::egui::Sense::click()),
+                                    );
+                                    add_format_badge(ui, item);
+                                    item_label
+                                })
+                                .inner;
+
+                            handle_item_label(
+                                &item_label,
+                                item,
+                                ctx.player.as_mut().unwrap(),
+                                ctx.current_playlist_idx.map(|idx| &mut ctx.playlists[idx]),
+                            );
                         }
+                    });
+
+                    let header_response = library_group.header_response.on_hover_text(format!(
+                        "Double-click: {}\nCtrl+double-click: {}\nShift+double-click: {}",
+                        ctx.settings.album_double_click,
+                        AlbumAction::AddAndPlay,
+                        AlbumAction::ReplaceAndPlay,
+                    ));
+                    if header_response.double_clicked() {
+                        let modifiers = ui.input(|i| i.modifiers);
+                        let action = if modifiers.shift {
+                            AlbumAction::ReplaceAndPlay
+                        } else if modifiers.command {
+                            AlbumAction::AddAndPlay
+                        } else {
+                            ctx.settings.album_double_click
+                        };
+                        album_to_add = Some((items.into_iter().cloned().collect(), action));
                     }
-                });
-        });
+                }
+            });
+
+        if let Some((tracks, action)) = album_to_add {
+            ctx.add_album(tracks, action);
+        }
+    }
+
+    fn add_recently_added(ctx: &mut App, ui: &mut eframe::egui::Ui) {
+        ui.checkbox(&mut ctx.show_all_recently_added, "Show all")
+            .on_hover_text("Otherwise only items imported in the last 30 days are shown");
+
+        let max_age_secs = if ctx.show_all_recently_added {
+            None
+        } else {
+            Some(RECENTLY_ADDED_SECS)
+        };
+        let now = unix_timestamp();
+
+        eframe::egui::Grid::new("recently_added")
+            .striped(true)
+            .num_columns(4)
+            .show(ui, |ui| {
+                for item in ctx
+                    .library
+                    .recently_added(max_age_secs)
+                    .into_iter()
+                    .filter(|item| matches_format(item, &ctx.library_format_filter))
+                    .filter(|item| item.matches_search(&ctx.library_search))
+                {
+                    let item_label = ui.add(
+                        eframe::egui::Label::new(
+                            item.title().unwrap_or("unknown title".to_string()),
+                        )
+                        .sense(eframe::egui::Sense::click()),
+                    );
+                    ui.label(item.artist().unwrap_or("unknown artist".to_string()));
+                    add_format_badge(ui, item);
+                    ui.weak(match item.added_at() {
+                        Some(added_at) => days_ago(now.saturating_sub(added_at)),
+                        None => "unknown".to_string(),
+                    });
+
+                    handle_item_label(
+                        &item_label,
+                        item,
+                        ctx.player.as_mut().unwrap(),
+                        ctx.current_playlist_idx.map(|idx| &mut ctx.playlists[idx]),
+                    );
+
+                    ui.end_row();
+                }
+            });
+    }
+}
+
+// Double click adds the item to the current playlist, right click offers queueing it.
+fn handle_item_label(
+    item_label: &eframe::egui::Response,
+    item: &LibraryItem,
+    player: &mut Player,
+    current_playlist: Option<&mut Playlist>,
+) {
+    item_label.context_menu(|ui| {
+        if ui.button("Add to queue").clicked() {
+            player.enqueue(item.clone());
+            ui.close_menu();
+        }
+    });
+
+    if item_label.double_clicked() {
+        if let Some(current_playlist) = current_playlist {
+            if !current_playlist.tracks.contains(item) {
+                current_playlist.add(item.clone());
+            }
+        }
+    }
+}
+
+fn matches_format(item: &LibraryItem, format_filter: &Option<String>) -> bool {
+    format_filter.is_none() || item.format() == *format_filter
+}
+
+// Always adds a label, even an empty one, so grid columns stay aligned.
+fn add_format_badge(ui: &mut eframe::egui::Ui, item: &LibraryItem) {
+    ui.label(
+        eframe::egui::RichText::new(item.format().unwrap_or_default())
+            .small()
+            .weak(),
+    );
+}
+
+fn days_ago(age_secs: u64) -> String {
+    match age_secs / (24 * 60 * 60) {
+        0 => "today".to_string(),
+        1 => "yesterday".to_string(),
+        days => format!("{days} days ago"),
     }
 }
@@ -0,0 +1,127 @@
+use super::AppComponent;
+use crate::app::{library::LibraryPathStatus, App};
+use egui_extras::{Column, TableBuilder};
+
+/// The folders the library is imported from, to add, remove and rescan them. The list is saved
+/// with the library.
+pub struct LibraryConfigWindow;
+
+impl AppComponent for LibraryConfigWindow {
+    type Context = App;
+
+    fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        if !ctx.is_library_cfg_open {
+            return;
+        }
+
+        let mut is_open = ctx.is_library_cfg_open;
+
+        eframe::egui::Window::new("Library Configuration")
+            .open(&mut is_open)
+            .default_width(480.0)
+            .default_height(600.0)
+            .resizable([true, true])
+            .show(ui.ctx(), |ui| {
+                let available_height = ui.available_height();
+                let table = TableBuilder::new(ui)
+                    .striped(true)
+                    .resizable(true)
+                    .cell_layout(eframe::egui::Layout::left_to_right(
+                        eframe::egui::Align::Center,
+                    ))
+                    .column(
+                        // Path
+                        Column::remainder().at_least(40.0).clip(true),
+                    )
+                    .column(Column::remainder()) // State
+                    .sense(eframe::egui::Sense::click())
+                    .min_scrolled_height(0.0)
+                    .max_scroll_height(available_height);
+
+                table
+                    .header(20.0, |mut header| {
+                        header.col(|ui| {
+                            ui.strong("Path");
+                        });
+                        header.col(|ui| {
+                            ui.strong("Status");
+                        });
+                    })
+                    .body(|mut body| {
+                        for path in ctx.library.paths().iter() {
+                            body.row(20.0, |mut row| {
+                                let row_id = path.id();
+                                row.set_selected(ctx.lib_config_selections.contains(&row_id));
+                                row.col(|ui| {
+                                    ui.label(path.path().display().to_string());
+                                });
+
+                                row.col(|ui| {
+                                    ui.style_mut().wrap_mode =
+                                        Some(eframe::egui::TextWrapMode::Extend);
+                                    if let Some((done, total)) =
+                                        ctx.import_progress.get(&path.id())
+                                    {
+                                        ui.spinner();
+                                        ui.label(format!("Importing {done}/{total}"));
+                                        return;
+                                    }
+                                    let status = match path.status() {
+                                        LibraryPathStatus::NotImported => "Not imported",
+                                        LibraryPathStatus::Imported => "Imported",
+                                    };
+                                    match path.skipped() {
+                                        0 => ui.label(status),
+                                        skipped => ui
+                                            .label(format!("{status}, {skipped} skipped"))
+                                            .on_hover_text("Files whose tags couldn't be read"),
+                                    };
+                                });
+
+                                // Toggle Row Clicked Status
+                                if row.response().clicked() {
+                                    if ctx.lib_config_selections.contains(&row_id) {
+                                        ctx.lib_config_selections.remove(&row_id);
+                                    } else {
+                                        ctx.lib_config_selections.insert(row_id);
+                                    }
+                                }
+                            })
+                        }
+                    });
+
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    if ui.button("Add folder").clicked() {
+                        if let Some(new_path) = rfd::FileDialog::new().pick_folder() {
+                            ctx.add_library_path(new_path);
+                        }
+                    }
+
+                    if ui
+                        .add_enabled(
+                            !ctx.lib_config_selections.is_empty(),
+                            eframe::egui::Button::new("Remove selected"),
+                        )
+                        .on_hover_text("Their tracks are taken out of the library too")
+                        .clicked()
+                    {
+                        ctx.remove_selected_library_paths();
+                    }
+
+                    if ui
+                        .button("Rescan")
+                        .on_hover_text(
+                            "Pick up files added, changed or deleted since the folders were imported",
+                        )
+                        .clicked()
+                    {
+                        ctx.rescan_library();
+                    }
+                })
+            });
+
+        ctx.is_library_cfg_open = is_open;
+    }
+}
@@ -0,0 +1,62 @@
+use super::AppComponent;
+use crate::app::loudness::FLOOR_DB;
+use crate::app::App;
+use crate::egui::{pos2, vec2, Frame, Rect, Sense, Stroke};
+
+pub struct LoudnessComponent;
+
+impl AppComponent for LoudnessComponent {
+    type Context = App;
+
+    fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        let track = ctx
+            .player
+            .as_ref()
+            .unwrap()
+            .selected_track
+            .as_ref()
+            .map(|track| track.path());
+        if ctx.loudness.track != track {
+            ctx.loudness.clear();
+            ctx.loudness.track = track;
+        }
+
+        Frame::canvas(ui.style()).show(ui, |ui| {
+            let desired_size = vec2(ui.available_width(), 40.0);
+            let (rect, response) = ui.allocate_exact_size(desired_size, Sense::hover());
+
+            let to_screen = eframe::emath::RectTransform::from_to(
+                Rect::from_x_y_ranges(0.0..=1.0, 0.0..=FLOOR_DB),
+                rect,
+            );
+            let painter = ui.painter_at(rect);
+
+            // Guide lines every 20dB.
+            let guide_stroke = Stroke::new(1.0, ui.visuals().faint_bg_color);
+            for db in [-20.0, -40.0] {
+                painter.line_segment(
+                    [to_screen * pos2(0.0, db), to_screen * pos2(1.0, db)],
+                    guide_stroke,
+                );
+            }
+
+            // Drawn segment by segment so nothing is allocated per frame. The newest reading is on
+            // the right edge.
+            let stroke = Stroke::new(1.0, ui.visuals().selection.bg_fill);
+            let capacity = ctx.loudness.capacity() as f32;
+            let offset = capacity - ctx.loudness.blocks().len() as f32;
+            let mut previous = None;
+            for (i, db) in ctx.loudness.blocks().enumerate() {
+                let point = to_screen * pos2((offset + i as f32) / capacity, db);
+                if let Some(previous) = previous {
+                    painter.line_segment([previous, point], stroke);
+                }
+                previous = Some(point);
+            }
+
+            if let Some(db) = ctx.loudness.blocks().last() {
+                response.on_hover_text(format!("{db:.1} dBFS"));
+            }
+        });
+    }
+}
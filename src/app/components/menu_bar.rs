@@ -1,7 +1,9 @@
 use super::AppComponent;
 
-use crate::app::{library::LibraryPathStatus, App, Playlist};
-use egui_extras::{Column, TableBuilder};
+use crate::app::keybindings::Action;
+use crate::app::settings::{Settings, ShuffleMode};
+use crate::app::App;
+use crate::audio_check::AudioCheck;
 
 pub struct MenuBar;
 
@@ -21,28 +23,80 @@ impl AppComponent for MenuBar {
                 ui.separator();
 
                 if ui.button("New Playlist").clicked() {
-                    let default_name_count = ctx
-                        .playlists
-                        .iter()
-                        .filter(|pl| pl.get_name().unwrap().starts_with("New Playlist"))
-                        .count();
-                    let playlist_name = match default_name_count {
-                        0 => "New Playlist".to_string(),
-                        _ => format!("New Playlist ({})", default_name_count - 1),
-                    };
-
-                    let mut new_playlist = Playlist::new();
-                    new_playlist.set_name(playlist_name);
-
-                    ctx.playlists.push(new_playlist.clone());
-                    ctx.current_playlist_idx = Some(ctx.playlists.len() - 1);
+                    ctx.new_playlist();
+                }
+                if ui.button("Import Playlist...").clicked() {
+                    ui.close_menu();
+                    if let Some(path) = rfd::FileDialog::new()
+                        .add_filter("M3U playlist", &["m3u", "m3u8"])
+                        .pick_file()
+                    {
+                        if let Err(err) = ctx.import_playlist(&path) {
+                            tracing::error!("Couldn't import playlist: {}", err);
+                            ctx.status_hint = Some(format!("Couldn't import playlist: {err}"));
+                        }
+                    }
+                }
+
+                let export_playlist_btn = ui.add_enabled(
+                    ctx.current_playlist_idx.is_some(),
+                    eframe::egui::Button::new("Export Playlist..."),
+                );
+                if export_playlist_btn.clicked() {
+                    ui.close_menu();
+                    let playlist = &ctx.playlists[ctx.current_playlist_idx.unwrap()];
+                    if let Some(path) = rfd::FileDialog::new()
+                        .add_filter("M3U playlist", &["m3u8", "m3u"])
+                        .set_file_name(format!(
+                            "{}.m3u8",
+                            playlist
+                                .get_name()
+                                .unwrap_or_else(|| "Playlist".to_string())
+                        ))
+                        .save_file()
+                    {
+                        if let Err(err) = playlist.export_m3u(&path) {
+                            tracing::error!("Couldn't export playlist: {}", err);
+                            ctx.status_hint = Some(format!("Couldn't export playlist: {err}"));
+                        }
+                    }
+                }
+
+                let export_btn = ui.add_enabled(
+                    ctx.current_playlist_idx.is_some(),
+                    eframe::egui::Button::new("Export Playlist Files..."),
+                );
+                if export_btn.clicked() {
+                    ctx.is_export_open = true;
+                    ui.close_menu();
+                }
+
+                if ui
+                    .button("Export Listening Data...")
+                    .on_hover_text("Save the listening history and play counts as CSV or JSON")
+                    .clicked()
+                {
+                    ui.close_menu();
+                    if let Some(path) = rfd::FileDialog::new()
+                        .add_filter("CSV", &["csv"])
+                        .add_filter("JSON", &["json"])
+                        .set_file_name("listening-data.csv")
+                        .save_file()
+                    {
+                        if let Err(err) = ctx.history.export(&path) {
+                            tracing::error!("Couldn't export listening data: {}", err);
+                            ctx.status_hint =
+                                Some(format!("Couldn't export listening data: {err}"));
+                        }
+                    }
                 }
-                let _load_playlist_btn = ui.button("Load Playlist");
-                let _save_playlist_btn = ui.button("Save Playlist");
 
                 ui.separator();
 
-                let _pref_btn = ui.button("Preferences");
+                if ui.button("Preferences").clicked() {
+                    ctx.is_preferences_open = true;
+                    ui.close_menu();
+                }
 
                 ui.separator();
 
@@ -62,33 +116,62 @@ impl AppComponent for MenuBar {
                 let next_btn = ui.button("Next");
                 let prev_btn = ui.button("Previous");
 
-                if let Some(_selected_track) = &ctx.player.as_mut().unwrap().selected_track {
-                    if play_btn.clicked() {
-                        ctx.player.as_mut().unwrap().play();
-                    }
+                if play_btn.clicked() {
+                    ctx.play();
+                }
 
+                if let Some(_selected_track) = &ctx.player.as_mut().unwrap().selected_track {
                     if stop_btn.clicked() {
-                        ctx.player.as_mut().unwrap().stop();
+                        let result = ctx.player.as_mut().unwrap().stop();
+                        ctx.check_player(result);
                     }
 
                     if pause_btn.clicked() {
-                        ctx.player.as_mut().unwrap().pause();
+                        let result = ctx.player.as_mut().unwrap().pause();
+                        ctx.check_player(result);
                     }
 
                     if next_btn.clicked() {
-                        ctx.player
-                            .as_mut()
-                            .unwrap()
-                            .next(&ctx.playlists[(ctx.current_playlist_idx).unwrap()])
+                        ctx.run_action(Action::Next);
                     }
 
                     if prev_btn.clicked() {
-                        ctx.player
-                            .as_mut()
-                            .unwrap()
-                            .previous(&ctx.playlists[(ctx.current_playlist_idx).unwrap()])
+                        ctx.run_action(Action::Previous);
                     }
                 }
+
+                ui.separator();
+
+                let has_track = ctx.player.as_ref().unwrap().selected_track.is_some();
+                if ui
+                    .add_enabled(has_track, eframe::egui::Button::new("Reload from disk"))
+                    .on_hover_text("Pick up tags or audio changed outside the app")
+                    .clicked()
+                {
+                    ctx.reload_track();
+                    ui.close_menu();
+                }
+
+                ui.separator();
+
+                ui.menu_button("Shuffle", |ui| {
+                    let mut shuffle = ctx.settings.shuffle;
+                    for mode in [
+                        ShuffleMode::Off,
+                        ShuffleMode::Uniform,
+                        ShuffleMode::LeastPlayed,
+                    ] {
+                        ui.radio_value(&mut shuffle, mode, mode.to_string());
+                    }
+
+                    if shuffle != ctx.settings.shuffle {
+                        ctx.apply_settings(Settings {
+                            shuffle,
+                            ..ctx.settings.clone()
+                        });
+                        ui.close_menu();
+                    }
+                });
             });
 
             ui.menu_button("Library", |ui| {
@@ -96,117 +179,27 @@ impl AppComponent for MenuBar {
 
                 if cfg_btn.clicked() {
                     ctx.is_library_cfg_open = true;
+                    ui.close_menu();
                 };
             });
 
             ui.menu_button("Help", |ui| {
+                if ui
+                    .button("Test audio output")
+                    .on_hover_text("Play a short tone to check that sound comes out")
+                    .clicked()
+                {
+                    ctx.audio_check = Some(AudioCheck::start(ctx.settings.output_host.clone()));
+                    ui.close_menu();
+                }
+
+                if ui.button("Keyboard Shortcuts").clicked() {
+                    ctx.is_shortcuts_open = true;
+                    ui.close_menu();
+                }
+
                 let _about_btn = ui.button("About");
             });
-
-            if ctx.is_library_cfg_open {
-                // TODO - Turn this library configuation into a separate component
-                eframe::egui::Window::new("Library Configuration")
-                    .default_width(480.0)
-                    .default_height(600.0)
-                    .resizable([true, true])
-                    .show(ui.ctx(), |ui| {
-                        let available_height = ui.available_height();
-                        let table = TableBuilder::new(ui)
-                            .striped(true)
-                            .resizable(true)
-                            .cell_layout(eframe::egui::Layout::left_to_right(
-                                eframe::egui::Align::Center,
-                            ))
-                            .column(
-                                // Path
-                                Column::remainder().at_least(40.0).clip(true),
-                            )
-                            .column(Column::remainder()) // State
-                            .sense(eframe::egui::Sense::click())
-                            .min_scrolled_height(0.0)
-                            .max_scroll_height(available_height);
-
-                        table
-                            .header(20.0, |mut header| {
-                                header.col(|ui| {
-                                    ui.strong("Path");
-                                });
-                                header.col(|ui| {
-                                    ui.strong("Status");
-                                });
-                            })
-                            .body(|mut body| {
-                                for path in ctx.library.paths().iter() {
-                                    body.row(20.0, |mut row| {
-                                        let row_id = path.id();
-                                        row.set_selected(
-                                            ctx.lib_config_selections.contains(&row_id),
-                                        );
-                                        row.col(|ui| {
-                                            ui.label(
-                                                path.path()
-                                                    .clone()
-                                                    .into_os_string()
-                                                    .into_string()
-                                                    .unwrap_or("Could not format path".to_string()),
-                                            );
-                                        });
-
-                                        row.col(|ui| {
-                                            ui.style_mut().wrap_mode =
-                                                Some(eframe::egui::TextWrapMode::Extend);
-                                            ui.label("Status unknown");
-                                        });
-
-                                        // Toggle Row Clicked Status
-                                        if row.response().clicked() {
-                                            if ctx.lib_config_selections.contains(&row_id) {
-                                                ctx.lib_config_selections.remove(&row_id);
-                                            } else {
-                                                ctx.lib_config_selections.insert(row_id);
-                                            }
-                                        }
-                                    })
-                                }
-                            });
-
-                        ui.separator();
-
-                        ui.horizontal(|ui| {
-                            if ui.button("Add path").clicked() {
-                                if let Some(new_path) = rfd::FileDialog::new().pick_folder() {
-                                    ctx.library.add_path(new_path);
-                                }
-                            }
-
-                            if ui.button("Remove selected paths").clicked() {
-                                // TODO - Should only appear clickable when a path is selected.
-                                // Will also remove any files in the library with the same LibraryPathId
-                                if !ctx.lib_config_selections.is_empty() {
-                                    for path_id in ctx.lib_config_selections.iter() {
-                                        ctx.library.remove_path(*path_id);
-                                    }
-                                }
-                            }
-
-                            if ui.button("Cancel").clicked() {
-                                ctx.is_library_cfg_open = false;
-                            }
-
-                            if ui.button("Save").clicked() {
-                                for lib_path in ctx
-                                    .library
-                                    .paths()
-                                    .iter()
-                                    .filter(|p| p.status() == LibraryPathStatus::NotImported)
-                                {
-                                    ctx.import_library_paths(lib_path);
-                                }
-                                ctx.is_library_cfg_open = false;
-                            }
-                        })
-                    });
-            }
         });
     }
 }
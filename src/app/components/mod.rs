@@ -1,13 +1,43 @@
+pub mod bookmarks_window;
+pub mod cover_art_component;
+pub mod eq_component;
+pub mod export_window;
 pub mod footer;
 pub mod library_component;
+pub mod library_config_window;
+pub mod loudness_component;
 pub mod menu_bar;
+pub mod now_playing_component;
 pub mod player_component;
 pub mod playlist_table;
 pub mod playlist_tabs;
+pub mod preferences_window;
 pub mod scope_component;
+pub mod shortcuts_window;
 
 pub trait AppComponent {
     type Context;
 
     fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui);
 }
+
+/// A search field that Ctrl+F can focus through `panel`'s id. Esc clears it and gives the
+/// keyboard back to the shortcuts.
+/// Returns whether the query changed.
+pub fn search_box(ui: &mut eframe::egui::Ui, query: &mut String, panel: crate::app::Panel) -> bool {
+    let response = ui.add(
+        eframe::egui::TextEdit::singleline(query)
+            .id(panel.search_id())
+            .hint_text("Search (Ctrl+F)"),
+    );
+
+    // egui already drops focus on Esc, so this also catches the frame it was lost.
+    let is_escaped = ui.input(|i| i.key_pressed(eframe::egui::Key::Escape));
+    if is_escaped && (response.has_focus() || response.lost_focus()) && !query.is_empty() {
+        query.clear();
+        response.surrender_focus();
+        return true;
+    }
+
+    response.changed()
+}
@@ -0,0 +1,87 @@
+use super::AppComponent;
+use crate::app::App;
+use crate::TrackFormat;
+
+const COVER_SIZE: f32 = 72.0;
+
+/// Details of the selected track: its cover, tags, how it's encoded and where the file is.
+pub struct NowPlayingComponent;
+
+impl AppComponent for NowPlayingComponent {
+    type Context = App;
+
+    fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        let player = ctx.player.as_ref().unwrap();
+        let Some(track) = player.selected_track.as_ref() else {
+            return;
+        };
+
+        ui.horizontal(|ui| {
+            if let Some(texture) =
+                ctx.cover_art
+                    .texture(ui.ctx(), Some(track), ctx.settings.default_cover.as_deref())
+            {
+                ui.add(
+                    eframe::egui::Image::new(texture)
+                        .fit_to_exact_size(eframe::egui::vec2(COVER_SIZE, COVER_SIZE)),
+                );
+            }
+
+            ui.vertical(|ui| {
+                ui.strong(track.title().unwrap_or("Unknown Title".to_string()));
+
+                let mut album = track.album().unwrap_or_default();
+                if let Some(year) = track.year() {
+                    album = format!("{album} ({year})");
+                }
+                ui.label(
+                    [track.artist().unwrap_or_default(), album]
+                        .into_iter()
+                        .filter(|field| !field.is_empty())
+                        .collect::<Vec<_>>()
+                        .join(" – "),
+                );
+
+                let mut details = Vec::new();
+                if let Some(genre) = track.genre() {
+                    details.push(genre);
+                }
+                match (track.track_number(), track.disc_number()) {
+                    (Some(track_number), Some(disc_number)) => {
+                        details.push(format!("Track {disc_number}.{track_number}"))
+                    }
+                    (Some(track_number), None) => details.push(format!("Track {track_number}")),
+                    _ => {}
+                }
+                if let Some(track_format) = &player.track_format {
+                    details.push(format_description(track_format));
+                }
+                ui.label(details.join(" · "));
+
+                ui.small(track.path().display().to_string())
+                    .on_hover_text("Location of the file");
+            });
+        });
+    }
+}
+
+// E.g. "FLAC 16-bit 44.1 kHz 2 ch 912 kbps", leaving out whatever isn't known.
+fn format_description(track_format: &TrackFormat) -> String {
+    let mut parts = Vec::new();
+    if let Some(codec_name) = &track_format.codec_name {
+        parts.push(codec_name.clone());
+    }
+    if let Some(bits_per_sample) = track_format.bits_per_sample {
+        parts.push(format!("{bits_per_sample}-bit"));
+    }
+    if let Some(sample_rate) = track_format.sample_rate {
+        parts.push(format!("{:.1} kHz", sample_rate as f32 / 1000.0));
+    }
+    if let Some(channels) = track_format.channels {
+        parts.push(format!("{channels} ch"));
+    }
+    if let Some(bitrate_kbps) = track_format.bitrate_kbps {
+        parts.push(format!("{bitrate_kbps} kbps"));
+    }
+    parts.join(" ")
+}
@@ -1,6 +1,17 @@
 use super::AppComponent;
+use crate::app::bookmarks::Bookmark;
+use crate::app::keybindings::Action;
+use crate::app::library::LibraryItem;
+use crate::app::observer::PlaybackEvent;
+use crate::app::player::TrackState;
+use crate::app::scope::Scope;
+use crate::app::settings::{RepeatMode, ShuffleMode};
 use crate::egui::style::HandleShape;
-use crate::{app::App, UiCommand};
+use crate::preview::Preview;
+use crate::{app::App, PlaybackStatus, UiCommand};
+
+// Size of the cover next to the playing track's title.
+const THUMBNAIL_SIZE: f32 = 40.0;
 
 pub struct PlayerComponent;
 
@@ -8,25 +19,102 @@ impl AppComponent for PlayerComponent {
     type Context = App;
 
     fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        if let Some(track) = ctx.player.as_ref().unwrap().selected_track.clone() {
+            ui.horizontal(|ui| {
+                let size = eframe::egui::vec2(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+                // Shares its cache with the big cover in the library panel.
+                let texture = ctx.cover_art.texture(
+                    ui.ctx(),
+                    Some(&track),
+                    ctx.settings.default_cover.as_deref(),
+                );
+                match texture {
+                    Some(texture) => {
+                        ui.add(eframe::egui::Image::new(texture).fit_to_exact_size(size));
+                    }
+                    None => {
+                        ui.add_sized(size, eframe::egui::Label::new("🎵"));
+                    }
+                }
+
+                ui.vertical(|ui| {
+                    ui.strong(track.title().unwrap_or("unknown title".to_string()));
+                    ui.weak(track.artist().unwrap_or("unknown artist".to_string()));
+                });
+            });
+        }
+
         ui.horizontal(|ui| {
             let stop_btn = ui.button("■");
             let play_btn = ui.button("▶");
             let pause_btn = ui.button("⏸");
             let prev_btn = ui.button("|◀");
             let next_btn = ui.button("▶|");
+            let skip_backward_btn = ui
+                .button(format!("↺{}", ctx.settings.skip_backward_secs))
+                .on_hover_text(format!("Back {}s", ctx.settings.skip_backward_secs));
+            let skip_forward_btn = ui
+                .button(format!("{}↻", ctx.settings.skip_forward_secs))
+                .on_hover_text(format!("Forward {}s", ctx.settings.skip_forward_secs));
+            let prev_chapter_btn = ui.button("⏪").on_hover_text("Previous chapter");
+            let next_chapter_btn = ui.button("⏩").on_hover_text("Next chapter");
+            let add_bookmark_btn = ui.button("🔖").on_hover_text("Add bookmark");
+            if ui.button("Bookmarks").clicked() {
+                ctx.is_bookmarks_open = !ctx.is_bookmarks_open;
+            }
+            if ui.button("EQ").on_hover_text("Equalizer").clicked() {
+                ctx.is_eq_open = !ctx.is_eq_open;
+            }
+            let is_shuffling = ctx.settings.shuffle != ShuffleMode::Off;
+            if ui
+                .selectable_label(is_shuffling, "🔀")
+                .on_hover_text(format!("Shuffle: {}", ctx.settings.shuffle))
+                .clicked()
+            {
+                ctx.toggle_shuffle();
+            }
+            let repeat = ctx.settings.repeat;
+            let repeat_icon = if repeat == RepeatMode::One {
+                "🔂"
+            } else {
+                "🔁"
+            };
+            if ui
+                .selectable_label(repeat != RepeatMode::Off, repeat_icon)
+                .on_hover_text(format!("Repeat: {repeat}"))
+                .clicked()
+            {
+                ctx.cycle_repeat();
+            }
+
+            let is_muted = ctx.player.as_ref().unwrap().is_muted();
+            if ui
+                .selectable_label(is_muted, if is_muted { "🔇" } else { "🔊" })
+                .on_hover_text(if is_muted { "Unmute" } else { "Mute" })
+                .clicked()
+            {
+                let result = ctx.player.as_mut().unwrap().toggle_mute();
+                ctx.check_player(result);
+            }
 
             let mut volume = ctx.player.as_ref().unwrap().volume;
             let previous_vol = volume;
 
+            // Logarithmic, so the quiet half of the range isn't squeezed into the end of the
+            // slider. Below -60dB it drops straight to silence.
             let volume_slider = ui.add(
                 eframe::egui::Slider::new(&mut volume, 0.0_f32..=1.0_f32)
-                    .logarithmic(false)
+                    .logarithmic(true)
+                    .smallest_positive(0.001)
                     .show_value(true)
                     .clamp_to_range(true)
-                    .step_by(0.01)
                     .custom_formatter(|num, _| {
-                        let db = 20.0 * num.log10();
-                        format!("{db:.02}dB")
+                        if num > 0.0 {
+                            let db = 20.0 * num.log10();
+                            format!("{db:.02}dB")
+                        } else {
+                            "-∞dB".to_string()
+                        }
                     }),
             );
 
@@ -34,34 +122,223 @@ impl AppComponent for PlayerComponent {
                 if let Some(is_processing_ui_change) = &ctx.is_processing_ui_change {
                     // Only send if the volume is actually changing
                     if volume != previous_vol {
-                        ctx.player
+                        let result = ctx
+                            .player
                             .as_mut()
                             .unwrap()
                             .set_volume(volume, is_processing_ui_change);
+                        ctx.check_player(result);
                     }
                 }
             }
 
+            let mut balance = ctx.player.as_ref().unwrap().balance;
+            let balance_slider = ui
+                .add(
+                    eframe::egui::Slider::new(&mut balance, -1.0_f32..=1.0_f32)
+                        .show_value(false)
+                        .clamp_to_range(true)
+                        .step_by(0.05),
+                )
+                .on_hover_text("Balance (double-click to center)");
+
+            if balance_slider.double_clicked() {
+                let result = ctx.player.as_mut().unwrap().set_balance(0.0);
+                ctx.check_player(result);
+            } else if balance_slider.changed() {
+                let result = ctx.player.as_mut().unwrap().set_balance(balance);
+                ctx.check_player(result);
+            }
+
+            let gain_label = match ctx.player.as_ref().unwrap().gain_db() {
+                Some(db) => format!("Gain: {db:+.1} dB"),
+                None => "Gain: -inf dB".to_string(),
+            };
+            ui.monospace(gain_label)
+                .on_hover_text("Effective gain applied to the playing track");
+
+            // Lit for the limiter's release too, so short peaks still show up.
+            let limit_color = if ctx.player.as_ref().unwrap().is_limiting {
+                eframe::egui::Color32::from_rgb(255, 140, 0)
+            } else {
+                ui.visuals().weak_text_color()
+            };
+            ui.colored_label(limit_color, "LIMIT")
+                .on_hover_text("Lit while the limiter holds peaks down to keep them from clipping");
+
+            if let Some(stream_info) = ctx.player.as_ref().unwrap().stream_info {
+                ui.monospace(format!(
+                    "{:.1} kHz {} ch",
+                    stream_info.source.rate as f32 / 1000.0,
+                    stream_info.source.channels.count()
+                ))
+                .on_hover_text("Sample rate and channels of the playing track");
+
+                if stream_info.is_resampled() {
+                    ui.colored_label(
+                        eframe::egui::Color32::YELLOW,
+                        format!(
+                            "⚠ Resampled to {:.1} kHz",
+                            stream_info.output_rate as f32 / 1000.0
+                        ),
+                    )
+                    .on_hover_text("The output device runs at a different sample rate");
+                }
+
+                if stream_info.is_remixed() {
+                    ui.colored_label(
+                        eframe::egui::Color32::YELLOW,
+                        format!("⚠ Device expects {} ch", stream_info.output_channels),
+                    )
+                    .on_hover_text("The output device has a different channel layout");
+                }
+
+                let stored_dynamic_range = ctx
+                    .player
+                    .as_ref()
+                    .unwrap()
+                    .selected_track
+                    .as_ref()
+                    .and_then(|track| track.dynamic_range());
+                match (stored_dynamic_range, ctx.dynamic_range.value()) {
+                    (Some(dynamic_range), _) => {
+                        ui.monospace(format!("DR{dynamic_range}")).on_hover_text(
+                            "Dynamic range, measured when the track last played through",
+                        );
+                    }
+                    (None, Some(dynamic_range)) => {
+                        ui.monospace(format!("DR{dynamic_range:.0}…")).on_hover_text(
+                            "Dynamic range so far, kept once the track plays through without skipping",
+                        );
+                    }
+                    (None, None) => {}
+                }
+
+                if ctx.player.as_ref().unwrap().stream_changed {
+                    ui.colored_label(eframe::egui::Color32::YELLOW, "⚠ Format changed")
+                        .on_hover_text(
+                            "The track changed sample rate or channels partway through, the output was reopened",
+                        );
+                }
+            }
+
             let mut seek_to_timestamp = ctx.player.as_ref().unwrap().seek_to_timestamp;
             let mut duration = ctx.player.as_ref().unwrap().duration;
 
             if let Ok(new_seek_cmd) = ctx.player.as_ref().unwrap().ui_rx.try_recv() {
                 match new_seek_cmd {
                     UiCommand::CurrentTimestamp(seek_timestamp) => {
-                        seek_to_timestamp = seek_timestamp;
+                        let player = ctx.player.as_mut().unwrap();
+                        if player.update_position(seek_timestamp) {
+                            seek_to_timestamp = seek_timestamp;
+                        }
+
+                        if player
+                            .update_listen_progress(seek_timestamp, &ctx.settings.play_threshold)
+                        {
+                            if let Some(track) = &player.selected_track {
+                                ctx.history.record_play(track);
+                            }
+                        }
                     }
                     UiCommand::TotalTrackDuration(dur) => {
                         tracing::info!("Received Duration: {}", dur);
                         duration = dur;
                         ctx.player.as_mut().unwrap().set_duration(dur);
                     }
+                    UiCommand::UnknownDuration => {
+                        duration = 0;
+                        ctx.player.as_mut().unwrap().set_duration(0);
+                    }
+                    UiCommand::Chapters(chapters) => {
+                        ctx.player.as_mut().unwrap().chapters = chapters;
+                    }
+                    UiCommand::PlaybackError(message) => {
+                        ctx.playback_error = Some(message);
+                    }
+                    UiCommand::TrackFailed(path) => {
+                        tracing::warn!("Track failed to play: {:?}", path);
+                        ctx.advance_playback(Some(path));
+                    }
+                    UiCommand::Loading(is_loading) => {
+                        ctx.player.as_mut().unwrap().is_loading = is_loading;
+                    }
+                    UiCommand::Limiting(is_limiting) => {
+                        ctx.player.as_mut().unwrap().is_limiting = is_limiting;
+                    }
+                    UiCommand::PlaybackStatus(status) => {
+                        ctx.player.as_mut().unwrap().playback_status = status;
+                    }
+                    UiCommand::Connecting(attempt) => {
+                        ctx.player.as_mut().unwrap().connecting_attempt = attempt;
+                    }
+                    UiCommand::PlaybackStalled => {
+                        tracing::warn!("Playback stalled");
+                        let player = ctx.player.as_mut().unwrap();
+                        // The audio thread already stopped on its own.
+                        player.track_state = TrackState::Stopped;
+                        if let Some(track) = &player.selected_track {
+                            player.errored_tracks.insert(track.path());
+                        }
+                    }
+                    UiCommand::TrackFormat(track_format) => {
+                        ctx.player.as_mut().unwrap().track_format = Some(track_format);
+                    }
+                    UiCommand::StreamInfo(stream_info) => {
+                        let player = ctx.player.as_mut().unwrap();
+                        // Only sent again for the same track when its format changed mid-stream.
+                        if player
+                            .stream_info
+                            .is_some_and(|previous| previous.source != stream_info.source)
+                        {
+                            player.stream_changed = true;
+                        }
+                        player.stream_info = Some(stream_info);
+                    }
+                    UiCommand::ScopeBuffer(scope_buffer) => {
+                        tracing::info!(
+                            "Scope buffer resized to {} samples at {}Hz",
+                            scope_buffer.len,
+                            scope_buffer.sample_rate
+                        );
+                        ctx.played_audio_buffer = Some(scope_buffer.consumer);
+                        ctx.temp_buf = Some(vec![0.0f32; scope_buffer.len]);
+                        let mode = ctx.scope.as_ref().map(|scope| scope.mode).unwrap_or_default();
+                        ctx.scope = Some(Scope {
+                            mode,
+                            sample_rate: scope_buffer.sample_rate,
+                            ..Scope::with_len(scope_buffer.len / scope_buffer.channels)
+                        });
+                        ctx.scope_channels = scope_buffer.channels;
+                        ctx.loudness
+                            .set_format(scope_buffer.sample_rate, scope_buffer.channels);
+                        ctx.dynamic_range
+                            .set_format(scope_buffer.sample_rate, scope_buffer.channels);
+                        ctx.loudness_meter
+                            .set_format(scope_buffer.sample_rate, scope_buffer.channels);
+                    }
+                    UiCommand::OutputHost(host_name) => {
+                        ctx.player.as_mut().unwrap().output_host = Some(host_name);
+                    }
+                    UiCommand::OutputDevice(device) => {
+                        ctx.switch_output_device(device);
+                    }
+                    UiCommand::Playback(event) => {
+                        if let PlaybackEvent::Seeked(_) = event {
+                            ctx.player.as_mut().unwrap().seek_finished();
+                            ctx.media_controls.seeked();
+                        }
+                        ctx.measure_dynamic_range(&event);
+                        ctx.measure_loudness(&event);
+                        ctx.notify_observers(event);
+                    }
                     UiCommand::AudioFinished => {
                         tracing::info!("Track finished, getting next...");
-
-                        ctx.player
-                            .as_mut()
-                            .unwrap()
-                            .next(&ctx.playlists[(ctx.current_playlist_idx).unwrap()]);
+                        ctx.advance_playback(None);
+                    }
+                    UiCommand::ContinuedWith(path) => {
+                        tracing::info!("Track finished, continued with {:?}", path);
+                        ctx.continued_with(path);
                     } //_ => {}
                 }
             }
@@ -69,7 +346,9 @@ impl AppComponent for PlayerComponent {
             // Time Slider
             // TODO - use custom_formatter to maybe turn the duration/timestamp into a
             // hr:min:seconds:ms display?
-            let time_slider = ui.add(
+            // There's nowhere to seek to in a stream of unknown length.
+            let time_slider = ui.add_enabled(
+                duration > 0,
                 eframe::egui::Slider::new(&mut seek_to_timestamp, 0..=duration)
                     .logarithmic(false)
                     .show_value(false)
@@ -78,42 +357,177 @@ impl AppComponent for PlayerComponent {
                     .handle_shape(HandleShape::Rect { aspect_ratio: 0.5 }),
             );
 
-            ctx.player
-                .as_mut()
-                .unwrap()
-                .set_seek_to_timestamp(seek_to_timestamp);
+            // Follows the handle while it's dragged, the seek only happens once it's let go.
+            let player = ctx.player.as_mut().unwrap();
+            player.set_seek_to_timestamp(seek_to_timestamp);
+            if duration > 0 {
+                ui.monospace(format!(
+                    "{} / {}",
+                    player.format_timestamp(seek_to_timestamp),
+                    player.format_timestamp(duration)
+                ));
+            }
+
+            if time_slider.drag_stopped() || time_slider.clicked() {
+                let result = ctx.player.as_mut().unwrap().seek_to(seek_to_timestamp);
+                ctx.check_player(result);
+            }
 
-            if time_slider.drag_stopped() {
-                ctx.player.as_mut().unwrap().seek_to(seek_to_timestamp);
+            if play_btn.clicked() {
+                ctx.play();
             }
 
             if let Some(_selected_track) = &ctx.player.as_mut().unwrap().selected_track {
                 if stop_btn.clicked() {
-                    ctx.player.as_mut().unwrap().stop();
-                }
-
-                if play_btn.clicked() {
-                    ctx.player.as_mut().unwrap().play();
+                    let result = ctx.player.as_mut().unwrap().stop();
+                    ctx.check_player(result);
                 }
 
                 if pause_btn.clicked() {
-                    ctx.player.as_mut().unwrap().pause();
+                    let result = ctx.player.as_mut().unwrap().pause();
+                    ctx.check_player(result);
                 }
 
                 if prev_btn.clicked() {
-                    ctx.player
+                    ctx.run_action(Action::Previous);
+                }
+
+                if next_btn.clicked() {
+                    ctx.run_action(Action::Next);
+                }
+
+                if skip_backward_btn.clicked() {
+                    let result = ctx
+                        .player
                         .as_mut()
                         .unwrap()
-                        .previous(&ctx.playlists[(ctx.current_playlist_idx).unwrap()]);
+                        .seek_by(-(ctx.settings.skip_backward_secs as f64));
+                    ctx.check_player(result);
                 }
 
-                if next_btn.clicked() {
-                    ctx.player
+                if skip_forward_btn.clicked() {
+                    let result = ctx
+                        .player
                         .as_mut()
                         .unwrap()
-                        .next(&ctx.playlists[(ctx.current_playlist_idx).unwrap()]);
+                        .seek_by(ctx.settings.skip_forward_secs as f64);
+                    ctx.check_player(result);
+                }
+
+                if add_bookmark_btn.clicked() {
+                    let player = ctx.player.as_ref().unwrap();
+                    let path = player.selected_track.as_ref().unwrap().path();
+                    let timestamp = player.seek_to_timestamp;
+                    let name = format!("Bookmark at {}", player.format_timestamp(timestamp));
+
+                    ctx.bookmarks.add(path, Bookmark { name, timestamp });
+                    ctx.is_bookmarks_open = true;
+                }
+
+                if prev_chapter_btn.clicked() {
+                    ctx.run_action(Action::PreviousChapter);
+                }
+
+                if next_chapter_btn.clicked() {
+                    ctx.run_action(Action::NextChapter);
                 }
             }
+
+            let player = ctx.player.as_ref().unwrap();
+            if let Some(attempt) = player.connecting_attempt {
+                ui.add(eframe::egui::Spinner::new());
+                ui.weak(format!(
+                    "Connecting… (retry {attempt} of {})",
+                    player.max_open_retries
+                ))
+                .on_hover_text("The track's location isn't responding, it is skipped if it stays that way");
+            } else if player.is_loading {
+                ui.add(eframe::egui::Spinner::new())
+                    .on_hover_text("Loading track");
+            } else if player.playback_status == PlaybackStatus::Buffering {
+                ui.add(eframe::egui::Spinner::new());
+                ui.weak("Buffering…")
+                    .on_hover_text("Waiting for the track's audio to arrive");
+            } else if player.playback_status == PlaybackStatus::Error {
+                ui.colored_label(eframe::egui::Color32::RED, "⚠ Playback error")
+                    .on_hover_text("The track couldn't be played, see the log for details");
+            }
+
+            if let Some(chapter) = ctx.player.as_ref().unwrap().current_chapter() {
+                ui.label(format!(
+                    "Chapter: {}",
+                    chapter.title.as_deref().unwrap_or("untitled")
+                ));
+            }
+
+            if ctx.settings.audition_enabled || ctx.preview.is_some() {
+                Self::add_audition(ctx, ui);
+            }
         });
     }
 }
+
+// How much of the next track an audition plays.
+const AUDITION_LENGTH: std::time::Duration = std::time::Duration::from_secs(10);
+
+impl PlayerComponent {
+    fn add_audition(ctx: &mut App, ui: &mut eframe::egui::Ui) {
+        if ctx
+            .preview
+            .as_ref()
+            .is_some_and(|preview| preview.is_finished())
+        {
+            ctx.preview = None;
+            let result = ctx.player.as_mut().unwrap().set_ducked(false);
+            ctx.check_player(result);
+        }
+
+        ui.separator();
+
+        match &ctx.preview {
+            Some(preview) => {
+                let name = preview
+                    .path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                ui.colored_label(ui.visuals().warn_fg_color, format!("🎧 Previewing {name}"));
+
+                if ui.button("Stop preview").clicked() {
+                    preview.stop();
+                }
+            }
+            None => {
+                let upcoming_track = upcoming_track(ctx);
+                let audition_btn = ui
+                    .add_enabled(
+                        upcoming_track.is_some(),
+                        eframe::egui::Button::new("🎧 Audition next"),
+                    )
+                    .on_hover_text("Play a few seconds of the next track over this one");
+
+                if let (true, Some(track)) = (audition_btn.clicked(), upcoming_track) {
+                    ctx.preview = Some(Preview::start(
+                        track.path(),
+                        AUDITION_LENGTH,
+                        ctx.settings.output_host.clone(),
+                    ));
+                    let result = ctx.player.as_mut().unwrap().set_ducked(true);
+                    ctx.check_player(result);
+                }
+            }
+        }
+    }
+}
+
+// What would play once the current track finishes: the queue first, then the playlist.
+fn upcoming_track(ctx: &App) -> Option<LibraryItem> {
+    let player = ctx.player.as_ref().unwrap();
+    if let Some(queued_track) = player.queue.front() {
+        return Some(queued_track.clone());
+    }
+
+    let playlist = &ctx.playlists[ctx.current_playlist_idx?];
+    let position = playlist.get_pos(player.selected_track.as_ref()?)?;
+    playlist.tracks.get(position + 1).cloned()
+}
@@ -1,6 +1,10 @@
 use super::AppComponent;
 use crate::app::App;
 use eframe::egui;
+use std::time::Duration;
+
+// How long after the table was scrolled by hand it stops following the playing track.
+const FOLLOW_PAUSE: Duration = Duration::from_secs(10);
 
 pub struct PlaylistTable;
 
@@ -8,7 +12,7 @@ impl AppComponent for PlaylistTable {
     type Context = App;
 
     fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
-        if let Some(current_playlist_idx) = &mut ctx.current_playlist_idx {
+        if let Some(current_playlist_idx) = ctx.current_playlist_idx {
             egui::Grid::new("playlist")
                 .striped(true)
                 .min_col_width(25.)
@@ -16,27 +20,61 @@ impl AppComponent for PlaylistTable {
                     // Header
                     ui.label("Playing");
                     ui.label("#");
-                    ui.label("Title");
+                    let title_header = ui.add(
+                        egui::Label::new(if ctx.show_playlist_paths {
+                            "Path"
+                        } else {
+                            "Title"
+                        })
+                        .sense(egui::Sense::click()),
+                    );
+                    title_header.context_menu(|ui| {
+                        if ui
+                            .checkbox(&mut ctx.show_playlist_paths, "Show file paths")
+                            .clicked()
+                        {
+                            ui.close_menu();
+                        }
+                    });
                     ui.label("Artist");
                     ui.label("Album");
                     ui.label("Genre");
+                    let dynamic_range_header = ui
+                        .add(egui::Label::new("DR").sense(egui::Sense::click()))
+                        .on_hover_text("Dynamic range. Click to sort, most dynamic first.");
+                    if dynamic_range_header.clicked() {
+                        // Stable, so tracks with the same or no DR keep their order.
+                        ctx.playlists[current_playlist_idx]
+                            .tracks
+                            .sort_by_key(|track| std::cmp::Reverse(track.dynamic_range()));
+                    }
                     ui.end_row();
 
                     // Rows
-                    for (iter_idx, track) in ctx.playlists[*current_playlist_idx]
+                    let mut selected_track = None;
+                    let mut removed_idx = None;
+                    let mut moved = None;
+                    let mut play_result = None;
+                    for (iter_idx, track) in ctx.playlists[current_playlist_idx]
                         .tracks
                         .iter()
                         .enumerate()
+                        .filter(|(_, track)| track.matches_search(&ctx.playlist_search))
                     {
-                        if let Some(selected_track) = &ctx.player.as_ref().unwrap().selected_track {
-                            if selected_track == track {
-                                ui.label("▶".to_string());
-                            } else {
-                                ui.label("-".to_string());
-                            }
+                        // Filled in once the row is laid out, to highlight it behind its cells.
+                        let row_background = ui.painter().add(egui::Shape::Noop);
+
+                        let player = ctx.player.as_ref().unwrap();
+                        let is_playing = player.selected_track.as_ref() == Some(track);
+                        let first_cell = if player.errored_tracks.contains(&track.path()) {
+                            ui.label("⚠").on_hover_text(
+                                "This file couldn't be played. It may be missing, empty or truncated.",
+                            )
+                        } else if is_playing {
+                            ui.label("▶".to_string())
                         } else {
-                            ui.label("-".to_string());
-                        }
+                            ui.label("-".to_string())
+                        };
 
                         if let Some(track_number) = &track.track_number() {
                             ui.label(track_number.to_string());
@@ -44,32 +82,125 @@ impl AppComponent for PlaylistTable {
                             ui.label((iter_idx + 1).to_string());
                         }
 
-                        let title_label = ui.add(
-                            egui::Label::new(track.title().unwrap_or("unknown title".to_string()))
-                                .sense(egui::Sense::click()),
-                        );
-
+                        let title = if ctx.show_playlist_paths {
+                            track.path().display().to_string()
+                        } else {
+                            track.title().unwrap_or("unknown title".to_string())
+                        };
+                        ui.label(title);
                         ui.label(track.artist().unwrap_or("unknown artist".to_string()));
                         ui.label(track.album().unwrap_or("unknown album".to_string()));
                         ui.label(track.genre().unwrap_or("unknown genre".to_string()));
+                        let last_cell = ui.label(
+                            track
+                                .dynamic_range()
+                                .map(|dynamic_range| dynamic_range.to_string())
+                                .unwrap_or_default(),
+                        );
+
+                        // One response over the whole row, on top of its cells, so clicks
+                        // anywhere on it count. Rows take keyboard focus too: tabbing onto one
+                        // selects it and Enter plays it.
+                        let row_rect = first_cell.rect.union(last_cell.rect);
+                        let row = ui.interact(
+                            row_rect,
+                            ui.id().with(("playlist_row", iter_idx)),
+                            egui::Sense::click_and_drag(),
+                        );
+
+                        let is_selected =
+                            ctx.playlists[current_playlist_idx].selected.as_ref() == Some(track);
+                        let fill = if is_selected {
+                            Some(ui.visuals().selection.bg_fill)
+                        } else if row.hovered() || row.has_focus() {
+                            Some(ui.visuals().widgets.hovered.weak_bg_fill)
+                        } else if is_playing {
+                            Some(ui.visuals().selection.bg_fill.gamma_multiply(0.35))
+                        } else {
+                            None
+                        };
+                        if let Some(fill) = fill {
+                            ui.painter().set(
+                                row_background,
+                                egui::Shape::rect_filled(row_rect, 0.0, fill),
+                            );
+                        }
+
+                        // Brought into view when another track starts, unless the list was just
+                        // scrolled by hand.
+                        if is_playing && ctx.scrolled_to_track.as_ref() != Some(&track.path()) {
+                            ctx.scrolled_to_track = Some(track.path());
+                            let is_scrolled_by_hand = ctx
+                                .playlist_scrolled_at
+                                .is_some_and(|scrolled_at| scrolled_at.elapsed() < FOLLOW_PAUSE);
+                            if !is_scrolled_by_hand {
+                                ui.scroll_to_rect(row_rect, None);
+                            }
+                        }
+
+                        // Dragging a row onto another one moves the track there.
+                        row.dnd_set_drag_payload(iter_idx);
+                        if let Some(from) = row.dnd_hover_payload::<usize>() {
+                            let y = if *from < iter_idx {
+                                row_rect.bottom()
+                            } else {
+                                row_rect.top()
+                            };
+                            ui.painter().hline(
+                                row_rect.x_range(),
+                                y,
+                                ui.visuals().selection.stroke,
+                            );
+                        }
+                        if let Some(from) = row.dnd_release_payload::<usize>() {
+                            moved = Some((*from, iter_idx));
+                        }
 
-                        // Temporary hack because I don't yet know how to treat an entire Row
-                        // as a response
-                        if title_label.double_clicked() {
-                            //ctx.player.as_mut().unwrap().selected_track = Some(track.clone());
-                            ctx.player
-                                .as_mut()
-                                .unwrap()
-                                .select_track(Some(track.clone()));
-                            ctx.player.as_mut().unwrap().play();
+                        let is_play_requested = row.double_clicked()
+                            || (row.has_focus()
+                                && ui.input(|i| i.key_pressed(egui::Key::Enter)));
+                        if is_play_requested {
+                            // Starting playback by hand begins a new chain of linked playlists.
+                            ctx.played_playlists.clear();
+                            let player = ctx.player.as_mut().unwrap();
+                            play_result = Some(
+                                player
+                                    .select_track(Some(track.clone()))
+                                    .and_then(|()| player.play()),
+                            );
                         }
 
-                        if title_label.clicked() {
-                            ctx.player.as_mut().unwrap().selected_track = Some(track.clone());
+                        // Only highlights the row, the player keeps the track it has loaded.
+                        if row.clicked() || row.gained_focus() {
+                            selected_track = Some(track.clone());
                         }
 
+                        row.context_menu(|ui| {
+                            if ui.button("Add to queue").clicked() {
+                                ctx.player.as_mut().unwrap().enqueue(track.clone());
+                                ui.close_menu();
+                            }
+                            if ui.button("Remove from playlist").clicked() {
+                                removed_idx = Some(iter_idx);
+                                ui.close_menu();
+                            }
+                        });
+
                         ui.end_row();
                     }
+
+                    if let Some(result) = play_result {
+                        ctx.check_player(result);
+                    }
+                    if let Some(track) = selected_track {
+                        ctx.playlists[current_playlist_idx].selected = Some(track);
+                    }
+                    if let Some((from, to)) = moved {
+                        ctx.move_in_playlist(current_playlist_idx, from, to);
+                    }
+                    if let Some(idx) = removed_idx {
+                        ctx.remove_from_playlist(current_playlist_idx, idx);
+                    }
                 });
         }
     }
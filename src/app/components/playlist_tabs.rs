@@ -1,4 +1,5 @@
 use super::AppComponent;
+use crate::app::playlist::tab_order;
 use crate::app::App;
 use eframe::egui;
 
@@ -9,40 +10,160 @@ impl AppComponent for PlaylistTabs {
 
     fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
         ui.horizontal_wrapped(|ui| {
-            for (idx, playlist) in ctx.playlists.iter().enumerate() {
-                let playlist_tab = ui.add(
-                    egui::Label::new(playlist.get_name().unwrap()).sense(egui::Sense::click()),
-                );
+            // (id, name) of every playlist, for picking the one to continue with.
+            let playlist_names = ctx
+                .playlists
+                .iter()
+                .map(|playlist| (playlist.id(), playlist.get_name().unwrap()))
+                .collect::<Vec<_>>();
+
+            let output_host = ctx.settings.output_host.clone();
+
+            for idx in tab_order(&ctx.playlists) {
+                let playlist = &mut ctx.playlists[idx];
+                let next_playlist_name = playlist.next_playlist.and_then(|next_id| {
+                    playlist_names
+                        .iter()
+                        .find(|(id, _)| *id == next_id)
+                        .map(|(_, name)| name.clone())
+                });
+
+                // Typing the new name in place of the tab. Enter or clicking elsewhere keeps it,
+                // Esc cancels.
+                if let Some((_, name)) = ctx
+                    .renaming_playlist
+                    .as_mut()
+                    .filter(|(id, _)| *id == playlist.id())
+                {
+                    let response = ui.add(
+                        egui::TextEdit::singleline(name)
+                            .id(rename_id(playlist.id()))
+                            .desired_width(120.0),
+                    );
+                    if response.lost_focus() {
+                        let is_cancelled = ui.input(|i| i.key_pressed(egui::Key::Escape));
+                        if !is_cancelled && !name.trim().is_empty() {
+                            playlist.set_name(name.trim().to_string());
+                        }
+                        ctx.renaming_playlist = None;
+                    }
+                    continue;
+                }
+
+                let mut tab_name = playlist.get_name().unwrap();
+                if playlist.pinned {
+                    tab_name = format!("📌 {tab_name}");
+                }
+
+                let mut playlist_tab =
+                    ui.add(egui::Label::new(tab_name).sense(egui::Sense::click()));
+
+                if let Some(next_playlist_name) = &next_playlist_name {
+                    playlist_tab =
+                        playlist_tab.on_hover_text(format!("Continues with {next_playlist_name}"));
+                }
 
                 if playlist_tab.clicked() {
                     ctx.current_playlist_idx = Some(idx);
                 }
 
-                // TODO - make this bring up a context menu, however just delete for
-                // now.
-                if playlist_tab.clicked_by(egui::PointerButton::Secondary) {
+                let mut is_rename_requested = playlist_tab.double_clicked();
+
+                if ui
+                    .add(egui::Button::new("✖").small().frame(false))
+                    .on_hover_text("Delete playlist")
+                    .clicked()
+                {
                     ctx.playlist_idx_to_remove = Some(idx);
                 }
-            }
 
-            if let Some(idx) = ctx.playlist_idx_to_remove {
-                ctx.playlist_idx_to_remove = None;
-
-                // Because the current playlist is referenced via index, we need to take
-                // into account that the index may be out of bounds when removing a
-                // playlist. This should be resolved when I figure out how to reference the
-                // actual selected playlist.
-                if let Some(mut current_playlist_idx) = ctx.current_playlist_idx {
-                    if current_playlist_idx == 0 && idx == 0 {
-                        ctx.current_playlist_idx = None;
-                    } else if current_playlist_idx >= idx {
-                        current_playlist_idx -= 1;
-                        ctx.current_playlist_idx = Some(current_playlist_idx);
+                playlist_tab.context_menu(|ui| {
+                    ui.menu_button("Continue with", |ui| {
+                        if ui
+                            .radio(playlist.next_playlist.is_none(), "Nothing (stop)")
+                            .clicked()
+                        {
+                            playlist.next_playlist = None;
+                            ui.close_menu();
+                        }
+
+                        for (id, name) in playlist_names.iter() {
+                            if ui
+                                .radio(playlist.next_playlist == Some(*id), name)
+                                .clicked()
+                            {
+                                playlist.next_playlist = Some(*id);
+                                ui.close_menu();
+                            }
+                        }
+                    });
+
+                    let play_on = ui.menu_button("Play on", |ui| {
+                        if ui
+                            .radio(playlist.output_device.is_none(), "Default device")
+                            .clicked()
+                        {
+                            playlist.output_device = None;
+                            ui.close_menu();
+                        }
+
+                        let devices = ctx.output_devices.get_or_insert_with(|| {
+                            crate::output::available_devices(output_host.as_deref())
+                        });
+                        for device_name in devices.iter() {
+                            let is_selected =
+                                playlist.output_device.as_ref() == Some(device_name);
+                            if ui.radio(is_selected, device_name).clicked() {
+                                playlist.output_device = Some(device_name.clone());
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                    // Listed again the next time it opens, in case a device was plugged in.
+                    if play_on.inner.is_none() {
+                        ctx.output_devices = None;
                     }
+                    play_on.response.on_hover_text("Falls back to the default device when this one isn't connected. Applies from the next track on.");
+
+                    if ui.button("Rename").clicked() {
+                        is_rename_requested = true;
+                        ui.close_menu();
+                    }
+
+                    let pin_label = if playlist.pinned { "Unpin" } else { "Pin" };
+                    if ui.button(pin_label).clicked() {
+                        playlist.pinned = !playlist.pinned;
+                        ui.close_menu();
+                    }
+
+                    ui.separator();
+
+                    if ui.button("Delete").clicked() {
+                        ctx.playlist_idx_to_remove = Some(idx);
+                        ui.close_menu();
+                    }
+                });
+
+                if is_rename_requested {
+                    ctx.renaming_playlist = Some((playlist.id(), playlist.get_name().unwrap()));
+                    ui.memory_mut(|memory| memory.request_focus(rename_id(playlist.id())));
                 }
 
-                ctx.playlists.remove(idx);
+                ui.separator();
+            }
+
+            if ui.button("+").on_hover_text("New playlist").clicked() {
+                ctx.new_playlist();
+            }
+
+            // Removed once the tabs are drawn, they borrow the playlists until then.
+            if let Some(idx) = ctx.playlist_idx_to_remove.take() {
+                ctx.remove_playlist(idx);
             }
         });
     }
 }
+
+fn rename_id(playlist_id: u64) -> egui::Id {
+    egui::Id::new(("rename_playlist", playlist_id))
+}
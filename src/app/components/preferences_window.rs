@@ -0,0 +1,680 @@
+use super::AppComponent;
+use crate::app::keybindings::{Action, KeyBinding, Keybindings};
+use crate::app::settings::{
+    AlbumAction, AudioThreadPriority, NormalizationMode, QueueEndBehavior, RepeatMode,
+    SeekPrecision, UnreadableTags,
+};
+use crate::app::spelling;
+use crate::app::App;
+use crate::dsp::{FadeCurve, LimiterMode, PanLaw};
+use itertools::Itertools;
+
+pub struct PreferencesWindow;
+
+impl AppComponent for PreferencesWindow {
+    type Context = App;
+
+    fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        if !ctx.is_preferences_open {
+            return;
+        }
+
+        let mut is_open = ctx.is_preferences_open;
+        // Edit a copy so changes can be pushed to the audio thread through `apply_settings`.
+        let mut settings = ctx.settings.clone();
+
+        eframe::egui::Window::new("Preferences")
+            .open(&mut is_open)
+            .default_width(360.0)
+            .resizable(true)
+            .show(ui.ctx(), |ui| {
+                ui.collapsing("Playback", |ui| {
+                    eframe::egui::Grid::new("preferences_playback")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            ui.label("When the queue ends");
+                            eframe::egui::ComboBox::from_id_source("queue_end_behavior")
+                                .selected_text(settings.queue_end_behavior.to_string())
+                                .show_ui(ui, |ui| {
+                                    for behavior in
+                                        [QueueEndBehavior::ContinuePlaylist, QueueEndBehavior::Stop]
+                                    {
+                                        ui.selectable_value(
+                                            &mut settings.queue_end_behavior,
+                                            behavior,
+                                            behavior.to_string(),
+                                        );
+                                    }
+                                });
+                            ui.end_row();
+
+                            ui.label("Count a play after").on_hover_text(
+                                "Whichever comes first. Used for play counts and history.",
+                            );
+                            ui.horizontal(|ui| {
+                                ui.add(
+                                    eframe::egui::DragValue::new(
+                                        &mut settings.play_threshold.percent,
+                                    )
+                                    .range(0.0..=100.0)
+                                    .suffix("%"),
+                                );
+                                ui.label("or");
+                                ui.add(
+                                    eframe::egui::DragValue::new(
+                                        &mut settings.play_threshold.seconds,
+                                    )
+                                    .suffix("s"),
+                                );
+                            });
+                            ui.end_row();
+
+                            ui.label("Skip buttons");
+                            ui.horizontal(|ui| {
+                                ui.label("Back");
+                                ui.add(
+                                    eframe::egui::DragValue::new(&mut settings.skip_backward_secs)
+                                        .range(1..=600)
+                                        .suffix("s"),
+                                );
+                                ui.label("Forward");
+                                ui.add(
+                                    eframe::egui::DragValue::new(&mut settings.skip_forward_secs)
+                                        .range(1..=600)
+                                        .suffix("s"),
+                                );
+                            });
+                            ui.end_row();
+
+                            ui.label("Seeking").on_hover_text(
+                                "Fast seeking may land on a keyframe before the chosen position.",
+                            );
+                            eframe::egui::ComboBox::from_id_source("seek_precision")
+                                .selected_text(settings.seek_precision.to_string())
+                                .show_ui(ui, |ui| {
+                                    for seek_precision in
+                                        [SeekPrecision::Accurate, SeekPrecision::Coarse]
+                                    {
+                                        ui.selectable_value(
+                                            &mut settings.seek_precision,
+                                            seek_precision,
+                                            seek_precision.to_string(),
+                                        );
+                                    }
+                                });
+                            ui.end_row();
+
+                            ui.label("Play button");
+                            ui.checkbox(
+                                &mut settings.play_starts_playlist,
+                                "Start the playlist when nothing is selected",
+                            )
+                            .on_hover_text("Queued tracks are played first");
+                            ui.end_row();
+
+                            ui.label("On startup");
+                            ui.checkbox(
+                                &mut settings.resume_autoplay,
+                                "Resume playing the last track",
+                            )
+                            .on_hover_text("Otherwise it's loaded paused at the position it was left at");
+                            ui.end_row();
+
+                            if ctx.tray.is_active() {
+                                ui.label("Closing the window");
+                                ui.checkbox(&mut settings.close_to_tray, "Keep playing in the tray")
+                                    .on_hover_text("Quit from the tray icon's menu");
+                                ui.end_row();
+                            }
+
+                            ui.label("Audition");
+                            ui.checkbox(&mut settings.audition_enabled, "Preview the next track")
+                                .on_hover_text(
+                                    "Plays a few seconds of the next track on a second stream, with the current track turned down",
+                                );
+                            ui.end_row();
+
+                            ui.label("Repeat").on_hover_text(
+                                "Repeat all starts over when a playlist, or a chain of linked playlists, ends",
+                            );
+                            eframe::egui::ComboBox::from_id_source("repeat")
+                                .selected_text(settings.repeat.to_string())
+                                .show_ui(ui, |ui| {
+                                    for repeat in [RepeatMode::Off, RepeatMode::One, RepeatMode::All]
+                                    {
+                                        ui.selectable_value(
+                                            &mut settings.repeat,
+                                            repeat,
+                                            repeat.to_string(),
+                                        );
+                                    }
+                                });
+                            ui.end_row();
+
+                            ui.label("Gap between tracks").on_hover_text(
+                                "Silence before the next track starts on its own. Skipping tracks isn't delayed.",
+                            );
+                            ui.add(
+                                eframe::egui::DragValue::new(&mut settings.track_gap_ms)
+                                    .range(0..=10_000)
+                                    .speed(10)
+                                    .suffix(" ms"),
+                            );
+                            ui.end_row();
+
+                            ui.label("Crossfade").on_hover_text(
+                                "Fades the next track in over the end of the one playing, instead of the gap. 0 turns it off.",
+                            );
+                            ui.add(
+                                eframe::egui::DragValue::new(&mut settings.crossfade_secs)
+                                    .range(0.0..=12.0)
+                                    .speed(0.1)
+                                    .max_decimals(1)
+                                    .suffix(" s"),
+                            );
+                            ui.end_row();
+
+                            ui.label("Crossfade curve")
+                                .on_hover_text("Shape of the fades where the tracks overlap");
+                            eframe::egui::ComboBox::from_id_source("crossfade_curve")
+                                .selected_text(settings.crossfade_curve.to_string())
+                                .show_ui(ui, |ui| {
+                                    for fade_curve in [
+                                        FadeCurve::EqualPower,
+                                        FadeCurve::Linear,
+                                        FadeCurve::Logarithmic,
+                                        FadeCurve::SCurve,
+                                    ] {
+                                        ui.selectable_value(
+                                            &mut settings.crossfade_curve,
+                                            fade_curve,
+                                            fade_curve.to_string(),
+                                        );
+                                    }
+                                });
+                            ui.end_row();
+                        });
+                });
+
+                ui.collapsing("Audio", |ui| {
+                    eframe::egui::Grid::new("preferences_audio")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            ui.label("Balance pan law");
+                            eframe::egui::ComboBox::from_id_source("pan_law")
+                                .selected_text(settings.pan_law.to_string())
+                                .show_ui(ui, |ui| {
+                                    for pan_law in
+                                        [PanLaw::EqualPower, PanLaw::Linear, PanLaw::Minus6Db]
+                                    {
+                                        ui.selectable_value(
+                                            &mut settings.pan_law,
+                                            pan_law,
+                                            pan_law.to_string(),
+                                        );
+                                    }
+                                });
+                            ui.end_row();
+
+                            ui.label("Loudness normalization").on_hover_text(
+                                "Plays tracks at the loudness their ReplayGain tags ask for. Tracks without tags are measured the first time they play through.",
+                            );
+                            eframe::egui::ComboBox::from_id_source("normalization")
+                                .selected_text(settings.normalization.to_string())
+                                .show_ui(ui, |ui| {
+                                    for normalization in [
+                                        NormalizationMode::Off,
+                                        NormalizationMode::Track,
+                                        NormalizationMode::Album,
+                                    ] {
+                                        ui.selectable_value(
+                                            &mut settings.normalization,
+                                            normalization,
+                                            normalization.to_string(),
+                                        );
+                                    }
+                                });
+                            ui.end_row();
+
+                            ui.label("Fade curve").on_hover_text(
+                                "Shape of the short fade in when playback starts or resumes",
+                            );
+                            eframe::egui::ComboBox::from_id_source("fade_curve")
+                                .selected_text(settings.fade_curve.to_string())
+                                .show_ui(ui, |ui| {
+                                    for fade_curve in [
+                                        FadeCurve::Linear,
+                                        FadeCurve::Logarithmic,
+                                        FadeCurve::EqualPower,
+                                        FadeCurve::SCurve,
+                                    ] {
+                                        ui.selectable_value(
+                                            &mut settings.fade_curve,
+                                            fade_curve,
+                                            fade_curve.to_string(),
+                                        );
+                                    }
+                                });
+                            ui.end_row();
+
+                            ui.label("Limiter").on_hover_text(
+                                "Keeps peaks from clipping when the balance or other processing boosts the signal",
+                            );
+                            ui.vertical(|ui| {
+                                eframe::egui::ComboBox::from_id_source("limiter_mode")
+                                    .selected_text(settings.limiter.mode.to_string())
+                                    .show_ui(ui, |ui| {
+                                        for mode in
+                                            [LimiterMode::Auto, LimiterMode::On, LimiterMode::Off]
+                                        {
+                                            ui.selectable_value(
+                                                &mut settings.limiter.mode,
+                                                mode,
+                                                mode.to_string(),
+                                            );
+                                        }
+                                    });
+                                ui.add_enabled_ui(settings.limiter.mode != LimiterMode::Off, |ui| {
+                                    ui.horizontal(|ui| {
+                                        ui.label("Threshold");
+                                        ui.add(
+                                            eframe::egui::DragValue::new(
+                                                &mut settings.limiter.threshold_db,
+                                            )
+                                            .range(-12.0..=0.0)
+                                            .speed(0.1)
+                                            .suffix(" dB"),
+                                        );
+                                        ui.label("Release");
+                                        ui.add(
+                                            eframe::egui::DragValue::new(
+                                                &mut settings.limiter.release_ms,
+                                            )
+                                            .range(1.0..=2000.0)
+                                            .suffix(" ms"),
+                                        );
+                                    });
+                                });
+                            });
+                            ui.end_row();
+
+                            ui.label("Audio thread priority").on_hover_text(
+                                "A higher priority helps avoid dropouts on a busy system. It may \
+                                 need extra permissions, on Linux CAP_SYS_NICE or an rtprio \
+                                 limit, otherwise the normal priority is kept. Lowering it takes \
+                                 effect after a restart.",
+                            );
+                            eframe::egui::ComboBox::from_id_source("audio_thread_priority")
+                                .selected_text(settings.audio_thread_priority.to_string())
+                                .show_ui(ui, |ui| {
+                                    for priority in [
+                                        AudioThreadPriority::Normal,
+                                        AudioThreadPriority::High,
+                                        AudioThreadPriority::Highest,
+                                    ] {
+                                        ui.selectable_value(
+                                            &mut settings.audio_thread_priority,
+                                            priority,
+                                            priority.to_string(),
+                                        );
+                                    }
+                                });
+                            ui.end_row();
+
+                            ui.label("Night mode");
+                            ui.vertical(|ui| {
+                                ui.checkbox(&mut settings.night_mode.enabled, "Enabled");
+                                ui.add_enabled_ui(settings.night_mode.enabled, |ui| {
+                                    ui.horizontal(|ui| {
+                                        ui.label("Fade in over");
+                                        ui.add(
+                                            eframe::egui::DragValue::new(
+                                                &mut settings.night_mode.ramp_secs,
+                                            )
+                                            .range(0.0..=60.0)
+                                            .speed(0.1)
+                                            .suffix("s"),
+                                        );
+                                    })
+                                    .response
+                                    .on_hover_text("Starts quietly whenever playback starts or resumes");
+                                    ui.checkbox(
+                                        &mut settings.night_mode.compress,
+                                        "Compress loud peaks",
+                                    );
+                                });
+                            });
+                            ui.end_row();
+
+                            ui.label("Output backend");
+                            ui.vertical(|ui| {
+                                let default_host = crate::output::default_host();
+                                let default_label = format!("Default ({default_host})");
+                                eframe::egui::ComboBox::from_id_source("output_host")
+                                    .selected_text(
+                                        settings.output_host.as_deref().unwrap_or(&default_label),
+                                    )
+                                    .show_ui(ui, |ui| {
+                                        ui.selectable_value(
+                                            &mut settings.output_host,
+                                            None,
+                                            &default_label,
+                                        );
+                                        for host_name in crate::output::available_hosts() {
+                                            ui.selectable_value(
+                                                &mut settings.output_host,
+                                                Some(host_name.to_string()),
+                                                host_name,
+                                            );
+                                        }
+                                    });
+
+                                if let Some(active_host) = &ctx.player.as_ref().unwrap().output_host
+                                {
+                                    ui.weak(format!("Active: {active_host}"));
+                                }
+                            });
+                            ui.end_row();
+
+                            ui.label("Decoding");
+                            ui.vertical(|ui| {
+                                ui.checkbox(&mut settings.gapless, "Gapless playback")
+                                    .on_hover_text("Trim encoder delay and padding");
+                                ui.checkbox(&mut settings.verify_decoding, "Verify decoded audio")
+                                    .on_hover_text(
+                                        "Check decoded audio against embedded checksums",
+                                    );
+                            });
+                            ui.end_row();
+                        });
+                });
+
+                ui.collapsing("Library", |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Double-clicking an album");
+                        eframe::egui::ComboBox::from_id_source("album_double_click")
+                            .selected_text(settings.album_double_click.to_string())
+                            .show_ui(ui, |ui| {
+                                for action in [
+                                    AlbumAction::Add,
+                                    AlbumAction::AddAndPlay,
+                                    AlbumAction::ReplaceAndPlay,
+                                ] {
+                                    ui.selectable_value(
+                                        &mut settings.album_double_click,
+                                        action,
+                                        action.to_string(),
+                                    );
+                                }
+                            });
+                    })
+                    .response
+                    .on_hover_text("Ctrl and Shift pick \"Add and play\" and \"Replace playlist and play\" whatever is set here. Tracks are added in disc and track order.");
+
+                    ui.checkbox(
+                        &mut settings.normalize_genres,
+                        "Show numeric ID3 genres like \"(17)\" by name",
+                    )
+                    .on_hover_text("Applies to files imported from now on.");
+
+                    ui.checkbox(
+                        &mut settings.merge_spellings,
+                        "Group albums, artists and genres spelled with different capitalization",
+                    )
+                    .on_hover_text("Files aren't changed. Applies to paths imported from now on.");
+
+                    ui.checkbox(
+                        &mut settings.infer_tags_from_folders,
+                        "Take missing tags from the folder layout",
+                    )
+                    .on_hover_text("Applies to files imported from now on.");
+                    ui.add_enabled_ui(settings.infer_tags_from_folders, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("Layout");
+                            ui.text_edit_singleline(&mut settings.folder_pattern);
+                        })
+                        .response
+                        .on_hover_text(
+                            "Folders leading up to the file, ending with the file name. Use {artist}, {album}, {title}, {track} and {year}.",
+                        );
+                    });
+
+                    ui.horizontal(|ui| {
+                        let mut is_limited = settings.import_max_depth.is_some();
+                        ui.checkbox(&mut is_limited, "Only import folders up to");
+                        let mut max_depth = settings.import_max_depth.unwrap_or(8);
+                        ui.add_enabled(
+                            is_limited,
+                            eframe::egui::DragValue::new(&mut max_depth)
+                                .range(1..=100)
+                                .suffix(" levels deep"),
+                        );
+                        settings.import_max_depth = is_limited.then_some(max_depth);
+                    })
+                    .response
+                    .on_hover_text("Counted from the library path. Applies to paths imported from now on.");
+
+                    ui.horizontal(|ui| {
+                        ui.label("Files with unreadable tags");
+                        eframe::egui::ComboBox::from_id_source("unreadable_tags")
+                            .selected_text(settings.unreadable_tags.to_string())
+                            .show_ui(ui, |ui| {
+                                for unreadable_tags in
+                                    [UnreadableTags::UseFileName, UnreadableTags::Skip]
+                                {
+                                    ui.selectable_value(
+                                        &mut settings.unreadable_tags,
+                                        unreadable_tags,
+                                        unreadable_tags.to_string(),
+                                    );
+                                }
+                            });
+                    })
+                    .response
+                    .on_hover_text("Applies to paths imported from now on. Skipped files are counted in the library paths window.");
+
+                    ui.checkbox(
+                        &mut settings.import_follow_symlinks,
+                        "Follow symbolic links when importing",
+                    )
+                    .on_hover_text("Links looping back on themselves are skipped.");
+
+                    ui.horizontal(|ui| {
+                        ui.label("Default cover").on_hover_text(
+                            "Image shown for tracks without embedded art or a cover.jpg/folder.jpg in their folder. Leave empty for none.",
+                        );
+                        let mut path = settings
+                            .default_cover
+                            .as_ref()
+                            .map(|path| path.display().to_string())
+                            .unwrap_or_default();
+                        if ui.text_edit_singleline(&mut path).changed() {
+                            settings.default_cover =
+                                (!path.is_empty()).then(|| std::path::PathBuf::from(path));
+                        }
+                    });
+
+                    ui.collapsing("Merged spellings", |ui| {
+                        let items = ctx.library.items();
+                        for (field, names) in [
+                            ("Artists", items.iter().filter_map(|item| item.artist()).collect_vec()),
+                            ("Albums", items.iter().filter_map(|item| item.album()).collect_vec()),
+                            ("Genres", items.iter().filter_map(|item| item.genre()).collect_vec()),
+                        ] {
+                            let merged = spelling::merged_spellings(names.iter().map(String::as_str));
+                            if merged.is_empty() {
+                                continue;
+                            }
+
+                            ui.strong(field);
+                            for (name, spellings) in merged {
+                                ui.label(format!("{} ← {}", name, spellings.join(", ")));
+                            }
+                        }
+                    });
+                });
+
+                ui.collapsing("Keyboard", |ui| {
+                    if let Some(action) = ctx.capturing_keybinding {
+                        let pressed = ui.input(|i| {
+                            i.events.iter().find_map(|event| match event {
+                                eframe::egui::Event::Key {
+                                    key,
+                                    pressed: true,
+                                    modifiers,
+                                    ..
+                                } => Some((*key, *modifiers)),
+                                _ => None,
+                            })
+                        });
+
+                        match pressed {
+                            Some((eframe::egui::Key::Escape, _)) => ctx.capturing_keybinding = None,
+                            Some((key, modifiers)) => {
+                                settings
+                                    .keybindings
+                                    .set(action, Some(KeyBinding::from_input(key, modifiers)));
+                                ctx.capturing_keybinding = None;
+                            }
+                            None => {}
+                        }
+                    }
+
+                    eframe::egui::Grid::new("preferences_keyboard")
+                        .num_columns(3)
+                        .show(ui, |ui| {
+                            for action in Action::ALL {
+                                ui.label(action.to_string());
+
+                                ui.horizontal(|ui| {
+                                    let binding_text = if ctx.capturing_keybinding == Some(action) {
+                                        "Press a key…".to_string()
+                                    } else {
+                                        settings
+                                            .keybindings
+                                            .get(action)
+                                            .map(|binding| binding.to_string())
+                                            .unwrap_or("None".to_string())
+                                    };
+
+                                    if ui
+                                        .button(binding_text)
+                                        .on_hover_text("Click, then press the new shortcut. Esc cancels.")
+                                        .clicked()
+                                    {
+                                        ctx.capturing_keybinding = Some(action);
+                                    }
+
+                                    if ui.small_button("✖").on_hover_text("Remove shortcut").clicked() {
+                                        settings.keybindings.set(action, None);
+                                    }
+                                });
+
+                                let conflicts = settings.keybindings.conflicts(action);
+                                if conflicts.is_empty() {
+                                    ui.label("");
+                                } else {
+                                    ui.colored_label(
+                                        eframe::egui::Color32::YELLOW,
+                                        format!(
+                                            "⚠ Also bound to {}",
+                                            conflicts.iter().map(|a| a.to_string()).join(", ")
+                                        ),
+                                    );
+                                }
+                                ui.end_row();
+                            }
+                        });
+
+                    if ui.button("Reset to defaults").clicked() {
+                        settings.keybindings = Keybindings::default();
+                        ctx.capturing_keybinding = None;
+                    }
+                });
+
+                ui.collapsing("Advanced", |ui| {
+                    ui.checkbox(
+                        &mut settings.watch_config_file,
+                        "Reload settings when the config file changes",
+                    )
+                    .on_hover_text("Library and playlists are only read at startup.");
+
+                    ui.horizontal(|ui| {
+                        ui.label("Scope window");
+                        ui.add(
+                            eframe::egui::DragValue::new(&mut settings.scope_window_ms)
+                                .range(10..=1000)
+                                .suffix(" ms"),
+                        );
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Spectrum bars fall at");
+                        ui.add(
+                            eframe::egui::DragValue::new(&mut settings.spectrum_fall_off_db)
+                                .range(5.0..=500.0)
+                                .suffix(" dB/s"),
+                        );
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Keep up to");
+                        ui.add(
+                            eframe::egui::DragValue::new(&mut settings.max_history_entries)
+                                .range(1..=1_000_000)
+                                .suffix(" history entries"),
+                        );
+                        ui.add(
+                            eframe::egui::DragValue::new(&mut settings.max_queue_len)
+                                .range(1..=100_000)
+                                .suffix(" queued tracks"),
+                        );
+                    })
+                    .response
+                    .on_hover_text("The oldest entries are dropped past these. Play counts are kept.");
+
+                    ui.horizontal(|ui| {
+                        ui.label("Give up opening a track after");
+                        ui.add(
+                            eframe::egui::DragValue::new(
+                                &mut settings.network_retry.timeout_secs,
+                            )
+                            .range(1..=120)
+                            .suffix(" s"),
+                        );
+                        ui.label("and retry");
+                        ui.add(
+                            eframe::egui::DragValue::new(&mut settings.network_retry.retries)
+                                .range(0..=20)
+                                .suffix(" times"),
+                        );
+                    })
+                    .response
+                    .on_hover_text(
+                        "For music on network shares. A track that still can't be opened is skipped.",
+                    );
+
+                    ui.horizontal(|ui| {
+                        ui.label("Now playing file").on_hover_text(
+                            "Kept up to date with the playing track's artist and title, e.g. for a stream overlay. Leave empty to turn off.",
+                        );
+                        let mut path = settings
+                            .now_playing_file
+                            .as_ref()
+                            .map(|path| path.display().to_string())
+                            .unwrap_or_default();
+                        if ui.text_edit_singleline(&mut path).changed() {
+                            settings.now_playing_file =
+                                (!path.is_empty()).then(|| std::path::PathBuf::from(path));
+                        }
+                    });
+                });
+            });
+
+        if settings != ctx.settings {
+            ctx.apply_settings(settings);
+        }
+
+        ctx.is_preferences_open = is_open;
+    }
+}
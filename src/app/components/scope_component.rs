@@ -1,7 +1,9 @@
 use super::AppComponent;
+use crate::app::scope::{Scope, ScopeMode};
+use crate::app::spectrum::{self, BAR_COUNT, FLOOR_DB, MIN_HZ};
 use crate::app::App;
 use crate::egui::epaint::*;
-use crate::egui::{pos2, vec2, Frame, Pos2, Rect};
+use crate::egui::{pos2, vec2, Align2, Frame, Pos2, Rect};
 use rb::RbConsumer;
 
 pub struct ScopeComponent;
@@ -9,6 +11,13 @@ pub struct ScopeComponent;
 impl AppComponent for ScopeComponent {
     type Context = App;
     fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        if let Some(scope) = &mut ctx.scope {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut scope.mode, ScopeMode::Waveform, "Waveform");
+                ui.selectable_value(&mut scope.mode, ScopeMode::Spectrum, "Spectrum");
+            });
+        }
+
         Frame::canvas(ui.style()).show(ui, |ui| {
             ui.ctx().request_repaint();
             let _time = ui.input(|i| i.time);
@@ -27,26 +36,101 @@ impl AppComponent for ScopeComponent {
                         let num_bytes_read = audio_buf.read(&mut local_buf[..]).unwrap_or(0);
 
                         if num_bytes_read > 0 {
-                            for sample in (local_buf[0..num_bytes_read]).iter().step_by(2) {
+                            ctx.loudness.push_samples(&local_buf[0..num_bytes_read]);
+                            ctx.dynamic_range
+                                .push_samples(&local_buf[0..num_bytes_read]);
+                            ctx.loudness_meter
+                                .push_samples(&local_buf[0..num_bytes_read]);
+
+                            for sample in (local_buf[0..num_bytes_read])
+                                .iter()
+                                .step_by(ctx.scope_channels.max(1))
+                            {
                                 scope.write_sample(*sample);
                             }
                         }
                     }
                 }
 
-                let points: Vec<Pos2> = scope
-                    .into_iter()
-                    .enumerate()
-                    .map(|(i, sample)| to_screen * pos2(i as f32 / (48000.0 * 1.0), sample))
-                    .collect();
+                match scope.mode {
+                    ScopeMode::Waveform => {
+                        let len = scope.buffer.len() as f32;
+                        let points: Vec<Pos2> = scope
+                            .into_iter()
+                            .enumerate()
+                            .map(|(i, sample)| to_screen * pos2(i as f32 / len, sample))
+                            .collect();
 
-                shapes.push(crate::egui::epaint::Shape::line(
-                    points,
-                    crate::egui::epaint::Stroke::new(1.0, color),
-                ));
+                        shapes.push(crate::egui::epaint::Shape::line(
+                            points,
+                            crate::egui::epaint::Stroke::new(1.0, color),
+                        ));
+                    }
+                    ScopeMode::Spectrum => {
+                        let elapsed = ui.input(|i| i.stable_dt);
+                        add_spectrum(
+                            scope,
+                            ui,
+                            rect,
+                            ctx.settings.spectrum_fall_off_db,
+                            elapsed,
+                            color,
+                            &mut shapes,
+                        );
+                    }
+                }
             }
 
             ui.painter().extend(shapes);
         });
     }
 }
+
+// Bars from the lowest band on the left, a level of 0dB reaching the top.
+fn add_spectrum(
+    scope: &mut Scope,
+    ui: &eframe::egui::Ui,
+    rect: Rect,
+    fall_off_db: f32,
+    elapsed: f32,
+    color: Color32,
+    shapes: &mut Vec<Shape>,
+) {
+    let samples = scope.into_iter().collect::<Vec<_>>();
+    scope
+        .spectrum
+        .update(&samples, scope.sample_rate, fall_off_db, elapsed);
+
+    let bar_width = rect.width() / BAR_COUNT as f32;
+    for (idx, level) in scope.spectrum.bars.iter().enumerate() {
+        let height = rect.height() * (1.0 - level / FLOOR_DB);
+        let left = rect.left() + idx as f32 * bar_width;
+        shapes.push(Shape::rect_filled(
+            Rect::from_min_max(
+                pos2(left + 1.0, rect.bottom() - height),
+                pos2(left + bar_width - 1.0, rect.bottom()),
+            ),
+            0.0,
+            color,
+        ));
+    }
+
+    // The bars are log spaced, so are the labels.
+    let max_hz = spectrum::max_hz(scope.sample_rate);
+    let font = FontId::monospace(10.0);
+    for (hz, label) in [(100.0, "100"), (1000.0, "1k"), (10_000.0, "10k")] {
+        if hz < max_hz {
+            let x = rect.left() + rect.width() * (hz / MIN_HZ).ln() / (max_hz / MIN_HZ).ln();
+            shapes.push(ui.fonts(|fonts| {
+                Shape::text(
+                    fonts,
+                    pos2(x, rect.top()),
+                    Align2::CENTER_TOP,
+                    label,
+                    font.clone(),
+                    ui.visuals().weak_text_color(),
+                )
+            }));
+        }
+    }
+}
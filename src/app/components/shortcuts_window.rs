@@ -0,0 +1,74 @@
+use super::AppComponent;
+use crate::app::keybindings::Action;
+use crate::app::App;
+use eframe::egui;
+
+/// Modal cheat sheet of the keyboard shortcuts, as currently configured.
+pub struct ShortcutsWindow;
+
+impl AppComponent for ShortcutsWindow {
+    type Context = App;
+
+    fn add(ctx: &mut Self::Context, ui: &mut eframe::egui::Ui) {
+        if !ctx.is_shortcuts_open {
+            return;
+        }
+
+        let egui_ctx = ui.ctx().clone();
+
+        // Dim everything behind the sheet, clicking there closes it.
+        let screen_rect = egui_ctx.screen_rect();
+        let backdrop = egui::Area::new(egui::Id::new("shortcuts_backdrop"))
+            .order(egui::Order::Foreground)
+            .fixed_pos(screen_rect.min)
+            .show(&egui_ctx, |ui| {
+                ui.painter()
+                    .rect_filled(screen_rect, 0.0, egui::Color32::from_black_alpha(160));
+                ui.allocate_rect(screen_rect, egui::Sense::click())
+            })
+            .inner;
+
+        egui::Area::new(egui::Id::new("shortcuts_sheet"))
+            .order(egui::Order::Tooltip)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(&egui_ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.heading("Keyboard Shortcuts");
+                    ui.separator();
+
+                    egui::Grid::new("shortcuts")
+                        .num_columns(2)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for action in Action::ALL {
+                                ui.label(action.to_string());
+                                match ctx.settings.keybindings.get(action) {
+                                    Some(binding) => ui.monospace(binding.to_string()),
+                                    None => ui.weak("Not set"),
+                                };
+                                ui.end_row();
+                            }
+
+                            ui.label("Search the panel under the pointer");
+                            ui.monospace("Ctrl+F");
+                            ui.end_row();
+
+                            ui.label("Clear the search");
+                            ui.monospace("Esc");
+                            ui.end_row();
+
+                            ui.label("Show this help");
+                            ui.monospace("?");
+                            ui.end_row();
+                        });
+
+                    ui.separator();
+                    ui.weak("Shortcuts can be changed in File ▸ Preferences ▸ Keyboard.");
+                });
+            });
+
+        if backdrop.clicked() || egui_ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            ctx.is_shortcuts_open = false;
+        }
+    }
+}
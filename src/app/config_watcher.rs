@@ -0,0 +1,55 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Polls the confy config file for changes made outside of the app.
+pub struct ConfigWatcher {
+    path: Option<PathBuf>,
+    last_modified: Option<SystemTime>,
+    last_checked: Instant,
+}
+
+impl Default for ConfigWatcher {
+    fn default() -> Self {
+        let path = confy::get_configuration_file_path("music_player", None).ok();
+        let mut watcher = Self {
+            path,
+            last_modified: None,
+            last_checked: Instant::now(),
+        };
+        watcher.last_modified = watcher.modified();
+        watcher
+    }
+}
+
+impl ConfigWatcher {
+    /// Returns `true` when the file changed on disk since it was last seen. Checks at most once
+    /// per `POLL_INTERVAL` so it's cheap to call every frame.
+    pub fn poll(&mut self) -> bool {
+        if self.last_checked.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_checked = Instant::now();
+
+        let modified = self.modified();
+        if modified.is_some() && modified != self.last_modified {
+            self.last_modified = modified;
+            return true;
+        }
+
+        false
+    }
+
+    /// Remembers the file's current state so the app's own writes aren't mistaken for edits.
+    pub fn mark_written(&mut self) {
+        self.last_modified = self.modified();
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        self.path
+            .as_ref()
+            .and_then(|path| std::fs::metadata(path).ok())
+            .and_then(|metadata| metadata.modified().ok())
+    }
+}
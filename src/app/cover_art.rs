@@ -0,0 +1,127 @@
+use crate::app::library::LibraryItem;
+use eframe::egui;
+use lofty::file::TaggedFileExt;
+use lofty::picture::PictureType;
+use std::path::{Path, PathBuf};
+
+// Image files in an album folder that are taken as its cover, in order of preference.
+const FOLDER_COVER_NAMES: [&str; 4] = ["cover.jpg", "folder.jpg", "cover.png", "folder.png"];
+
+/// Cover image next to the track, e.g. `cover.jpg` in the album folder. Matched regardless of
+/// case.
+pub fn find_folder_cover(track_path: &Path) -> Option<PathBuf> {
+    let entries = std::fs::read_dir(track_path.parent()?)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>();
+
+    FOLDER_COVER_NAMES.iter().find_map(|name| {
+        entries
+            .iter()
+            .find(|path| {
+                path.file_name()
+                    .is_some_and(|file_name| file_name.to_string_lossy().eq_ignore_ascii_case(name))
+            })
+            .cloned()
+    })
+}
+
+/// Cover of the selected track as a texture, cached until the track changes. The album
+/// folder's cover wins over art embedded in the file, and the default cover from the settings
+/// is shown for tracks with neither.
+#[derive(Default)]
+pub struct CoverArt {
+    // Track the cover was looked up for.
+    track: Option<PathBuf>,
+    texture: Option<egui::TextureHandle>,
+    default_path: Option<PathBuf>,
+    default_texture: Option<egui::TextureHandle>,
+}
+
+impl CoverArt {
+    pub fn texture(
+        &mut self,
+        ctx: &egui::Context,
+        track: Option<&LibraryItem>,
+        default_cover: Option<&Path>,
+    ) -> Option<&egui::TextureHandle> {
+        let track_path = track.map(|track| track.path());
+        if self.track != track_path {
+            self.texture = track
+                .and_then(track_cover)
+                .map(|image| ctx.load_texture("cover_art", image, Default::default()));
+            self.track = track_path;
+        }
+
+        // Loaded once and kept for as long as the setting doesn't change.
+        if self.default_path.as_deref() != default_cover {
+            self.default_texture = default_cover
+                .and_then(|path| std::fs::read(path).ok())
+                .and_then(|bytes| decode(&bytes))
+                .map(|image| ctx.load_texture("default_cover_art", image, Default::default()));
+            self.default_path = default_cover.map(Path::to_path_buf);
+        }
+
+        self.track.as_ref()?;
+        self.texture.as_ref().or(self.default_texture.as_ref())
+    }
+}
+
+fn track_cover(track: &LibraryItem) -> Option<egui::ColorImage> {
+    if let Some(image) = track
+        .cover_path()
+        .and_then(|path| std::fs::read(path).ok())
+        .and_then(|bytes| decode(&bytes))
+    {
+        return Some(image);
+    }
+
+    // Art embedded in any of the file's tags, ID3 APIC frames, FLAC pictures, MP4 covr atoms and
+    // so on. The front cover wins over the other pictures.
+    let tagged_file = lofty::read_from_path(track.path()).ok()?;
+    let pictures = || tagged_file.tags().iter().flat_map(|tag| tag.pictures());
+    let picture = pictures()
+        .find(|picture| picture.pic_type() == PictureType::CoverFront)
+        .or_else(|| pictures().next())?;
+
+    decode(picture.data())
+}
+
+fn decode(bytes: &[u8]) -> Option<egui::ColorImage> {
+    match image::load_from_memory(bytes) {
+        Ok(image) => {
+            let image = image.to_rgba8();
+            let size = [image.width() as usize, image.height() as usize];
+            Some(egui::ColorImage::from_rgba_unmultiplied(
+                size,
+                image.as_flat_samples().as_slice(),
+            ))
+        }
+        Err(err) => {
+            tracing::warn!("Couldn't decode cover art: {}", err);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folder_cover_is_found_regardless_of_case() {
+        let dir = std::env::temp_dir().join(format!("music-player-{}-cover", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let track = dir.join("01 Song.mp3");
+        assert_eq!(find_folder_cover(&track), None);
+
+        std::fs::write(dir.join("Folder.JPG"), []).unwrap();
+        assert_eq!(find_folder_cover(&track), Some(dir.join("Folder.JPG")));
+
+        std::fs::write(dir.join("cover.jpg"), []).unwrap();
+        assert_eq!(find_folder_cover(&track), Some(dir.join("cover.jpg")));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
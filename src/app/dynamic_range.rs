@@ -0,0 +1,135 @@
+use std::path::PathBuf;
+
+// Length of each block the track is cut into, as in the TT DR meter.
+const BLOCK_SECS: u64 = 3;
+// Share of the loudest blocks the loudness is taken from.
+const LOUDEST_SHARE: f32 = 0.2;
+
+/// Dynamic range of the playing track the way the TT DR meter reports it: the peak over the
+/// loudness of the loudest fifth, in dB. Heavily compressed masters end up around DR5, dynamic
+/// ones at DR12 and up. Fed from the same sample stream as the scope.
+pub struct DynamicRangeMeter {
+    // Track the measurement belongs to.
+    pub track: Option<PathBuf>,
+    // Part of the track was skipped, so the figure isn't worth keeping.
+    pub is_partial: bool,
+    // RMS and peak of each finished block.
+    blocks: Vec<(f32, f32)>,
+    block_len: usize,
+    sum_squares: f64,
+    peak: f32,
+    count: usize,
+}
+
+impl Default for DynamicRangeMeter {
+    fn default() -> Self {
+        Self::new(48000, 2)
+    }
+}
+
+impl DynamicRangeMeter {
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        Self {
+            track: None,
+            is_partial: false,
+            blocks: Vec::new(),
+            block_len: (sample_rate as u64 * BLOCK_SECS) as usize * channels.max(1),
+            sum_squares: 0.0,
+            peak: 0.0,
+            count: 0,
+        }
+    }
+
+    pub fn set_format(&mut self, sample_rate: u32, channels: usize) {
+        *self = Self {
+            track: self.track.take(),
+            is_partial: self.is_partial,
+            blocks: std::mem::take(&mut self.blocks),
+            ..Self::new(sample_rate, channels)
+        };
+    }
+
+    /// Starts measuring `track` from scratch.
+    pub fn start(&mut self, track: Option<PathBuf>) {
+        self.track = track;
+        self.is_partial = false;
+        self.blocks.clear();
+        self.sum_squares = 0.0;
+        self.peak = 0.0;
+        self.count = 0;
+    }
+
+    pub fn push_samples(&mut self, samples: &[f32]) {
+        for sample in samples {
+            self.sum_squares += (*sample as f64).powi(2);
+            self.peak = self.peak.max(sample.abs());
+            self.count += 1;
+
+            if self.count >= self.block_len {
+                // Scaled so a full scale sine measures 0dB, like its peak.
+                let rms = (2.0 * self.sum_squares / self.count as f64).sqrt() as f32;
+                self.blocks.push((rms, self.peak));
+
+                self.sum_squares = 0.0;
+                self.peak = 0.0;
+                self.count = 0;
+            }
+        }
+    }
+
+    /// DR value in dB, once at least one block has been measured. Silence has none.
+    pub fn value(&self) -> Option<f32> {
+        let mut rms = self.blocks.iter().map(|(rms, _)| *rms).collect::<Vec<_>>();
+        let mut peaks = self
+            .blocks
+            .iter()
+            .map(|(_, peak)| *peak)
+            .collect::<Vec<_>>();
+        rms.sort_by(|a, b| b.total_cmp(a));
+        peaks.sort_by(|a, b| b.total_cmp(a));
+
+        // The second highest peak, so a single stray sample doesn't count.
+        let peak = *peaks.get(1).or(peaks.first())?;
+
+        let loudest = ((rms.len() as f32 * LOUDEST_SHARE).round() as usize).max(1);
+        let loudness =
+            (rms[..loudest].iter().map(|rms| rms * rms).sum::<f32>() / loudest as f32).sqrt();
+
+        (loudness > 0.0).then(|| 20.0 * (peak / loudness).log10())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Mono samples at 4Hz, so each block is 12 samples: a sine sampled at its zero crossings
+    // and peaks.
+    fn sine(amplitude: f32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| [0.0, amplitude, 0.0, -amplitude][i % 4])
+            .collect()
+    }
+
+    #[test]
+    fn steady_sine_has_no_dynamic_range() {
+        let mut meter = DynamicRangeMeter::new(4, 1);
+        assert_eq!(meter.value(), None);
+
+        meter.push_samples(&sine(0.5, 60));
+        assert!(meter.value().unwrap().abs() < 1e-4);
+    }
+
+    #[test]
+    fn peaks_over_a_quiet_signal_add_dynamic_range() {
+        let mut meter = DynamicRangeMeter::new(4, 1);
+        let mut samples = sine(0.1, 60);
+        samples[12] = 1.0;
+        samples[36] = 1.0;
+        meter.push_samples(&samples);
+
+        // The loudest block holds a peak: sqrt(2 * (6 * 0.01 + 1) / 12) ≈ 0.42 against 1.0.
+        let expected = 20.0 * (1.0 / (2.0 * 1.06f32 / 12.0).sqrt()).log10();
+        assert!((meter.value().unwrap() - expected).abs() < 1e-3);
+    }
+}
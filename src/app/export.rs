@@ -0,0 +1,214 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExportOptions {
+    /// Prefix file names with their playlist position so players that sort by name keep the
+    /// playlist order.
+    pub renumber: bool,
+}
+
+enum ExportEvent {
+    Copied,
+    Failed { path: PathBuf, error: String },
+    Finished { stopped_early: Option<String> },
+}
+
+/// Copies a playlist's files into a folder, e.g. a USB stick for a car stereo. The copy runs on
+/// a background thread; call `poll` from the UI to pick up its progress.
+pub struct Export {
+    pub destination: PathBuf,
+    pub total: usize,
+    pub copied: usize,
+    pub errors: Vec<(PathBuf, String)>,
+    pub is_finished: bool,
+    /// Why the export ended before all files were tried, e.g. the destination filled up.
+    pub stopped_early: Option<String>,
+    is_cancelled: Arc<AtomicBool>,
+    rx: Receiver<ExportEvent>,
+}
+
+impl Export {
+    pub fn start(tracks: Vec<PathBuf>, destination: PathBuf, options: ExportOptions) -> Self {
+        let (tx, rx) = channel();
+        let is_cancelled = Arc::new(AtomicBool::new(false));
+
+        let thread_destination = destination.clone();
+        let thread_is_cancelled = is_cancelled.clone();
+        let total = tracks.len();
+        std::thread::spawn(move || {
+            export_tracks(
+                &tracks,
+                &thread_destination,
+                options,
+                &thread_is_cancelled,
+                &tx,
+            );
+        });
+
+        Self {
+            destination,
+            total,
+            copied: 0,
+            errors: Vec::new(),
+            is_finished: false,
+            stopped_early: None,
+            is_cancelled,
+            rx,
+        }
+    }
+
+    pub fn poll(&mut self) {
+        while let Ok(event) = self.rx.try_recv() {
+            match event {
+                ExportEvent::Copied => self.copied += 1,
+                ExportEvent::Failed { path, error } => self.errors.push((path, error)),
+                ExportEvent::Finished { stopped_early } => {
+                    self.is_finished = true;
+                    self.stopped_early = stopped_early;
+                }
+            }
+        }
+    }
+
+    pub fn processed(&self) -> usize {
+        self.copied + self.errors.len()
+    }
+
+    /// Stops after the file currently being copied.
+    pub fn cancel(&self) {
+        self.is_cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+fn export_tracks(
+    tracks: &[PathBuf],
+    destination: &Path,
+    options: ExportOptions,
+    is_cancelled: &AtomicBool,
+    tx: &Sender<ExportEvent>,
+) {
+    let mut stopped_early = None;
+
+    for (idx, track) in tracks.iter().enumerate() {
+        if is_cancelled.load(Ordering::Relaxed) {
+            stopped_early = Some("Cancelled".to_string());
+            break;
+        }
+
+        let Some(file_name) = track.file_name() else {
+            _ = tx.send(ExportEvent::Failed {
+                path: track.clone(),
+                error: "Not a file".to_string(),
+            });
+            continue;
+        };
+
+        let file_name = export_file_name(file_name, idx, tracks.len(), options);
+        let target = unique_path(destination, &file_name);
+
+        // A plain copy keeps the embedded tags as they are.
+        match std::fs::copy(track, &target) {
+            Ok(_) => {
+                _ = tx.send(ExportEvent::Copied);
+            }
+            Err(err) => {
+                // Don't leave a truncated file behind.
+                if target.exists() {
+                    _ = std::fs::remove_file(&target);
+                }
+
+                let is_full = err.kind() == std::io::ErrorKind::StorageFull;
+                _ = tx.send(ExportEvent::Failed {
+                    path: track.clone(),
+                    error: err.to_string(),
+                });
+
+                // Every remaining file would fail the same way.
+                if is_full {
+                    stopped_early = Some("Not enough space left on the destination".to_string());
+                    break;
+                }
+            }
+        }
+    }
+
+    _ = tx.send(ExportEvent::Finished { stopped_early });
+}
+
+fn export_file_name(
+    file_name: &std::ffi::OsStr,
+    idx: usize,
+    total: usize,
+    options: ExportOptions,
+) -> OsString {
+    if !options.renumber {
+        return file_name.to_os_string();
+    }
+
+    let width = total.to_string().len().max(2);
+    let mut numbered = OsString::from(format!("{:0width$} - ", idx + 1));
+    numbered.push(file_name);
+    numbered
+}
+
+// Picks a name that doesn't overwrite anything in `dir`, adding " (n)" before the extension when
+// needed.
+fn unique_path(dir: &Path, file_name: &std::ffi::OsStr) -> PathBuf {
+    let candidate = dir.join(file_name);
+    if !candidate.exists() {
+        return candidate;
+    }
+
+    let file_name = Path::new(file_name);
+    let stem = file_name.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = file_name.extension().map(|ext| ext.to_string_lossy());
+
+    (1..)
+        .map(|n| match &extension {
+            Some(extension) => dir.join(format!("{stem} ({n}).{extension}")),
+            None => dir.join(format!("{stem} ({n})")),
+        })
+        .find(|candidate| !candidate.exists())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn renumbers_file_names_in_playlist_order() {
+        let options = ExportOptions { renumber: true };
+
+        assert_eq!(
+            export_file_name(OsStr::new("song.mp3"), 0, 12, options),
+            OsString::from("01 - song.mp3")
+        );
+        assert_eq!(
+            export_file_name(OsStr::new("song.mp3"), 99, 150, options),
+            OsString::from("100 - song.mp3")
+        );
+        assert_eq!(
+            export_file_name(OsStr::new("song.mp3"), 3, 12, ExportOptions::default()),
+            OsString::from("song.mp3")
+        );
+    }
+
+    #[test]
+    fn avoids_overwriting_existing_files() {
+        let dir = std::env::temp_dir().join(format!("music-player-export-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("song.mp3"), []).unwrap();
+        std::fs::write(dir.join("song (1).mp3"), []).unwrap();
+
+        let path = unique_path(&dir, OsStr::new("song.mp3"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(path, dir.join("song (2).mp3"));
+    }
+}
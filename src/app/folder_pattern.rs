@@ -0,0 +1,184 @@
+use std::path::Path;
+
+/// Layout of a folder organized library, like `{artist}/{album}/{track} {title}`, used to fill
+/// in tags missing from the files. Each `/` separated part is matched against a folder name,
+/// lined up from the file's end of the path; the last part is matched against the file name
+/// without its extension. Parts that don't fit are skipped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FolderPattern {
+    segments: Vec<Vec<Token>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Literal(String),
+    Field(Field),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Artist,
+    Album,
+    Title,
+    Track,
+    Year,
+    // Unknown placeholders match anything and are thrown away.
+    Ignored,
+}
+
+/// Tags read off the path.
+#[derive(Debug, Default, PartialEq)]
+pub struct InferredTags {
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub title: Option<String>,
+    pub track_number: Option<u32>,
+    pub year: Option<i32>,
+}
+
+impl FolderPattern {
+    pub fn parse(pattern: &str) -> Self {
+        Self {
+            segments: pattern
+                .split('/')
+                .filter(|segment| !segment.is_empty())
+                .map(parse_segment)
+                .collect(),
+        }
+    }
+
+    pub fn infer(&self, path: &Path) -> InferredTags {
+        let mut names = path
+            .parent()
+            .into_iter()
+            .flat_map(|parent| parent.components().rev())
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        if let Some(stem) = path.file_stem() {
+            names.insert(0, stem.to_string_lossy().into_owned());
+        }
+
+        let mut tags = InferredTags::default();
+        for (segment, name) in self.segments.iter().rev().zip(&names) {
+            let Some(captures) = match_tokens(segment, name) else {
+                continue;
+            };
+
+            for (field, value) in captures {
+                let value = value.trim();
+                if value.is_empty() {
+                    continue;
+                }
+
+                match field {
+                    Field::Artist => tags.artist = Some(value.to_string()),
+                    Field::Album => tags.album = Some(value.to_string()),
+                    Field::Title => tags.title = Some(value.to_string()),
+                    Field::Track => tags.track_number = value.parse().ok(),
+                    Field::Year => tags.year = value.parse().ok(),
+                    Field::Ignored => {}
+                }
+            }
+        }
+
+        tags
+    }
+}
+
+fn parse_segment(segment: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut rest = segment;
+
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+
+        if start > 0 {
+            tokens.push(Token::Literal(rest[..start].to_string()));
+        }
+        tokens.push(Token::Field(match &rest[start + 1..start + len] {
+            "artist" => Field::Artist,
+            "album" => Field::Album,
+            "title" => Field::Title,
+            "track" => Field::Track,
+            "year" => Field::Year,
+            _ => Field::Ignored,
+        }));
+        rest = &rest[start + len + 1..];
+    }
+
+    if !rest.is_empty() {
+        tokens.push(Token::Literal(rest.to_string()));
+    }
+
+    tokens
+}
+
+// Matches the whole of `text`. Fields take as little as they can, except a trailing one which
+// takes the rest. Track numbers and years only match digits.
+fn match_tokens<'a>(tokens: &[Token], text: &'a str) -> Option<Vec<(Field, &'a str)>> {
+    let Some((token, rest_tokens)) = tokens.split_first() else {
+        return text.is_empty().then(Vec::new);
+    };
+
+    match token {
+        Token::Literal(literal) => match_tokens(rest_tokens, text.strip_prefix(literal.as_str())?),
+        Token::Field(field) => text
+            .char_indices()
+            .skip(1)
+            .map(|(idx, _)| idx)
+            .chain(std::iter::once(text.len()))
+            .filter(|end| *end > 0)
+            .find_map(|end| {
+                let value = &text[..end];
+                if matches!(field, Field::Track | Field::Year)
+                    && !value.chars().all(|c| c.is_ascii_digit())
+                {
+                    return None;
+                }
+
+                let mut captures = match_tokens(rest_tokens, &text[end..])?;
+                captures.insert(0, (*field, value));
+                Some(captures)
+            }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn tags_are_read_off_the_folders() {
+        let pattern = FolderPattern::parse("{artist}/{album} ({year})/{track} {title}");
+
+        assert_eq!(
+            pattern.infer(&PathBuf::from(
+                "/music/Nick Drake/Pink Moon (1972)/03 Road.mp3"
+            )),
+            InferredTags {
+                artist: Some("Nick Drake".to_string()),
+                album: Some("Pink Moon".to_string()),
+                title: Some("Road".to_string()),
+                track_number: Some(3),
+                year: Some(1972),
+            }
+        );
+    }
+
+    #[test]
+    fn parts_that_dont_fit_are_skipped() {
+        let pattern = FolderPattern::parse("{artist}/{album}/{track} - {title}");
+
+        assert_eq!(
+            pattern.infer(&PathBuf::from("Artist/Album/Untitled.mp3")),
+            InferredTags {
+                artist: Some("Artist".to_string()),
+                album: Some("Album".to_string()),
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,282 @@
+/// ID3v1 genres by numeric code, including the Winamp extensions most taggers write.
+const ID3V1_GENRES: [&str; 192] = [
+    "Blues",
+    "Classic Rock",
+    "Country",
+    "Dance",
+    "Disco",
+    "Funk",
+    "Grunge",
+    "Hip-Hop",
+    "Jazz",
+    "Metal",
+    "New Age",
+    "Oldies",
+    "Other",
+    "Pop",
+    "R&B",
+    "Rap",
+    "Reggae",
+    "Rock",
+    "Techno",
+    "Industrial",
+    "Alternative",
+    "Ska",
+    "Death Metal",
+    "Pranks",
+    "Soundtrack",
+    "Euro-Techno",
+    "Ambient",
+    "Trip-Hop",
+    "Vocal",
+    "Jazz+Funk",
+    "Fusion",
+    "Trance",
+    "Classical",
+    "Instrumental",
+    "Acid",
+    "House",
+    "Game",
+    "Sound Clip",
+    "Gospel",
+    "Noise",
+    "Alternative Rock",
+    "Bass",
+    "Soul",
+    "Punk",
+    "Space",
+    "Meditative",
+    "Instrumental Pop",
+    "Instrumental Rock",
+    "Ethnic",
+    "Gothic",
+    "Darkwave",
+    "Techno-Industrial",
+    "Electronic",
+    "Pop-Folk",
+    "Eurodance",
+    "Dream",
+    "Southern Rock",
+    "Comedy",
+    "Cult",
+    "Gangsta",
+    "Top 40",
+    "Christian Rap",
+    "Pop/Funk",
+    "Jungle",
+    "Native American",
+    "Cabaret",
+    "New Wave",
+    "Psychedelic",
+    "Rave",
+    "Showtunes",
+    "Trailer",
+    "Lo-Fi",
+    "Tribal",
+    "Acid Punk",
+    "Acid Jazz",
+    "Polka",
+    "Retro",
+    "Musical",
+    "Rock & Roll",
+    "Hard Rock",
+    "Folk",
+    "Folk-Rock",
+    "National Folk",
+    "Swing",
+    "Fast Fusion",
+    "Bebop",
+    "Latin",
+    "Revival",
+    "Celtic",
+    "Bluegrass",
+    "Avantgarde",
+    "Gothic Rock",
+    "Progressive Rock",
+    "Psychedelic Rock",
+    "Symphonic Rock",
+    "Slow Rock",
+    "Big Band",
+    "Chorus",
+    "Easy Listening",
+    "Acoustic",
+    "Humour",
+    "Speech",
+    "Chanson",
+    "Opera",
+    "Chamber Music",
+    "Sonata",
+    "Symphony",
+    "Booty Bass",
+    "Primus",
+    "Porn Groove",
+    "Satire",
+    "Slow Jam",
+    "Club",
+    "Tango",
+    "Samba",
+    "Folklore",
+    "Ballad",
+    "Power Ballad",
+    "Rhythmic Soul",
+    "Freestyle",
+    "Duet",
+    "Punk Rock",
+    "Drum Solo",
+    "A Cappella",
+    "Euro-House",
+    "Dance Hall",
+    "Goa",
+    "Drum & Bass",
+    "Club-House",
+    "Hardcore Techno",
+    "Terror",
+    "Indie",
+    "BritPop",
+    "Negerpunk",
+    "Polsk Punk",
+    "Beat",
+    "Christian Gangsta Rap",
+    "Heavy Metal",
+    "Black Metal",
+    "Crossover",
+    "Contemporary Christian",
+    "Christian Rock",
+    "Merengue",
+    "Salsa",
+    "Thrash Metal",
+    "Anime",
+    "Jpop",
+    "Synthpop",
+    "Abstract",
+    "Art Rock",
+    "Baroque",
+    "Bhangra",
+    "Big Beat",
+    "Breakbeat",
+    "Chillout",
+    "Downtempo",
+    "Dub",
+    "EBM",
+    "Eclectic",
+    "Electro",
+    "Electroclash",
+    "Emo",
+    "Experimental",
+    "Garage",
+    "Global",
+    "IDM",
+    "Illbient",
+    "Industro-Goth",
+    "Jam Band",
+    "Krautrock",
+    "Leftfield",
+    "Lounge",
+    "Math Rock",
+    "New Romantic",
+    "Nu-Breakz",
+    "Post-Punk",
+    "Post-Rock",
+    "Psytrance",
+    "Shoegaze",
+    "Space Rock",
+    "Trop Rock",
+    "World Music",
+    "Neoclassical",
+    "Audiobook",
+    "Audio Theatre",
+    "Neue Deutsche Welle",
+    "Podcast",
+    "Indie Rock",
+    "G-Funk",
+    "Dubstep",
+    "Garage Rock",
+    "Psybient",
+];
+
+/// Turns ID3 genre strings that reference numeric codes into readable names.
+///
+/// Handles a bare code (`"17"`), ID3v2.3 references (`"(17)"`, `"(17)(18)"`), refinements where
+/// the text after the reference wins (`"(17)Hard Rock"`), the `RX`/`CR` specials and the `((`
+/// escape for text that really starts with a parenthesis. Anything else is returned unchanged.
+pub fn normalize_genre(raw: &str) -> String {
+    let raw = raw.trim();
+
+    if let Ok(code) = raw.parse::<usize>() {
+        return genre_name(code).map_or(raw.to_string(), str::to_string);
+    }
+
+    let mut names = Vec::new();
+    let mut rest = raw;
+    while let Some(after_paren) = rest.strip_prefix('(') {
+        // "((" escapes a literal parenthesis, the rest is plain text.
+        if after_paren.starts_with('(') {
+            break;
+        }
+
+        let Some(end) = after_paren.find(')') else {
+            break;
+        };
+
+        let reference = &after_paren[..end];
+        let name = match reference {
+            "RX" => Some("Remix"),
+            "CR" => Some("Cover"),
+            code => code.parse().ok().and_then(genre_name),
+        };
+
+        match name {
+            Some(name) => names.push(name),
+            // Not a reference after all, keep the text as is.
+            None => break,
+        }
+
+        rest = &after_paren[end + 1..];
+    }
+
+    let refinement = rest
+        .strip_prefix('(')
+        .filter(|text| text.starts_with('('))
+        .unwrap_or(rest);
+    if !refinement.trim().is_empty() {
+        return refinement.trim().to_string();
+    }
+
+    if names.is_empty() {
+        raw.to_string()
+    } else {
+        names.join(" / ")
+    }
+}
+
+fn genre_name(code: usize) -> Option<&'static str> {
+    ID3V1_GENRES.get(code).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_numeric_codes() {
+        assert_eq!(normalize_genre("17"), "Rock");
+        assert_eq!(normalize_genre("(17)"), "Rock");
+        assert_eq!(normalize_genre("(17)(18)"), "Rock / Techno");
+        assert_eq!(normalize_genre("(RX)"), "Remix");
+        assert_eq!(normalize_genre("(999)"), "(999)");
+    }
+
+    #[test]
+    fn prefers_the_refinement() {
+        assert_eq!(normalize_genre("(17)Hard Rock"), "Hard Rock");
+        assert_eq!(normalize_genre("(17)((Live)"), "(Live)");
+    }
+
+    #[test]
+    fn leaves_plain_text_alone() {
+        assert_eq!(normalize_genre("Shoegaze"), "Shoegaze");
+        assert_eq!(normalize_genre("((Untitled)"), "(Untitled)");
+        assert_eq!(normalize_genre("(Unknown) Stuff"), "(Unknown) Stuff");
+        assert_eq!(normalize_genre("Drum & Bass (Live)"), "Drum & Bass (Live)");
+    }
+}
@@ -0,0 +1,193 @@
+use crate::app::library::LibraryItem;
+use crate::app::unix_timestamp;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Listening history and per-track play counts. A track is only recorded here once it has
+/// crossed the configured play threshold.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct History {
+    entries: Vec<HistoryEntry>,
+    play_counts: HashMap<PathBuf, u32>,
+    // Entries kept before the oldest are dropped, unlimited with `None`. Play counts are kept
+    // regardless.
+    #[serde(skip)]
+    max_entries: Option<usize>,
+}
+
+impl History {
+    pub fn record_play(&mut self, track: &LibraryItem) {
+        tracing::info!("Counting play for {:?}", track.path());
+        self.entries.push(HistoryEntry::new(track));
+        *self.play_counts.entry(track.path()).or_default() += 1;
+        self.trim();
+    }
+
+    pub fn set_max_entries(&mut self, max_entries: usize) {
+        self.max_entries = Some(max_entries);
+        self.trim();
+    }
+
+    fn trim(&mut self) {
+        if let Some(excess) = self
+            .max_entries
+            .and_then(|max_entries| self.entries.len().checked_sub(max_entries))
+        {
+            self.entries.drain(..excess);
+        }
+    }
+
+    pub fn entries(&self) -> &Vec<HistoryEntry> {
+        &self.entries
+    }
+
+    pub fn play_count(&self, path: &Path) -> u32 {
+        self.play_counts.get(path).copied().unwrap_or(0)
+    }
+
+    /// Writes the history and play counts to `path`, as JSON if it ends in `.json` and CSV
+    /// otherwise. CSV has a row per play along with the track's total play count, JSON also
+    /// lists the counts of tracks whose plays were dropped from the history.
+    pub fn export(&self, path: &Path) -> std::io::Result<()> {
+        let is_json = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+
+        let contents = if is_json {
+            serde_json::to_string_pretty(&ListeningData {
+                exported_at: unix_timestamp(),
+                plays: &self.entries,
+                play_counts: self.play_count_entries(),
+            })?
+        } else {
+            self.to_csv()
+        };
+
+        std::fs::write(path, contents)
+    }
+
+    // Play counts sorted by most played, with the tags of each track's latest play.
+    fn play_count_entries(&self) -> Vec<PlayCount> {
+        let mut play_counts = self
+            .play_counts
+            .iter()
+            .map(|(path, count)| {
+                let latest = self.entries.iter().rev().find(|entry| entry.path == *path);
+                PlayCount {
+                    path: path.clone(),
+                    title: latest.and_then(|entry| entry.title.clone()),
+                    artist: latest.and_then(|entry| entry.artist.clone()),
+                    album: latest.and_then(|entry| entry.album.clone()),
+                    count: *count,
+                }
+            })
+            .collect::<Vec<_>>();
+        play_counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.path.cmp(&b.path)));
+        play_counts
+    }
+
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("played_at,path,artist,title,album,play_count\n");
+        for entry in &self.entries {
+            let fields = [
+                entry.played_at.to_string(),
+                entry.path.display().to_string(),
+                entry.artist.clone().unwrap_or_default(),
+                entry.title.clone().unwrap_or_default(),
+                entry.album.clone().unwrap_or_default(),
+                self.play_count(&entry.path).to_string(),
+            ];
+            csv.push_str(&fields.iter().map(|field| csv_field(field)).join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+// Quotes a field when it holds anything CSV gives a meaning to.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[derive(Serialize)]
+struct ListeningData<'a> {
+    exported_at: u64,
+    plays: &'a [HistoryEntry],
+    play_counts: Vec<PlayCount>,
+}
+
+#[derive(Serialize)]
+struct PlayCount {
+    path: PathBuf,
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    count: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub path: PathBuf,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// Seconds since the unix epoch.
+    pub played_at: u64,
+}
+
+impl HistoryEntry {
+    fn new(track: &LibraryItem) -> Self {
+        Self {
+            path: track.path(),
+            title: track.title(),
+            artist: track.artist(),
+            album: track.album(),
+            played_at: unix_timestamp(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::library::LibraryPathId;
+
+    #[test]
+    fn oldest_entries_are_dropped_past_the_limit() {
+        let track = |name: &str| LibraryItem::new(PathBuf::from(name), LibraryPathId::new(0));
+        let mut history = History::default();
+        for name in ["a.mp3", "b.mp3", "c.mp3"] {
+            history.record_play(&track(name));
+        }
+
+        history.set_max_entries(2);
+        assert_eq!(history.entries().len(), 2);
+        assert_eq!(history.entries()[0].path, PathBuf::from("b.mp3"));
+
+        history.record_play(&track("a.mp3"));
+        assert_eq!(history.entries()[0].path, PathBuf::from("c.mp3"));
+        assert_eq!(history.play_count(Path::new("a.mp3")), 2);
+    }
+
+    #[test]
+    fn csv_export_quotes_fields() {
+        let mut track = LibraryItem::new(PathBuf::from("a.mp3"), LibraryPathId::new(0));
+        track.set_title(Some("Hello, \"World\""));
+        let mut history = History::default();
+        history.record_play(&track);
+        history.record_play(&track);
+
+        let csv = history.to_csv();
+        let rows = csv.lines().collect::<Vec<_>>();
+
+        assert_eq!(rows.len(), 3);
+        assert!(rows[1].ends_with(",a.mp3,,\"Hello, \"\"World\"\"\",,2"));
+    }
+}
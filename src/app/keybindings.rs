@@ -0,0 +1,266 @@
+use eframe::egui::{Key, KeyboardShortcut, Modifiers};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Something a keyboard shortcut can trigger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    PlayPause,
+    Stop,
+    Next,
+    Previous,
+    SeekForward,
+    SeekBackward,
+    SkipForward,
+    SkipBackward,
+    VolumeUp,
+    VolumeDown,
+    NextChapter,
+    PreviousChapter,
+}
+
+impl Action {
+    pub const ALL: [Action; 12] = [
+        Action::PlayPause,
+        Action::Stop,
+        Action::Next,
+        Action::Previous,
+        Action::SeekForward,
+        Action::SeekBackward,
+        Action::SkipForward,
+        Action::SkipBackward,
+        Action::VolumeUp,
+        Action::VolumeDown,
+        Action::NextChapter,
+        Action::PreviousChapter,
+    ];
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::PlayPause => write!(f, "Play / Pause"),
+            Action::Stop => write!(f, "Stop"),
+            Action::Next => write!(f, "Next track"),
+            Action::Previous => write!(f, "Previous track"),
+            Action::SeekForward => write!(f, "Seek forward"),
+            Action::SeekBackward => write!(f, "Seek backward"),
+            Action::SkipForward => write!(f, "Skip forward"),
+            Action::SkipBackward => write!(f, "Skip back"),
+            Action::VolumeUp => write!(f, "Volume up"),
+            Action::VolumeDown => write!(f, "Volume down"),
+            Action::NextChapter => write!(f, "Next chapter"),
+            Action::PreviousChapter => write!(f, "Previous chapter"),
+        }
+    }
+}
+
+/// A key plus modifiers. Saved as text like `"Ctrl+Shift+Right"` so the config file stays easy
+/// to edit by hand. `Ctrl` means Cmd on macOS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeyBinding {
+    pub key: Key,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl KeyBinding {
+    pub fn new(key: Key) -> Self {
+        Self {
+            key,
+            ctrl: false,
+            shift: false,
+            alt: false,
+        }
+    }
+
+    pub fn ctrl(mut self) -> Self {
+        self.ctrl = true;
+        self
+    }
+
+    pub fn shift(mut self) -> Self {
+        self.shift = true;
+        self
+    }
+
+    pub fn from_input(key: Key, modifiers: Modifiers) -> Self {
+        Self {
+            key,
+            ctrl: modifiers.command,
+            shift: modifiers.shift,
+            alt: modifiers.alt,
+        }
+    }
+
+    pub fn shortcut(&self) -> KeyboardShortcut {
+        let mut modifiers = Modifiers::NONE;
+        if self.ctrl {
+            modifiers = modifiers | Modifiers::COMMAND;
+        }
+        if self.shift {
+            modifiers = modifiers | Modifiers::SHIFT;
+        }
+        if self.alt {
+            modifiers = modifiers | Modifiers::ALT;
+        }
+
+        KeyboardShortcut::new(modifiers, self.key)
+    }
+
+    fn modifier_count(&self) -> usize {
+        [self.ctrl, self.shift, self.alt]
+            .iter()
+            .filter(|m| **m)
+            .count()
+    }
+}
+
+impl std::fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        write!(f, "{}", self.key.name())
+    }
+}
+
+impl TryFrom<String> for KeyBinding {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let mut parts = value.split('+').map(str::trim).collect::<Vec<_>>();
+        let key_name = parts.pop().unwrap_or_default();
+        let key = Key::from_name(key_name).ok_or_else(|| format!("unknown key {key_name:?}"))?;
+
+        let mut binding = KeyBinding::new(key);
+        for modifier in parts {
+            match modifier {
+                "Ctrl" | "Cmd" => binding.ctrl = true,
+                "Shift" => binding.shift = true,
+                "Alt" => binding.alt = true,
+                _ => return Err(format!("unknown modifier {modifier:?}")),
+            }
+        }
+
+        Ok(binding)
+    }
+}
+
+impl From<KeyBinding> for String {
+    fn from(binding: KeyBinding) -> Self {
+        binding.to_string()
+    }
+}
+
+/// Which shortcut triggers which action. Actions without a binding simply have no shortcut.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Keybindings {
+    bindings: BTreeMap<Action, KeyBinding>,
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        let bindings = BTreeMap::from([
+            (Action::PlayPause, KeyBinding::new(Key::Space)),
+            (Action::Stop, KeyBinding::new(Key::Space).ctrl()),
+            (Action::Next, KeyBinding::new(Key::ArrowRight).ctrl()),
+            (Action::Previous, KeyBinding::new(Key::ArrowLeft).ctrl()),
+            (Action::SeekForward, KeyBinding::new(Key::ArrowRight)),
+            (Action::SeekBackward, KeyBinding::new(Key::ArrowLeft)),
+            (
+                Action::SkipForward,
+                KeyBinding::new(Key::ArrowRight).shift(),
+            ),
+            (
+                Action::SkipBackward,
+                KeyBinding::new(Key::ArrowLeft).shift(),
+            ),
+            (Action::VolumeUp, KeyBinding::new(Key::ArrowUp)),
+            (Action::VolumeDown, KeyBinding::new(Key::ArrowDown)),
+            (Action::NextChapter, KeyBinding::new(Key::PageDown)),
+            (Action::PreviousChapter, KeyBinding::new(Key::PageUp)),
+        ]);
+
+        Self { bindings }
+    }
+}
+
+impl Keybindings {
+    pub fn get(&self, action: Action) -> Option<KeyBinding> {
+        self.bindings.get(&action).copied()
+    }
+
+    pub fn set(&mut self, action: Action, binding: Option<KeyBinding>) {
+        match binding {
+            Some(binding) => self.bindings.insert(action, binding),
+            None => self.bindings.remove(&action),
+        };
+    }
+
+    /// Bindings ordered so the ones with the most modifiers come first. egui ignores extra
+    /// modifiers when matching, so `Ctrl+Space` has to be checked before `Space`.
+    pub fn by_specificity(&self) -> Vec<(Action, KeyBinding)> {
+        let mut bindings = self
+            .bindings
+            .iter()
+            .map(|(action, binding)| (*action, *binding))
+            .collect::<Vec<_>>();
+        bindings.sort_by_key(|(_, binding)| std::cmp::Reverse(binding.modifier_count()));
+        bindings
+    }
+
+    /// Other actions bound to the same shortcut as `action`.
+    pub fn conflicts(&self, action: Action) -> Vec<Action> {
+        let Some(binding) = self.get(action) else {
+            return Vec::new();
+        };
+
+        self.bindings
+            .iter()
+            .filter(|(other, other_binding)| **other != action && **other_binding == binding)
+            .map(|(other, _)| *other)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bindings_round_trip_through_text() {
+        let binding = KeyBinding {
+            key: Key::ArrowRight,
+            ctrl: true,
+            shift: true,
+            alt: false,
+        };
+
+        assert_eq!(binding.to_string(), "Ctrl+Shift+Right");
+        assert_eq!(KeyBinding::try_from(binding.to_string()), Ok(binding));
+        assert!(KeyBinding::try_from("Hyper+Q".to_string()).is_err());
+    }
+
+    #[test]
+    fn detects_conflicting_bindings() {
+        let mut keybindings = Keybindings::default();
+        assert!(Action::ALL
+            .iter()
+            .all(|action| keybindings.conflicts(*action).is_empty()));
+
+        keybindings.set(Action::Stop, Some(KeyBinding::new(Key::Space)));
+
+        assert_eq!(keybindings.conflicts(Action::PlayPause), [Action::Stop]);
+        assert_eq!(keybindings.conflicts(Action::Stop), [Action::PlayPause]);
+    }
+}
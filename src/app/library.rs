@@ -1,11 +1,17 @@
+use crate::app::settings::NormalizationMode;
+use crate::app::{spelling, unix_timestamp};
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Library {
     paths: Vec<LibraryPath>,
     items: Vec<LibraryItem>,
-    library_view: LibraryView,
+    // One view per grouping, e.g. by album or by artist.
+    views: Vec<LibraryView>,
 }
 
 impl Default for Library {
@@ -19,10 +25,7 @@ impl Library {
         Self {
             paths: Vec::new(),
             items: Vec::new(),
-            library_view: LibraryView {
-                view_type: ViewType::Album,
-                containers: Vec::new(),
-            },
+            views: Vec::new(),
         }
     }
 
@@ -55,25 +58,22 @@ impl Library {
             self.items.swap_remove(idx);
         }
 
-        // Remove the view container items
-        for container in &mut self.library_view.containers {
-            while let Some(ct_idx) = container
-                .items
-                .iter()
-                .position(|ci| ci.library_id() == path_id)
-            {
-                container.items.swap_remove(ct_idx);
+        for view in &mut self.views {
+            // Remove the view container items
+            for container in &mut view.containers {
+                while let Some(ct_idx) = container
+                    .items
+                    .iter()
+                    .position(|ci| ci.library_id() == path_id)
+                {
+                    container.items.swap_remove(ct_idx);
+                }
             }
-        }
 
-        // Remove the empty containers
-        while let Some(idx) = self
-            .library_view
-            .containers
-            .iter()
-            .position(|ct| ct.items.is_empty())
-        {
-            self.library_view.containers.swap_remove(idx);
+            // Remove the empty containers
+            while let Some(idx) = view.containers.iter().position(|ct| ct.items.is_empty()) {
+                view.containers.swap_remove(idx);
+            }
         }
     }
 
@@ -85,22 +85,174 @@ impl Library {
         }
     }
 
+    pub fn set_path_skipped(&mut self, id: LibraryPathId, skipped: usize) {
+        for path in self.paths.iter_mut() {
+            if path.id() == id {
+                path.skipped = skipped;
+            }
+        }
+    }
+
     pub fn items(&self) -> &Vec<LibraryItem> {
         self.items.as_ref()
     }
 
-    pub fn view(&self) -> &LibraryView {
-        &self.library_view
+    /// `None` for views that aren't a grouping, like `ViewType::RecentlyAdded`, and before
+    /// anything was imported.
+    pub fn view(&self, view_type: ViewType) -> Option<&LibraryView> {
+        self.views.iter().find(|view| view.view_type == view_type)
     }
 
     pub fn add_item(&mut self, library_item: LibraryItem) {
         self.items.push(library_item);
     }
 
-    pub fn add_view(&mut self, library_view: LibraryView) {
-        let mut new = library_view.containers.clone();
+    /// Takes the item at `path` out, e.g. after the file was deleted. The views are only
+    /// updated by `rebuild_views`.
+    pub fn remove_item(&mut self, path: &std::path::Path) {
+        self.items.retain(|item| item.path != path);
+    }
+
+    /// Items imported within the last `max_age_secs` seconds (all items with `None`), newest
+    /// first. Items without a known import time come last.
+    pub fn recently_added(&self, max_age_secs: Option<u64>) -> Vec<&LibraryItem> {
+        let now = unix_timestamp();
+        let mut items = self
+            .items
+            .iter()
+            .filter(|item| match (max_age_secs, item.added_at()) {
+                (None, _) => true,
+                (Some(max_age_secs), Some(added_at)) => {
+                    now.saturating_sub(added_at) <= max_age_secs
+                }
+                (Some(_), None) => false,
+            })
+            .collect::<Vec<_>>();
+        items.sort_by_key(|item| std::cmp::Reverse(item.added_at()));
+
+        items
+    }
+
+    /// Runs `update` over every item, including the copies in the view.
+    pub fn update_items(&mut self, mut update: impl FnMut(&mut LibraryItem)) {
+        self.items.iter_mut().for_each(&mut update);
+        for view in &mut self.views {
+            for container in &mut view.containers {
+                container.items.iter_mut().for_each(&mut update);
+            }
+        }
+    }
+
+    /// Formats of the items in the library, sorted.
+    pub fn formats(&self) -> Vec<String> {
+        let mut formats = self
+            .items
+            .iter()
+            .filter_map(|item| item.format())
+            .collect::<Vec<_>>();
+        formats.sort();
+        formats.dedup();
+
+        formats
+    }
 
-        self.library_view.containers.append(&mut new);
+    /// Groups all items again, after an import or for state saved before there were views by
+    /// artist.
+    pub fn rebuild_views(&mut self, merge_spellings: bool) {
+        self.views = build_views(&self.items, merge_spellings);
+    }
+}
+
+// Groups `items` for each grouped view type. With `merge_spellings`, albums, artists and genres
+// whose names only differ in case or spacing end up in one group.
+fn build_views(items: &[LibraryItem], merge_spellings: bool) -> Vec<LibraryView> {
+    let key = |name: String| {
+        if merge_spellings {
+            spelling::canonical_key(&name)
+        } else {
+            name
+        }
+    };
+    // The spelling shown for each merged key.
+    let names = |field: fn(&LibraryItem) -> Option<String>| {
+        merge_spellings.then(|| {
+            let names = items.iter().filter_map(field).collect_vec();
+            spelling::canonical_names(names.iter().map(String::as_str))
+        })
+    };
+    let album_names = names(LibraryItem::album);
+    let artist_names = names(LibraryItem::artist);
+    let genre_names = names(LibraryItem::genre);
+    let name = |names: &Option<HashMap<String, String>>, key: String| {
+        names
+            .as_ref()
+            .and_then(|names| names.get(&key).cloned())
+            .unwrap_or(key)
+    };
+
+    vec![
+        // Compilations are kept apart from regular albums of the same name, e.g. a "Greatest
+        // Hits" by one artist.
+        grouped_view(
+            ViewType::Album,
+            items,
+            |item| {
+                let album = item.album().unwrap_or("unknown album".to_string());
+                (key(album), item.is_compilation())
+            },
+            |(album, is_compilation)| {
+                let mut album_name = name(&album_names, album);
+                if is_compilation {
+                    album_name.push_str(" (Various Artists)");
+                }
+                album_name
+            },
+        ),
+        grouped_view(
+            ViewType::Artist,
+            items,
+            |item| key(item.artist().unwrap_or("unknown artist".to_string())),
+            |artist| name(&artist_names, artist),
+        ),
+        grouped_view(
+            ViewType::Genre,
+            items,
+            |item| key(item.genre().unwrap_or("unknown genre".to_string())),
+            |genre| name(&genre_names, genre),
+        ),
+        // Oldest first, undated items last.
+        grouped_view(
+            ViewType::Year,
+            items,
+            |item| (item.year().is_none(), item.year()),
+            |(_, year)| year.map_or("unknown year".to_string(), |year| year.to_string()),
+        ),
+    ]
+}
+
+fn grouped_view<K: PartialEq + Ord>(
+    view_type: ViewType,
+    items: &[LibraryItem],
+    key: impl Fn(&LibraryItem) -> K,
+    name: impl Fn(K) -> String,
+) -> LibraryView {
+    // In order for group by to work from itertools, items must be consecutive, so sort them first.
+    let mut items = items.to_vec();
+    items.sort_by_key(&key);
+
+    let containers = items
+        .into_iter()
+        .group_by(&key)
+        .into_iter()
+        .map(|(group, items)| LibraryItemContainer {
+            name: name(group),
+            items: items.collect(),
+        })
+        .collect();
+
+    LibraryView {
+        view_type,
+        containers,
     }
 }
 
@@ -109,6 +261,9 @@ pub struct LibraryPath {
     id: LibraryPathId,
     path: PathBuf,
     status: LibraryPathStatus,
+    // Files left out of the last import because their tags couldn't be read.
+    #[serde(default)]
+    skipped: usize,
 }
 
 impl LibraryPath {
@@ -117,7 +272,9 @@ impl LibraryPath {
         Self {
             path,
             status: LibraryPathStatus::NotImported,
-            id: LibraryPathId::new(rand::thread_rng().gen()),
+            // Short of `LibraryPathId::NONE`.
+            id: LibraryPathId::new(rand::thread_rng().gen_range(0..usize::MAX)),
+            skipped: 0,
         }
     }
 
@@ -136,12 +293,20 @@ impl LibraryPath {
     pub fn set_status(&mut self, status: LibraryPathStatus) {
         self.status = status;
     }
+
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
 }
 
 #[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub struct LibraryPathId(usize);
 
 impl LibraryPathId {
+    /// Stands in for tracks that aren't part of any library path: opened from elsewhere, read
+    /// from a playlist file, or saved before items kept track of the folder they came from.
+    pub const NONE: Self = Self(usize::MAX);
+
     pub fn new(id: usize) -> Self {
         Self(id)
     }
@@ -153,22 +318,71 @@ pub enum LibraryPathStatus {
     Imported,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+// Everything besides the path must have a serde default so items saved by older versions still
+// load when fields are added.
 pub struct LibraryItem {
+    /// The library path the item was imported from, so removing that folder takes exactly its
+    /// tracks along.
+    #[serde(default = "no_library_path")]
     library_id: LibraryPathId,
     path: PathBuf,
+    #[serde(default)]
     title: Option<String>,
+    #[serde(default)]
     artist: Option<String>,
+    #[serde(default)]
     album: Option<String>,
+    #[serde(default)]
     year: Option<i32>,
+    #[serde(default)]
     genre: Option<String>,
+    #[serde(default)]
     track_number: Option<u32>,
+    #[serde(default)]
+    disc_number: Option<u32>,
+    #[serde(default = "random_key")]
     key: usize,
+    /// Seconds since the unix epoch when the item was imported. Unknown for items imported
+    /// before this was tracked.
+    #[serde(default)]
+    added_at: Option<u64>,
+    /// Part of an album with tracks by various artists, e.g. a soundtrack.
+    #[serde(default)]
+    compilation: bool,
+    /// DR value measured the last time the track played through.
+    #[serde(default)]
+    dynamic_range: Option<u32>,
+    /// ReplayGain adjustments in dB, from the file's tags or measured the first time an untagged
+    /// track played through. The peaks are the ones the gains were worked out with.
+    #[serde(default)]
+    track_gain_db: Option<f32>,
+    #[serde(default)]
+    track_peak: Option<f32>,
+    #[serde(default)]
+    album_gain_db: Option<f32>,
+    #[serde(default)]
+    album_peak: Option<f32>,
+    /// Cover image found in the album folder at import.
+    #[serde(default)]
+    cover_path: Option<PathBuf>,
+    /// The file's modification time when its tags were read, in seconds since the unix epoch.
+    /// Rescanning skips files that haven't changed since.
+    #[serde(default)]
+    modified_at: Option<u64>,
+}
+
+fn no_library_path() -> LibraryPathId {
+    LibraryPathId::NONE
+}
+
+fn random_key() -> usize {
+    use rand::Rng; // TODO - use ULID?
+    rand::thread_rng().gen()
 }
 
 impl LibraryItem {
     pub fn new(path: PathBuf, library_id: LibraryPathId) -> Self {
-        use rand::Rng; // TODO - use ULID?
         Self {
             library_id,
             path,
@@ -178,7 +392,17 @@ impl LibraryItem {
             year: None,
             genre: None,
             track_number: None,
-            key: rand::thread_rng().gen(),
+            disc_number: None,
+            key: random_key(),
+            added_at: Some(unix_timestamp()),
+            compilation: false,
+            dynamic_range: None,
+            track_gain_db: None,
+            track_peak: None,
+            album_gain_db: None,
+            album_peak: None,
+            cover_path: None,
+            modified_at: None,
         }
     }
 
@@ -256,23 +480,189 @@ impl LibraryItem {
     pub fn track_number(&self) -> Option<u32> {
         self.track_number
     }
+
+    pub fn set_disc_number(&mut self, disc_number: Option<u32>) -> Self {
+        self.disc_number = disc_number;
+        self.to_owned()
+    }
+
+    pub fn disc_number(&self) -> Option<u32> {
+        self.disc_number
+    }
+
+    pub fn added_at(&self) -> Option<u64> {
+        self.added_at
+    }
+
+    pub fn set_compilation(&mut self, compilation: bool) -> Self {
+        self.compilation = compilation;
+        self.to_owned()
+    }
+
+    pub fn is_compilation(&self) -> bool {
+        self.compilation
+    }
+
+    pub fn set_dynamic_range(&mut self, dynamic_range: Option<u32>) -> Self {
+        self.dynamic_range = dynamic_range;
+        self.to_owned()
+    }
+
+    pub fn dynamic_range(&self) -> Option<u32> {
+        self.dynamic_range
+    }
+
+    pub fn set_track_gain(&mut self, gain_db: Option<f32>, peak: Option<f32>) -> Self {
+        self.track_gain_db = gain_db;
+        self.track_peak = peak;
+        self.to_owned()
+    }
+
+    pub fn track_gain_db(&self) -> Option<f32> {
+        self.track_gain_db
+    }
+
+    pub fn set_album_gain(&mut self, gain_db: Option<f32>, peak: Option<f32>) -> Self {
+        self.album_gain_db = gain_db;
+        self.album_peak = peak;
+        self.to_owned()
+    }
+
+    pub fn album_gain_db(&self) -> Option<f32> {
+        self.album_gain_db
+    }
+
+    /// What the samples are multiplied by to play the track at the loudness `mode` asks for. A
+    /// gain is held back as far as its peak needs to stay below full scale.
+    pub fn normalization_gain(&self, mode: NormalizationMode) -> f32 {
+        let (gain_db, peak) = match mode {
+            NormalizationMode::Off => return 1.0,
+            NormalizationMode::Album if self.album_gain_db.is_some() => {
+                (self.album_gain_db, self.album_peak)
+            }
+            NormalizationMode::Track | NormalizationMode::Album => {
+                (self.track_gain_db, self.track_peak)
+            }
+        };
+        let Some(gain_db) = gain_db else {
+            return 1.0;
+        };
+
+        let gain = 10.0f32.powf(gain_db / 20.0);
+        match peak.filter(|peak| *peak > 0.0) {
+            Some(peak) => gain.min(1.0 / peak),
+            None => gain,
+        }
+    }
+
+    pub fn set_cover_path(&mut self, cover_path: Option<&std::path::Path>) -> Self {
+        self.cover_path = cover_path.map(|path| path.to_path_buf());
+        self.to_owned()
+    }
+
+    pub fn cover_path(&self) -> Option<&std::path::Path> {
+        self.cover_path.as_deref()
+    }
+
+    pub fn set_modified_at(&mut self, modified_at: Option<u64>) -> Self {
+        self.modified_at = modified_at;
+        self.to_owned()
+    }
+
+    pub fn modified_at(&self) -> Option<u64> {
+        self.modified_at
+    }
+
+    /// Takes the tag fields from `other`, e.g. after the file was re-tagged. The item keeps its
+    /// identity and import time.
+    pub fn refresh_tags(&mut self, other: &LibraryItem) {
+        self.title.clone_from(&other.title);
+        self.artist.clone_from(&other.artist);
+        self.album.clone_from(&other.album);
+        self.year = other.year;
+        self.genre.clone_from(&other.genre);
+        self.track_number = other.track_number;
+        self.disc_number = other.disc_number;
+        self.compilation = other.compilation;
+        self.track_gain_db = other.track_gain_db;
+        self.track_peak = other.track_peak;
+        self.album_gain_db = other.album_gain_db;
+        self.album_peak = other.album_peak;
+        self.cover_path.clone_from(&other.cover_path);
+        self.modified_at = other.modified_at;
+    }
+
+    /// File format as shown to the user, e.g. "MP3". Taken from the file extension.
+    pub fn format(&self) -> Option<String> {
+        self.path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_uppercase())
+    }
+
+    /// Whether the title, artist, album or genre contains `query`, ignoring case. An empty
+    /// query matches everything.
+    pub fn matches_search(&self, query: &str) -> bool {
+        let query = query.trim().to_lowercase();
+        [&self.title, &self.artist, &self.album, &self.genre]
+            .into_iter()
+            .flatten()
+            .any(|field| field.to_lowercase().contains(&query))
+            || query.is_empty()
+    }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LibraryView {
     pub view_type: ViewType,
     pub containers: Vec<LibraryItemContainer>,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LibraryItemContainer {
     pub name: String,
     pub items: Vec<LibraryItem>,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub enum ViewType {
+    #[default]
     Album,
     Artist,
     Genre,
+    Year,
+    RecentlyAdded,
+}
+
+impl std::fmt::Display for ViewType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ViewType::Album => write!(f, "Albums"),
+            ViewType::Artist => write!(f, "Artists"),
+            ViewType::Genre => write!(f, "Genres"),
+            ViewType::Year => write!(f, "Years"),
+            ViewType::RecentlyAdded => write!(f, "Recently Added"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn items_saved_without_a_library_path_stay_when_folders_are_removed() {
+        let old_item: LibraryItem =
+            serde_json::from_str(r#"{"path": "/music/old.mp3", "title": "Old"}"#).unwrap();
+        assert_eq!(old_item.library_id(), LibraryPathId::NONE);
+
+        let mut library = Library::new();
+        library.add_path(PathBuf::from("/music"));
+        let path_id = library.paths()[0].id();
+        library.add_item(LibraryItem::new(PathBuf::from("/music/new.mp3"), path_id));
+        library.add_item(old_item);
+
+        library.remove_path(path_id);
+        assert_eq!(library.items().len(), 1);
+        assert_eq!(library.items()[0].path(), PathBuf::from("/music/old.mp3"));
+    }
 }
@@ -0,0 +1,110 @@
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+// Length of each loudness reading.
+const BLOCK_MS: u64 = 100;
+// How much history is kept, in blocks.
+const MAX_BLOCKS: usize = 300;
+
+/// Quietest level that's told apart from silence, in dBFS.
+pub const FLOOR_DB: f32 = -60.0;
+
+/// Rolling RMS level of the playing track in dBFS, one reading per 100ms over the last 30
+/// seconds. Fed from the same sample stream as the scope.
+pub struct LoudnessHistory {
+    // Track the readings belong to, so they can be dropped on a track change.
+    pub track: Option<PathBuf>,
+    blocks: VecDeque<f32>,
+    block_len: usize,
+    sum_squares: f64,
+    count: usize,
+}
+
+impl Default for LoudnessHistory {
+    fn default() -> Self {
+        Self::new(48000, 2)
+    }
+}
+
+impl LoudnessHistory {
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        Self {
+            track: None,
+            blocks: VecDeque::with_capacity(MAX_BLOCKS),
+            block_len: block_len(sample_rate, channels),
+            sum_squares: 0.0,
+            count: 0,
+        }
+    }
+
+    pub fn set_format(&mut self, sample_rate: u32, channels: usize) {
+        self.block_len = block_len(sample_rate, channels);
+        self.sum_squares = 0.0;
+        self.count = 0;
+    }
+
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+        self.sum_squares = 0.0;
+        self.count = 0;
+    }
+
+    pub fn push_samples(&mut self, samples: &[f32]) {
+        for sample in samples {
+            self.sum_squares += (*sample as f64).powi(2);
+            self.count += 1;
+
+            if self.count >= self.block_len {
+                let rms = (self.sum_squares / self.count as f64).sqrt() as f32;
+                if self.blocks.len() == MAX_BLOCKS {
+                    self.blocks.pop_front();
+                }
+                self.blocks.push_back(to_db(rms));
+
+                self.sum_squares = 0.0;
+                self.count = 0;
+            }
+        }
+    }
+
+    /// Readings in dBFS, oldest first.
+    pub fn blocks(&self) -> impl ExactSizeIterator<Item = f32> + '_ {
+        self.blocks.iter().copied()
+    }
+
+    pub fn capacity(&self) -> usize {
+        MAX_BLOCKS
+    }
+}
+
+fn block_len(sample_rate: u32, channels: usize) -> usize {
+    ((sample_rate as u64 * BLOCK_MS / 1000) as usize * channels.max(1)).max(1)
+}
+
+fn to_db(rms: f32) -> f32 {
+    if rms <= 0.0 {
+        FLOOR_DB
+    } else {
+        (20.0 * rms.log10()).max(FLOOR_DB)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measures_one_reading_per_block() {
+        // 10 samples per block.
+        let mut loudness = LoudnessHistory::new(100, 1);
+
+        loudness.push_samples(&[1.0, -1.0].repeat(5));
+        loudness.push_samples(&[0.0; 10]);
+        loudness.push_samples(&[0.5; 5]);
+
+        let blocks = loudness.blocks().collect::<Vec<_>>();
+        assert_eq!(blocks.len(), 2);
+        assert!(blocks[0].abs() < 0.001);
+        assert_eq!(blocks[1], FLOOR_DB);
+    }
+}
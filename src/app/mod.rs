@@ -1,26 +1,64 @@
-use library::{
-    Library, LibraryItem, LibraryItemContainer, LibraryPath, LibraryPathId, LibraryPathStatus,
-    LibraryView, ViewType,
-};
-use player::Player;
+use crate::audio_check::AudioCheck;
+use crate::dsp::{FadeCurve, Limiter, NightMode, PanLaw};
+use crate::media_controls::MediaControls;
+use crate::preview::Preview;
+use crate::taskbar::TaskbarProgress;
+use crate::tray::Tray;
+use bookmarks::Bookmarks;
+use config_watcher::ConfigWatcher;
+use cover_art::CoverArt;
+use dynamic_range::DynamicRangeMeter;
+use export::{Export, ExportOptions};
+use folder_pattern::FolderPattern;
+use history::History;
+use library::{Library, LibraryItem, LibraryPath, LibraryPathId, LibraryPathStatus, ViewType};
+use loudness::LoudnessHistory;
+use observer::{PlaybackEvent, PlaybackObserver};
+use player::{Player, PlayerError, TrackState};
 use playlist::Playlist;
+use replay_gain::LoudnessMeter;
 use scope::Scope;
 use serde::{Deserialize, Serialize};
+use session::Session;
+use settings::{
+    AlbumAction, AudioThreadPriority, NetworkRetry, RepeatMode, SeekPrecision, Settings,
+    ShuffleMode, UnreadableTags,
+};
+use std::collections::HashMap;
 use std::sync::atomic::AtomicBool;
 use std::sync::mpsc::{Receiver, Sender};
 use std::sync::Arc;
+use symphonia::core::audio::SignalSpec;
+use symphonia::core::units::TimeBase;
+use window_geometry::WindowGeometry;
 
-use itertools::Itertools;
-
-use id3::{Tag, TagLike};
 use rayon::prelude::*;
 
 mod app_impl;
+pub mod bookmarks;
 mod components;
+mod config_watcher;
+pub mod cover_art;
+mod dynamic_range;
+pub mod export;
+mod folder_pattern;
+mod genre;
+mod history;
+pub mod keybindings;
 mod library;
+mod loudness;
+pub mod observer;
 pub mod player;
 mod playlist;
+mod replay_gain;
 pub mod scope;
+mod session;
+pub mod settings;
+mod shuffle;
+mod spectrum;
+mod spelling;
+mod tags;
+pub mod window_geometry;
 
 pub enum AudioCommand {
     Stop,
@@ -28,30 +66,230 @@ pub enum AudioCommand {
     Pause,
     Seek(u64),
     LoadFile(std::path::PathBuf),
+    /// Plays another track of a file holding several, by its index among the file's tracks. The
+    /// playing or paused file switches to the start of it and stays playing or paused. While
+    /// stopped it's played once the file starts again. Loading another file goes back to its
+    /// first playable track.
     Select(usize),
     SetVolume(f32),
+    SetBalance(f32),
+    SetPanLaw(PanLaw),
+    SetReaderOptions(ReaderOptions),
+    SetOutputHost(Option<String>),
+    SetOutputDevice(Option<String>),
+    SetTrackGap(u64),
+    SetCrossfade(f32),
+    SetSeekPrecision(SeekPrecision),
+    SetScopeWindow(u64),
+    SetDucked(bool),
+    SetNightMode(NightMode),
+    /// Loudness normalization of the loaded track, as a factor on its samples.
+    SetTrackGain(f32),
+    SetEqBand {
+        band: usize,
+        gain_db: f32,
+    },
+    SetFadeCurve(FadeCurve),
+    SetCrossfadeCurve(FadeCurve),
+    SetLimiter(Limiter),
+    SetThreadPriority(AudioThreadPriority),
+    SetNetworkRetry(NetworkRetry),
+    /// Reopen the playing track from disk where it is.
+    Reload,
+    /// The track to go straight on with when the current one ends, `None` when the UI can't tell
+    /// yet, and the gain it plays at. Loading a file forgets it.
+    EnqueueNext {
+        path: Option<std::path::PathBuf>,
+        gain: f32,
+    },
+}
+
+/// Options used when opening a track. Changing them reopens the playing track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReaderOptions {
+    pub gapless: bool,
+    pub verify: bool,
+}
+
+impl Default for ReaderOptions {
+    fn default() -> Self {
+        Self {
+            gapless: true,
+            verify: true,
+        }
+    }
 }
 
 pub enum UiCommand {
     AudioFinished,
+    /// The track ended and the audio thread went on with the one announced to follow it, without
+    /// a gap.
+    ContinuedWith(std::path::PathBuf),
     TotalTrackDuration(u64),
+    /// The track is a stream that doesn't say how long it is.
+    UnknownDuration,
+    /// Position of the playing track as a timestamp in its time base, not in seconds, so seeking
+    /// relative to it stays exact. `StreamInfo::time_base` turns it into the elapsed time. Sent
+    /// as the elapsed whole seconds tick over, and for every packet of a track without a time base.
     CurrentTimestamp(u64),
+    Chapters(Vec<Chapter>),
+    OutputHost(String),
+    OutputDevice(String),
+    /// Why the track couldn't be played, sent ahead of `TrackFailed`.
+    PlaybackError(String),
+    /// The track couldn't be opened or had no audio to play.
+    TrackFailed(std::path::PathBuf),
+    StreamInfo(StreamInfo),
+    /// How the loaded track is encoded, sent when it's loaded.
+    TrackFormat(TrackFormat),
+    /// The audio thread stopped making progress and playback was stopped.
+    PlaybackStalled,
+    /// A track is being opened.
+    Loading(bool),
+    /// Opening the track timed out and is retried, with the number of the next attempt. `None`
+    /// once it opened or was given up on.
+    Connecting(Option<u32>),
+    PlaybackStatus(PlaybackStatus),
+    /// The limiter started or stopped holding peaks down.
+    Limiting(bool),
+    /// The scope's ring buffer was reallocated for a new output rate or window length.
+    ScopeBuffer(ScopeBuffer),
+    Playback(PlaybackEvent),
+}
+
+/// Replacement for `App::played_audio_buffer`, sized to hold the scope window at the output's
+/// sample rate.
+pub struct ScopeBuffer {
+    pub consumer: rb::Consumer<f32>,
+    pub sample_rate: u32,
+    pub channels: usize,
+    pub len: usize,
+}
+
+/// The decoded signal next to what the output device is fed, to show conversions the engine
+/// makes on the way.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamInfo {
+    pub source: SignalSpec,
+    /// What the track's timestamps count in, which isn't always samples at `source.rate`.
+    pub time_base: TimeBase,
+    pub output_rate: u32,
+    pub output_channels: usize,
+}
+
+impl StreamInfo {
+    pub fn is_resampled(&self) -> bool {
+        self.source.rate != self.output_rate
+    }
+
+    pub fn is_remixed(&self) -> bool {
+        self.source.channels.count() != self.output_channels
+    }
+}
+
+/// How a track is encoded, as far as its container tells. Any of it may be missing.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrackFormat {
+    pub sample_rate: Option<u32>,
+    pub channels: Option<usize>,
+    pub bits_per_sample: Option<u32>,
+    /// Short name of the codec, like "FLAC" or "MP3".
+    pub codec_name: Option<String>,
+    /// Average over the whole file.
+    pub bitrate_kbps: Option<u32>,
+}
+
+/// What playback is doing as far as the listener can tell, e.g. a track that is meant to play
+/// but waits on a slow source is `Buffering`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PlaybackStatus {
+    Playing,
+    Buffering,
+    Paused,
+    #[default]
+    Stopped,
+    Error,
+}
+
+/// A chapter marker inside the playing track.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chapter {
+    pub title: Option<String>,
+    pub start_ts: u64,
 }
 
 pub enum LibraryCommand {
-    AddView(LibraryView),
     AddItem(LibraryItem),
+    /// The file was imported before and changed since.
+    UpdateItem(LibraryItem),
+    /// The file was imported before and is gone.
+    RemoveItem(std::path::PathBuf),
+    /// The library path finished importing.
     AddPathId(LibraryPathId),
+    /// Files of a library path left out because their tags couldn't be read.
+    Skipped(LibraryPathId, usize),
+    /// Files read so far out of those that need reading.
+    ImportProgress(LibraryPathId, usize, usize),
+}
+
+// Volume for output devices that haven't been played on before.
+const DEFAULT_DEVICE_VOLUME: f32 = 0.5;
+
+/// The main areas of the window that have a search box.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Panel {
+    Library,
+    #[default]
+    Playlist,
 }
 
+impl Panel {
+    /// Id of the panel's search box, for moving keyboard focus to it.
+    pub fn search_id(&self) -> eframe::egui::Id {
+        match self {
+            Panel::Library => eframe::egui::Id::new("library_search"),
+            Panel::Playlist => eframe::egui::Id::new("playlist_search"),
+        }
+    }
+}
+
+/// Bumped whenever the saved state changes in a way serde defaults can't cover. `App::migrate`
+/// brings older state up to date one version at a time.
+const STATE_VERSION: u32 = 3;
+
 #[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct App {
+    // Missing in state saved before versioning, which is version 0.
+    #[serde(default)]
+    pub version: u32,
+
     pub library: Library,
 
     pub playlists: Vec<Playlist>,
 
     pub current_playlist_idx: Option<usize>,
 
+    #[serde(default)]
+    pub settings: Settings,
+
+    #[serde(default)]
+    pub history: History,
+
+    #[serde(default)]
+    pub bookmarks: Bookmarks,
+
+    #[serde(default)]
+    pub session: Session,
+
+    /// Where the window was when the app was closed.
+    #[serde(default)]
+    pub window: Option<WindowGeometry>,
+
+    // Last volume used on each output device, by device name.
+    #[serde(default)]
+    pub device_volumes: HashMap<String, f32>,
+
     #[serde(skip_serializing, skip_deserializing)]
     pub player: Option<Player>,
 
@@ -73,40 +311,346 @@ pub struct App {
     #[serde(skip_serializing, skip_deserializing)]
     pub temp_buf: Option<Vec<f32>>,
 
+    // Channels interleaved in `played_audio_buffer`; the scope only draws the first one.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub scope_channels: usize,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub loudness: LoudnessHistory,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub dynamic_range: DynamicRangeMeter,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub loudness_meter: LoudnessMeter,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub cover_art: CoverArt,
+
+    // Shown in the footer while nothing is playing, e.g. why play didn't start anything.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub status_hint: Option<String>,
+
+    // Last track that failed to play and why, shown in the footer until dismissed.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub playback_error: Option<String>,
+
+    // Playlist the table showed last frame, to restore the scroll position on tab switches.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub shown_playlist_id: Option<u64>,
+
+    // Playing track the table last scrolled into view, to scroll again only when it changes.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub scrolled_to_track: Option<std::path::PathBuf>,
+
+    // When the playlist table was last scrolled by hand. Following the playing track waits a
+    // while after that.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub playlist_scrolled_at: Option<std::time::Instant>,
+
+    // Devices offered in a playlist's "Play on" menu. Listing them is slow, so it happens once
+    // each time the menu opens rather than every frame it's shown.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub output_devices: Option<Vec<String>>,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub preview: Option<Preview>,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub observers: Vec<Box<dyn PlaybackObserver>>,
+
+    // Last "Test audio output" run, its result stays in the footer until dismissed.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub audio_check: Option<AudioCheck>,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub taskbar: TaskbarProgress,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub media_controls: MediaControls,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub tray: Tray,
+
     #[serde(skip_serializing, skip_deserializing)]
     pub quit: bool,
 
     #[serde(skip_serializing, skip_deserializing)]
     pub lib_config_selections: std::collections::HashSet<LibraryPathId>,
 
+    // Files read and to read for each library path being imported.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub import_progress: HashMap<LibraryPathId, (usize, usize)>,
+
     #[serde(skip_serializing, skip_deserializing)]
     pub is_library_cfg_open: bool,
 
     #[serde(skip_serializing, skip_deserializing)]
     pub is_processing_ui_change: Option<Arc<AtomicBool>>,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub is_preferences_open: bool,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub show_playlist_paths: bool,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub config_watcher: ConfigWatcher,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub is_export_open: bool,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub export_options: ExportOptions,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub export: Option<Export>,
+
+    pub library_view_type: ViewType,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub show_all_recently_added: bool,
+
+    // Only library items of this format are shown, all of them with `None`.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub library_format_filter: Option<String>,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub library_search: String,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub playlist_search: String,
+
+    // Panel the pointer was last over, its search box is the one Ctrl+F focuses.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub active_panel: Panel,
+
+    // Playlists already played through since playback was last started by hand, so linked
+    // playlists can't loop forever.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub played_playlists: std::collections::HashSet<u64>,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub is_bookmarks_open: bool,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub is_eq_open: bool,
+
+    // The window was checked to be on screen after it opened.
+    #[serde(skip_serializing, skip_deserializing)]
+    is_window_checked: bool,
+
+    // The window was closed or hidden to the tray.
+    #[serde(skip_serializing, skip_deserializing)]
+    is_window_hidden: bool,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub is_shortcuts_open: bool,
+
+    // Id of the playlist whose tab is being renamed, and the name typed so far.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub renaming_playlist: Option<(u64, String)>,
+
+    // Action waiting for its new shortcut to be pressed in the preferences.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub capturing_keybinding: Option<keybindings::Action>,
 }
 
 impl Default for App {
     fn default() -> Self {
         Self {
+            version: STATE_VERSION,
             library: Library::new(),
             playlists: vec![],
             current_playlist_idx: None,
+            settings: Settings::default(),
+            history: History::default(),
+            bookmarks: Bookmarks::default(),
+            session: Session::default(),
+            window: None,
+            device_volumes: HashMap::new(),
             player: None,
             playlist_idx_to_remove: None,
             library_cmd_tx: None,
             library_cmd_rx: None,
+            import_progress: HashMap::new(),
             played_audio_buffer: None,
             scope: Some(Scope::new()),
             temp_buf: Some(vec![0.0f32; 4096]),
+            scope_channels: 2,
+            loudness: LoudnessHistory::default(),
+            dynamic_range: DynamicRangeMeter::default(),
+            loudness_meter: LoudnessMeter::default(),
+            cover_art: CoverArt::default(),
+            status_hint: None,
+            playback_error: None,
+            shown_playlist_id: None,
+            scrolled_to_track: None,
+            playlist_scrolled_at: None,
+            output_devices: None,
+            preview: None,
+            observers: Vec::new(),
+            audio_check: None,
+            taskbar: TaskbarProgress::default(),
+            media_controls: MediaControls::default(),
+            tray: Tray::default(),
             quit: false,
             lib_config_selections: Default::default(),
             is_library_cfg_open: false,
             is_processing_ui_change: None,
+            is_preferences_open: false,
+            show_playlist_paths: false,
+            config_watcher: ConfigWatcher::default(),
+            is_export_open: false,
+            export_options: ExportOptions::default(),
+            export: None,
+            library_view_type: ViewType::Album,
+            show_all_recently_added: false,
+            library_format_filter: None,
+            library_search: String::new(),
+            playlist_search: String::new(),
+            active_panel: Panel::default(),
+            played_playlists: Default::default(),
+            renaming_playlist: None,
+            is_bookmarks_open: false,
+            is_eq_open: false,
+            is_window_checked: false,
+            is_window_hidden: false,
+            is_shortcuts_open: false,
+            capturing_keybinding: None,
+        }
+    }
+}
+
+// Pushes the settings the audio thread cares about.
+fn push_audio_settings(player: &mut Player, settings: &Settings) -> Result<(), PlayerError> {
+    player.set_pan_law(settings.pan_law)?;
+    player.set_reader_options(ReaderOptions {
+        gapless: settings.gapless,
+        verify: settings.verify_decoding,
+    })?;
+    player.set_output_host(settings.output_host.clone())?;
+    player.set_track_gap(settings.track_gap_ms)?;
+    player.set_crossfade(settings.crossfade_secs)?;
+    player.set_crossfade_curve(settings.crossfade_curve)?;
+    player.set_seek_precision(settings.seek_precision)?;
+    player.set_scope_window(settings.scope_window_ms)?;
+    player.set_night_mode(settings.night_mode)?;
+    player.set_fade_curve(settings.fade_curve)?;
+    player.set_limiter(settings.limiter)?;
+    for (band, gain_db) in settings.eq_gains_db.into_iter().enumerate() {
+        player.set_eq_band(band, gain_db)?;
+    }
+    player.set_thread_priority(settings.audio_thread_priority)?;
+    player.set_network_retry(settings.network_retry)?;
+    player.set_normalization(settings.normalization)
+}
+
+fn file_modified_at(path: &std::path::Path) -> Option<u64> {
+    let modified = std::fs::metadata(path).and_then(|metadata| metadata.modified());
+    modified
+        .ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()
+        .map(|duration| duration.as_secs())
+}
+
+// Builds a library item from the file's tags. `None` when they can't be read and such files are
+// skipped.
+fn read_library_item(
+    path: &std::path::Path,
+    path_id: LibraryPathId,
+    options: &ImportOptions,
+) -> Option<LibraryItem> {
+    let mut item = LibraryItem::new(path.to_path_buf(), path_id)
+        .set_cover_path(cover_art::find_folder_cover(path).as_deref())
+        .set_modified_at(file_modified_at(path));
+
+    // Untagged files get what can be told from the folders they're in.
+    if let Some(folder_pattern) = &options.folder_pattern {
+        let inferred = folder_pattern.infer(path);
+        item = item
+            .set_title(inferred.title.as_deref())
+            .set_artist(inferred.artist.as_deref())
+            .set_album(inferred.album.as_deref())
+            .set_year(inferred.year)
+            .set_track_number(inferred.track_number);
+    }
+
+    let item = match tags::read(path) {
+        Some(tags) => item
+            .set_title(
+                tags.title
+                    .as_deref()
+                    .or(item.title().as_deref())
+                    .or(Some("Unknown Title")),
+            )
+            .set_artist(tags.artist.as_deref())
+            .set_album(tags.album.as_deref())
+            .set_year(tags.year.or(item.year()))
+            .set_genre(
+                tags.genre
+                    .map(|genre| {
+                        if options.normalize_genres {
+                            genre::normalize_genre(&genre)
+                        } else {
+                            genre
+                        }
+                    })
+                    .as_deref(),
+            )
+            .set_track_number(tags.track_number.or(item.track_number()))
+            .set_disc_number(tags.disc_number)
+            .set_compilation(tags.is_compilation)
+            .set_track_gain(tags.track_gain_db, tags.track_peak)
+            .set_album_gain(tags.album_gain_db, tags.album_peak),
+        None if options.unreadable_tags == UnreadableTags::Skip => {
+            tracing::warn!("Couldn't read tags, skipping: {:?}", path);
+            return None;
+        }
+        None => {
+            tracing::warn!("Couldn't read tags: {:?}", path);
+            let file_name = path.file_stem().map(|stem| stem.to_string_lossy());
+            let title = item.title();
+            item.set_title(title.as_deref().or(file_name.as_deref()))
+        }
+    };
+
+    Some(item)
+}
+
+/// How files are read into the library, taken from the settings when an import starts.
+#[derive(Debug, Clone)]
+struct ImportOptions {
+    normalize_genres: bool,
+    folder_pattern: Option<FolderPattern>,
+    max_depth: Option<usize>,
+    follow_symlinks: bool,
+    unreadable_tags: UnreadableTags,
+}
+
+impl ImportOptions {
+    fn new(settings: &Settings) -> Self {
+        Self {
+            normalize_genres: settings.normalize_genres,
+            folder_pattern: settings
+                .infer_tags_from_folders
+                .then(|| FolderPattern::parse(&settings.folder_pattern)),
+            max_depth: settings.import_max_depth,
+            follow_symlinks: settings.import_follow_symlinks,
+            unreadable_tags: settings.unreadable_tags,
         }
     }
 }
 
+/// Seconds since the unix epoch.
+pub fn unix_timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
 #[derive(Debug, Clone)]
 pub enum TempError {
     MissingAppState,
@@ -122,111 +666,1298 @@ impl App {
     pub fn load() -> Result<Self, TempError> {
         let file = confy::get_configuration_file_path("music_player", None).unwrap();
         println!("Load configuration file {:#?}", file);
-        confy::load("music_player", None).map_err(|_| TempError::MissingAppState)
+
+        match confy::load::<App>("music_player", None) {
+            Ok(mut app) => {
+                app.migrate();
+                Ok(app)
+            }
+            Err(err) => {
+                tracing::error!("Failed to load the app state: {}", err);
+
+                // The defaults get saved over it on exit, keep a copy so nothing is lost for good.
+                let backup = file.with_extension("bak");
+                if file.exists() && std::fs::copy(&file, &backup).is_ok() {
+                    tracing::warn!("Kept a copy of the unreadable state at {:?}", backup);
+                }
+
+                Err(TempError::MissingAppState)
+            }
+        }
+    }
+
+    fn migrate(&mut self) {
+        if self.version > STATE_VERSION {
+            tracing::warn!(
+                "State was saved by a newer version ({}), unknown fields are dropped",
+                self.version
+            );
+        }
+
+        while self.version < STATE_VERSION {
+            match self.version {
+                // Unversioned state only lacks fields that serde fills in with defaults.
+                0 => {}
+                // Repeating used to be on or off, and only for whole playlists.
+                1 => {
+                    if std::mem::take(&mut self.settings.repeat_all) {
+                        self.settings.repeat = RepeatMode::All;
+                    }
+                }
+                // The single view by album became one view per grouping.
+                2 => self.library.rebuild_views(self.settings.merge_spellings),
+                _ => unreachable!(),
+            }
+
+            self.version += 1;
+        }
+
+        // Hand edited or partially migrated state mustn't index out of bounds.
+        if self
+            .current_playlist_idx
+            .is_some_and(|idx| idx >= self.playlists.len())
+        {
+            self.current_playlist_idx = None;
+        }
     }
 
-    pub fn save_state(&self) {
-        let store_result = confy::store("music_player", None, self);
+    pub fn save_state(&mut self) {
+        if let Some(player) = &self.player {
+            self.session = Session::capture(player);
+
+            if let Some(device) = &player.output_device {
+                self.device_volumes
+                    .insert(device.clone(), player.unmuted_volume());
+            }
+        }
+
+        let store_result = confy::store("music_player", None, &*self);
         match store_result {
             Ok(_) => tracing::info!("Store was successful"),
             Err(err) => tracing::error!("Failed to store the app state: {}", err),
         }
+        self.config_watcher.mark_written();
     }
 
-    pub fn quit(&mut self) {
-        self.quit = true;
+    // Picks up settings edited in the config file while the app is running. Only settings are
+    // applied; the library and playlists can't be swapped out from under the UI.
+    pub fn reload_settings(&mut self) {
+        match confy::load::<App>("music_player", None) {
+            Ok(saved) if saved.settings != self.settings => {
+                tracing::info!("Applying settings changed on disk");
+                self.apply_settings(saved.settings);
+            }
+            Ok(_) => {}
+            Err(err) => tracing::warn!("Couldn't reload the config file: {}", err),
+        }
     }
 
-    // Spawns a background thread and imports files
-    // from each unimported library path
-    fn import_library_paths(&self, lib_path: &LibraryPath) {
-        if lib_path.status() == LibraryPathStatus::Imported {
-            tracing::info!("already imported library path...");
+    /// Switches shuffle on, or off when it's on in either mode.
+    pub fn toggle_shuffle(&mut self) {
+        let shuffle = match self.settings.shuffle {
+            ShuffleMode::Off => ShuffleMode::Uniform,
+            ShuffleMode::Uniform | ShuffleMode::LeastPlayed => ShuffleMode::Off,
+        };
+        self.apply_settings(Settings {
+            shuffle,
+            ..self.settings.clone()
+        });
+    }
+
+    /// Steps from no repeat to repeating all, then the track, then back to off.
+    pub fn cycle_repeat(&mut self) {
+        self.apply_settings(Settings {
+            repeat: self.settings.repeat.cycled(),
+            ..self.settings.clone()
+        });
+    }
+
+    pub fn apply_settings(&mut self, settings: Settings) {
+        self.settings = settings;
+
+        if let Some(player) = self.player.as_mut() {
+            let result = push_audio_settings(player, &self.settings);
+            player.set_shuffle_mode(self.settings.shuffle);
+            player.set_repeat_mode(self.settings.repeat);
+            player.set_max_queue_len(self.settings.max_queue_len);
+            self.check_player(result);
+        }
+        self.history
+            .set_max_entries(self.settings.max_history_entries);
+
+        for observer in &mut self.observers {
+            observer.settings_changed(&self.settings);
+        }
+    }
+
+    pub fn register_observer(&mut self, mut observer: Box<dyn PlaybackObserver>) {
+        observer.settings_changed(&self.settings);
+        self.observers.push(observer);
+    }
+
+    /// Passes a playback event from the audio thread on to the observers, along with the track
+    /// it happened to.
+    pub fn notify_observers(&mut self, event: PlaybackEvent) {
+        let track = match &event {
+            // By the time a track's end arrives the player may have moved on to the next one.
+            PlaybackEvent::Started(path) | PlaybackEvent::Ended(path) => self.find_track(path),
+            PlaybackEvent::Paused | PlaybackEvent::Resumed | PlaybackEvent::Seeked(_) => self
+                .player
+                .as_ref()
+                .and_then(|player| player.selected_track.clone()),
+        };
+        let Some(track) = track else {
+            tracing::warn!("No track to report {:?} for", event);
+            return;
+        };
+
+        for observer in &mut self.observers {
+            match &event {
+                PlaybackEvent::Started(_) => observer.track_started(&track),
+                PlaybackEvent::Ended(_) => observer.track_ended(&track),
+                PlaybackEvent::Paused => observer.paused(&track),
+                PlaybackEvent::Resumed => observer.resumed(&track),
+                PlaybackEvent::Seeked(timestamp) => observer.seeked(&track, *timestamp),
+            }
+        }
+    }
+
+    /// Moves on after the playing track finished on its own or failed to play, continuing into
+    /// the linked playlist once the current one runs out.
+    pub fn advance_playback(&mut self, failed_track: Option<std::path::PathBuf>) {
+        let Some(current_playlist_idx) = self.current_playlist_idx else {
+            if let Some(path) = failed_track {
+                self.player.as_mut().unwrap().errored_tracks.insert(path);
+            }
+            return;
+        };
+
+        let player = self.player.as_mut().unwrap();
+        let playlist = &self.playlists[current_playlist_idx];
+        let queue_end_behavior = self.settings.queue_end_behavior;
+        let has_advanced = match failed_track {
+            Some(path) => player.track_failed(path, playlist, &self.history, queue_end_behavior),
+            None => player.track_finished(playlist, &self.history, queue_end_behavior),
+        };
+        let Some(has_advanced) = self.check_player(has_advanced) else {
+            return;
+        };
+
+        // The end of the last track in the chain leaves the player stopped rather than showing
+        // the finished track as still playing.
+        if !has_advanced && !self.continue_into_next_playlist() {
+            let result = self.player.as_mut().unwrap().stop();
+            self.check_player(result);
+        }
+    }
+
+    /// Passes on what a player command returned. A command the audio thread never got is
+    /// logged and shown in the footer, rather than taking the whole app down with it.
+    pub fn check_player<T>(&mut self, result: Result<T, PlayerError>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(err) => {
+                tracing::error!("Playback command failed: {}", err);
+                self.status_hint = Some(format!("Can't play, {err}. Restart to play again"));
+                None
+            }
+        }
+    }
+
+    // Whether a track in another playlist started.
+    fn continue_into_next_playlist(&mut self) -> bool {
+        let Some(mut idx) = self.current_playlist_idx else {
+            return false;
+        };
+
+        // Follow links past empty playlists. Without repeat-all, stop at anything already played
+        // through in this chain.
+        loop {
+            self.played_playlists.insert(self.playlists[idx].id());
+
+            let next_idx = match self.playlists[idx].next_playlist {
+                Some(next_id) => self.playlists.iter().position(|pl| pl.id() == next_id),
+                None if self.settings.repeat == RepeatMode::All => Some(idx),
+                None => None,
+            };
+
+            let Some(next_idx) = next_idx else {
+                return false;
+            };
+
+            let next_playlist = &self.playlists[next_idx];
+            if self.played_playlists.contains(&next_playlist.id()) {
+                if self.settings.repeat != RepeatMode::All {
+                    return false;
+                }
+
+                // Only start over once everything in the chain was tried, otherwise a chain of
+                // empty playlists would spin forever.
+                self.played_playlists.clear();
+                if next_playlist.tracks.is_empty() {
+                    return false;
+                }
+            }
+
+            if let Some(first_track) = next_playlist.tracks.first() {
+                tracing::info!("Continuing with playlist {:?}", next_playlist.get_name());
+                let player = self.player.as_mut().unwrap();
+                player
+                    .routed_device
+                    .clone_from(&next_playlist.output_device);
+                let result = player
+                    .select_track(Some(first_track.clone()))
+                    .and_then(|()| player.play());
+                self.current_playlist_idx = Some(next_idx);
+                return self.check_player(result).is_some();
+            }
+
+            idx = next_idx;
+        }
+    }
+
+    /// Remembers the volume used on the previous output device and switches to the one last
+    /// used on `device`. Devices played on for the first time never start out louder than
+    /// `DEFAULT_DEVICE_VOLUME`, in case they're sensitive headphones.
+    pub fn switch_output_device(&mut self, device: String) {
+        let player = self.player.as_mut().unwrap();
+        if player.output_device.as_ref() == Some(&device) {
+            return;
+        }
+
+        if let Some(previous_device) = player.output_device.take() {
+            self.device_volumes
+                .insert(previous_device, player.unmuted_volume());
+        }
+
+        let volume = match self.device_volumes.get(&device) {
+            Some(volume) => *volume,
+            None => player.unmuted_volume().min(DEFAULT_DEVICE_VOLUME),
+        };
+        tracing::info!("Switched output to {}, volume {}", device, volume);
+
+        let result = player.restore_volume(volume);
+        player.output_device = Some(device);
+        self.check_player(result);
+    }
+
+    /// Points the player at the current playlist's output device, for the tracks it selects
+    /// from now on.
+    pub fn route_output(&mut self) {
+        let output_device = self
+            .current_playlist_idx
+            .and_then(|idx| self.playlists.get(idx))
+            .and_then(|playlist| playlist.output_device.clone());
+        if let Some(player) = self.player.as_mut() {
+            player.routed_device = output_device;
+        }
+    }
+
+    /// Tells the audio thread which track follows the selected one, for gapless playback.
+    pub fn announce_next_track(&mut self) {
+        let Some(player) = self.player.as_mut() else {
+            return;
+        };
+        let next = self
+            .current_playlist_idx
+            .and_then(|idx| self.playlists.get(idx))
+            .and_then(|playlist| player.upcoming_track(playlist));
+        let result = player.announce_next(next);
+        self.check_player(result);
+    }
+
+    /// The audio thread went on with the announced next track without a gap.
+    pub fn continued_with(&mut self, path: std::path::PathBuf) {
+        let Some(current_playlist_idx) = self.current_playlist_idx else {
+            return;
+        };
+        let playlist = &self.playlists[current_playlist_idx];
+        self.player
+            .as_mut()
+            .unwrap()
+            .continued_with(&path, playlist);
+    }
+
+    /// Picks the saved session back up: the queue, and the track that was playing at the position
+    /// it was left at.
+    pub fn resume_session(&mut self) {
+        self.route_output();
+        let session = std::mem::take(&mut self.session);
+        if let Some(player) = self.player.as_mut() {
+            let result = session.restore(player, self.settings.resume_autoplay);
+            self.check_player(result);
+        }
+    }
+
+    /// Plays the selected track. With nothing selected, starts the queue or the current playlist
+    /// unless that's turned off in the settings.
+    pub fn play(&mut self) {
+        let player = self.player.as_mut().unwrap();
+        if player.selected_track.is_some() {
+            let result = player.play();
+            self.check_player(result);
+            return;
+        }
+
+        if !self.settings.play_starts_playlist {
+            return;
+        }
+
+        let playlist = self.current_playlist_idx.map(|idx| &self.playlists[idx]);
+        let result = player.start(playlist);
+        match self.check_player(result) {
+            Some(true) => self.status_hint = None,
+            Some(false) => {
+                self.status_hint =
+                    Some("Nothing to play, add some tracks to the playlist first".to_string())
+            }
+            None => {}
+        }
+    }
+
+    /// Adds an album to the current playlist in disc and track order, then starts it if the
+    /// action says so. Tracks already in the playlist aren't added twice.
+    pub fn add_album(&mut self, mut tracks: Vec<LibraryItem>, action: AlbumAction) {
+        let Some(current_playlist_idx) = self.current_playlist_idx else {
+            return;
+        };
+
+        // Untagged tracks go after the numbered ones, in the order they were in.
+        tracks.sort_by_key(|track| {
+            (
+                track.disc_number().unwrap_or(0),
+                track.track_number().unwrap_or(u32::MAX),
+            )
+        });
+
+        let playlist = &mut self.playlists[current_playlist_idx];
+        if action == AlbumAction::ReplaceAndPlay {
+            playlist.tracks.clear();
+            playlist.selected = None;
+        }
+        for track in &tracks {
+            if !playlist.tracks.contains(track) {
+                playlist.add(track.clone());
+            }
+        }
+
+        if action != AlbumAction::Add {
+            if let Some(first) = tracks.first() {
+                // Starting playback by hand begins a new chain of linked playlists.
+                self.played_playlists.clear();
+                let player = self.player.as_mut().unwrap();
+                let result = player
+                    .select_track(Some(first.clone()))
+                    .and_then(|()| player.play());
+                self.check_player(result);
+            }
+        }
+    }
+
+    /// Takes a track out of a playlist. When it's the one playing, playback goes on with the
+    /// track that followed it, or stops at the end of the playlist.
+    pub fn remove_from_playlist(&mut self, playlist_idx: usize, idx: usize) {
+        let playlist = &mut self.playlists[playlist_idx];
+        let Some(track) = (idx < playlist.tracks.len()).then(|| playlist.remove(idx)) else {
+            return;
+        };
+
+        let player = self.player.as_mut().unwrap();
+        player.track_removed(playlist.id(), idx);
+        if player.selected_track.as_ref() != Some(&track) {
+            return;
+        }
+
+        let result = match playlist.tracks.get(idx) {
+            Some(next_track) if matches!(player.track_state, TrackState::Playing) => player
+                .select_track(Some(next_track.clone()))
+                .and_then(|()| player.play()),
+            _ => player.stop().and_then(|()| player.select_track(None)),
+        };
+        self.check_player(result);
+    }
+
+    pub fn move_in_playlist(&mut self, playlist_idx: usize, from: usize, to: usize) {
+        let playlist = &mut self.playlists[playlist_idx];
+        if from == to || from >= playlist.tracks.len() || to >= playlist.tracks.len() {
+            return;
+        }
+
+        playlist.reorder(from, to);
+        self.player
+            .as_mut()
+            .unwrap()
+            .track_moved(playlist.id(), from, to);
+    }
+
+    /// Adds the playlist saved in an M3U file and shows it. Tracks already in the library are
+    /// taken from there, files that no longer exist are flagged like ones that failed to play.
+    pub fn import_playlist(&mut self, path: &std::path::Path) -> std::io::Result<()> {
+        let mut playlist = Playlist::import_m3u(path)?;
+        for track in &mut playlist.tracks {
+            if let Some(item) = self
+                .library
+                .items()
+                .iter()
+                .find(|item| item.path() == track.path())
+            {
+                *track = item.clone();
+            }
+
+            if !track.path().exists() {
+                tracing::warn!(
+                    "{:?} from the imported playlist doesn't exist",
+                    track.path()
+                );
+                if let Some(player) = self.player.as_mut() {
+                    player.errored_tracks.insert(track.path());
+                }
+            }
+        }
+
+        self.playlists.push(playlist);
+        self.current_playlist_idx = Some(self.playlists.len() - 1);
+        Ok(())
+    }
+
+    /// Adds an empty playlist named "New Playlist", numbered when that's taken, and switches to
+    /// it.
+    pub fn new_playlist(&mut self) {
+        let default_name_count = self
+            .playlists
+            .iter()
+            .filter(|pl| pl.get_name().unwrap().starts_with("New Playlist"))
+            .count();
+        let playlist_name = match default_name_count {
+            0 => "New Playlist".to_string(),
+            _ => format!("New Playlist ({})", default_name_count - 1),
+        };
+
+        let mut new_playlist = Playlist::new();
+        new_playlist.set_name(playlist_name);
+
+        self.playlists.push(new_playlist);
+        self.current_playlist_idx = Some(self.playlists.len() - 1);
+    }
+
+    /// Removes the playlist at `idx`. The current playlist stays current, or the one taking the
+    /// removed one's place becomes current if it was.
+    pub fn remove_playlist(&mut self, idx: usize) {
+        if idx >= self.playlists.len() {
             return;
         }
 
-        tracing::info!("adding library path...");
+        let removed = self.playlists.remove(idx);
+        for playlist in &mut self.playlists {
+            if playlist.next_playlist == Some(removed.id()) {
+                playlist.next_playlist = None;
+            }
+        }
+
+        self.current_playlist_idx = match self.current_playlist_idx {
+            Some(current) if current > idx => Some(current - 1),
+            Some(current) if current == idx => {
+                (!self.playlists.is_empty()).then(|| idx.min(self.playlists.len() - 1))
+            }
+            current => current,
+        };
+    }
+
+    /// Adds a folder to the library and imports it, unless it was imported before.
+    pub fn add_library_path(&mut self, path: std::path::PathBuf) {
+        self.library.add_path(path.clone());
+        if let Some(lib_path) = self.library.paths().iter().find(|lib_path| {
+            *lib_path.path() == path && lib_path.status() == LibraryPathStatus::NotImported
+        }) {
+            self.import_library_paths(lib_path);
+        }
+    }
+
+    /// Takes the paths selected in the library configuration out of the library, along with
+    /// their tracks.
+    pub fn remove_selected_library_paths(&mut self) {
+        for path_id in self.lib_config_selections.drain() {
+            self.library.remove_path(path_id);
+        }
+    }
+
+    /// Imports the paths that weren't yet and rescans the others, except those being imported
+    /// right now.
+    pub fn rescan_library(&self) {
+        for lib_path in self
+            .library
+            .paths()
+            .iter()
+            .filter(|lib_path| !self.import_progress.contains_key(&lib_path.id()))
+        {
+            self.import_library_paths(lib_path);
+        }
+    }
+
+    /// Opens what was passed on the command line: added as by `add_paths`, and the first file
+    /// is played.
+    pub fn open_paths(&mut self, paths: Vec<std::path::PathBuf>) {
+        if let Some(track) = self.add_paths(paths) {
+            self.played_playlists.clear();
+            let player = self.player.as_mut().unwrap();
+            let result = player
+                .select_track(Some(track))
+                .and_then(|()| player.play());
+            self.check_player(result);
+        }
+    }
+
+    /// Adds files and folders, e.g. dropped on the window. Folders are imported as library
+    /// paths. Audio files are added to the current playlist unless they're in it already, taken
+    /// from the library when they're in it. Returns the track of the first file.
+    pub fn add_paths(&mut self, paths: Vec<std::path::PathBuf>) -> Option<LibraryItem> {
+        let mut first_track = None;
+        for path in paths {
+            let path = std::fs::canonicalize(&path).unwrap_or(path);
+            if path.is_dir() {
+                self.add_library_path(path);
+            } else if path.is_file() {
+                if !tags::is_supported(&path) {
+                    tracing::warn!("Can't add {:?}, it isn't an audio file", path);
+                    self.status_hint = Some(format!("{} isn't an audio file", path.display()));
+                    continue;
+                }
+
+                let track = self
+                    .library
+                    .items()
+                    .iter()
+                    .find(|item| item.path() == path)
+                    .cloned()
+                    .or_else(|| {
+                        // Files outside the library aren't part of any library path.
+                        read_library_item(
+                            &path,
+                            LibraryPathId::NONE,
+                            &ImportOptions::new(&self.settings),
+                        )
+                    });
+                let Some(mut track) = track else {
+                    continue;
+                };
+
+                if self.current_playlist_idx.is_none() {
+                    self.new_playlist();
+                }
+                let playlist = &mut self.playlists[self.current_playlist_idx.unwrap()];
+                match playlist.tracks.iter().find(|added| added.path() == path) {
+                    Some(added) => track = added.clone(),
+                    None => playlist.add(track.clone()),
+                }
+                first_track.get_or_insert(track);
+            } else {
+                tracing::warn!("Can't open {:?}, it doesn't exist", path);
+                self.status_hint = Some(format!("{} doesn't exist", path.display()));
+            }
+        }
+
+        first_track
+    }
+
+    /// Picks up changes made to the playing track's file outside the app: the tags are read
+    /// again everywhere the track shows up, and the audio is reopened at the current position.
+    pub fn reload_track(&mut self) {
+        let player = self.player.as_mut().unwrap();
+        let Some(track) = player.selected_track.clone() else {
+            return;
+        };
+
+        if !track.path().exists() {
+            tracing::warn!("Can't reload {:?}, the file is gone", track.path());
+            let result = player.stop().and_then(|()| player.select_track(None));
+            player.errored_tracks.insert(track.path());
+            self.status_hint = Some(format!("{} no longer exists", track.path().display()));
+            self.check_player(result);
+            return;
+        }
+
+        let fresh = read_library_item(
+            &track.path(),
+            track.library_id(),
+            &ImportOptions::new(&self.settings),
+        );
+        self.update_track(&track.path(), |item| {
+            // Unreadable tags keep the ones known so far when such files are skipped on import.
+            if let Some(fresh) = &fresh {
+                item.refresh_tags(fresh);
+            }
+            // The audio may have been re-encoded, it's measured again on the next play.
+            item.set_dynamic_range(None);
+        });
+
+        let result = self.player.as_mut().unwrap().reload();
+        self.check_player(result);
+    }
+
+    /// The track at `path`, preferably the player's copy of it, then one in the queue, the
+    /// playlists or the library.
+    fn find_track(&self, path: &std::path::Path) -> Option<LibraryItem> {
+        let player = self.player.as_ref()?;
+        player
+            .selected_track
+            .iter()
+            .chain(&player.queue)
+            .chain(self.playlists.iter().flat_map(|playlist| &playlist.tracks))
+            .chain(self.library.items())
+            .find(|item| item.path() == path)
+            .cloned()
+    }
+
+    /// Runs `update` on every copy of the track at `path`: in the library, the playlists, the
+    /// queue and the player.
+    fn update_track(&mut self, path: &std::path::Path, mut update: impl FnMut(&mut LibraryItem)) {
+        let mut update = |item: &mut LibraryItem| {
+            if item.path() == path {
+                update(item);
+            }
+        };
+
+        self.library.update_items(&mut update);
+        for playlist in &mut self.playlists {
+            playlist.tracks.iter_mut().for_each(&mut update);
+        }
+        let player = self.player.as_mut().unwrap();
+        player.queue.iter_mut().for_each(&mut update);
+        if let Some(selected_track) = player.selected_track.as_mut() {
+            update(selected_track);
+        }
+    }
+
+    /// Keeps the dynamic range measurement in step with playback, and stores the result on the
+    /// track once it has played through from start to end.
+    pub fn measure_dynamic_range(&mut self, event: &PlaybackEvent) {
+        match event {
+            PlaybackEvent::Started(path) => self.dynamic_range.start(Some(path.clone())),
+            PlaybackEvent::Seeked(_) => self.dynamic_range.is_partial = true,
+            PlaybackEvent::Ended(path)
+                if self.dynamic_range.track.as_ref() == Some(path)
+                    && !self.dynamic_range.is_partial =>
+            {
+                if let Some(dynamic_range) = self.dynamic_range.value() {
+                    let dynamic_range = dynamic_range.round().max(0.0) as u32;
+                    tracing::info!("Measured DR{} for {:?}", dynamic_range, path);
+                    self.update_track(path, |track| {
+                        track.set_dynamic_range(Some(dynamic_range));
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Measures the loudness of tracks without ReplayGain tags while they play, and stores a
+    /// track gain worked out from it once one has played through from start to end.
+    pub fn measure_loudness(&mut self, event: &PlaybackEvent) {
+        match event {
+            PlaybackEvent::Started(path) => self.loudness_meter.start(Some(path.clone())),
+            PlaybackEvent::Seeked(_) => self.loudness_meter.is_partial = true,
+            PlaybackEvent::Ended(path)
+                if self.loudness_meter.track.as_ref() == Some(path)
+                    && !self.loudness_meter.is_partial =>
+            {
+                if let Some(lufs) = self.loudness_meter.integrated() {
+                    let gain_db = replay_gain::REFERENCE_LUFS - lufs;
+                    let peak = self.loudness_meter.peak();
+                    tracing::info!("Measured {:.1} LUFS for {:?}", lufs, path);
+                    // Tagged gains are left alone, they may be album aware or better measured.
+                    self.update_track(path, |track| {
+                        if track.track_gain_db().is_none() {
+                            track.set_track_gain(Some(gain_db), Some(peak));
+                        }
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn quit(&mut self) {
+        self.quit = true;
+    }
+
+    /// Imports the files of a library path on a background thread. Paths imported before are
+    /// rescanned: only files that are new or changed since are read, and files that are gone are
+    /// taken out of the library.
+    fn import_library_paths(&self, lib_path: &LibraryPath) {
+        tracing::info!("importing library path {:?}", lib_path.path());
 
         let lib_cmd_tx = self.library_cmd_tx.as_ref().unwrap().clone();
         let path = lib_path.path().clone();
         let path_id = lib_path.id();
+        let import_options = ImportOptions::new(&self.settings);
+        let known = self
+            .library
+            .items()
+            .iter()
+            .filter(|item| item.library_id() == path_id)
+            .map(|item| (item.path(), item.modified_at()))
+            .collect::<HashMap<_, _>>();
 
         std::thread::spawn(move || {
-            let files = walkdir::WalkDir::new(path)
+            let mut walk_dir =
+                walkdir::WalkDir::new(path).follow_links(import_options.follow_symlinks);
+            if let Some(max_depth) = import_options.max_depth {
+                walk_dir = walk_dir.max_depth(max_depth);
+            }
+
+            let files = walk_dir
                 .into_iter()
                 .filter_map(|e| e.ok())
                 .skip(1)
-                .filter(|entry| {
-                    entry.file_type().is_file()
-                        && entry.path().extension().unwrap_or(std::ffi::OsStr::new("")) == "mp3"
+                .filter(|entry| entry.file_type().is_file() && tags::is_supported(entry.path()))
+                .collect::<Vec<_>>();
+
+            let found = files
+                .iter()
+                .map(|entry| entry.path())
+                .collect::<std::collections::HashSet<_>>();
+            for gone in known
+                .keys()
+                .filter(|known_path| !found.contains(known_path.as_path()))
+            {
+                lib_cmd_tx
+                    .send(LibraryCommand::RemoveItem(gone.clone()))
+                    .expect("Failed to send removed library item");
+            }
+
+            // Items imported before modification times were kept are read once more.
+            let changed = files
+                .iter()
+                .filter(|entry| match known.get(entry.path()) {
+                    Some(Some(modified_at)) => file_modified_at(entry.path()) != Some(*modified_at),
+                    _ => true,
                 })
                 .collect::<Vec<_>>();
 
-            let items = files
+            let total = changed.len();
+            let done = std::sync::atomic::AtomicUsize::new(0);
+            let items = changed
                 .par_iter()
-                .map(|entry| {
-                    let tag = Tag::read_from_path(entry.path());
-
-                    let library_item = match tag {
-                        Ok(tag) => LibraryItem::new(entry.path().to_path_buf(), path_id)
-                            .set_title(tag.title().or(Some("Unknown Title")))
-                            .set_artist(tag.artist())
-                            .set_album(tag.album())
-                            .set_year(tag.year())
-                            .set_genre(tag.genre())
-                            .set_track_number(tag.track()),
-                        Err(_err) => {
-                            tracing::warn!("Couldn't parse to id3: {:?}", &entry.path());
-                            LibraryItem::new(entry.path().to_path_buf(), path_id)
-                        }
-                    };
-
-                    library_item
+                .filter_map(|entry| {
+                    let item = read_library_item(entry.path(), path_id, &import_options);
+                    let done = done.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+                    if done.is_multiple_of(100) || done == total {
+                        lib_cmd_tx
+                            .send(LibraryCommand::ImportProgress(path_id, done, total))
+                            .expect("Failed to send import progress");
+                    }
+                    item
                 })
                 .collect::<Vec<LibraryItem>>();
 
-            tracing::info!("Done parsing library items");
+            let skipped = total - items.len();
+            tracing::info!(
+                "Done parsing {} new or changed library items, {} skipped",
+                total,
+                skipped
+            );
+            lib_cmd_tx
+                .send(LibraryCommand::Skipped(path_id, skipped))
+                .expect("Failed to send skipped count");
 
             // Populate the library
-            for item in &items {
+            for item in items {
+                let lib_cmd = if known.contains_key(&item.path()) {
+                    LibraryCommand::UpdateItem(item)
+                } else {
+                    LibraryCommand::AddItem(item)
+                };
                 lib_cmd_tx
-                    .send(LibraryCommand::AddItem((*item).clone()))
+                    .send(lib_cmd)
                     .expect("failed to send library item")
             }
 
-            // Build the views
-            let mut library_view = LibraryView {
-                view_type: ViewType::Album,
-                containers: Vec::new(),
-            };
+            lib_cmd_tx
+                .send(LibraryCommand::AddPathId(path_id))
+                .expect("Failed to send imported library path");
+        });
+    }
 
-            // In order for group by to work from itertools, items must be consecutive, so sort them first.
-            let mut library_items_clone = items.clone();
-            library_items_clone.sort_by_key(|item| item.album());
+    /// Applies what the import threads found. The views are grouped again once a path is done.
+    pub fn handle_library_commands(&mut self) {
+        let Some(lib_cmd_rx) = &self.library_cmd_rx else {
+            return;
+        };
 
-            let grouped_library_by_album = &library_items_clone.into_iter().group_by(|item| {
-                item.album()
-                    .unwrap_or("unknown album".to_string())
-                    .to_string()
-            });
+        for lib_cmd in lib_cmd_rx.try_iter().collect::<Vec<_>>() {
+            match lib_cmd {
+                LibraryCommand::AddItem(lib_item) => self.library.add_item(lib_item),
+                LibraryCommand::UpdateItem(lib_item) => {
+                    self.update_track(&lib_item.path(), |item| item.refresh_tags(&lib_item))
+                }
+                LibraryCommand::RemoveItem(path) => self.library.remove_item(&path),
+                LibraryCommand::AddPathId(path_id) => {
+                    self.import_progress.remove(&path_id);
+                    self.library.set_path_to_imported(path_id);
+                    self.library.rebuild_views(self.settings.merge_spellings);
+                }
+                LibraryCommand::Skipped(path_id, skipped) => {
+                    self.library.set_path_skipped(path_id, skipped)
+                }
+                LibraryCommand::ImportProgress(path_id, done, total) => {
+                    self.import_progress.insert(path_id, (done, total));
+                }
+            }
+        }
+    }
+}
 
-            for (album_name, album_library_items) in grouped_library_by_album {
-                let lib_item_container = LibraryItemContainer {
-                    name: album_name.clone(),
-                    items: album_library_items.collect::<Vec<LibraryItem>>(),
-                };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::Itertools;
+    use std::path::PathBuf;
+    use std::sync::atomic::AtomicU32;
+    use std::sync::mpsc::channel;
 
-                library_view.containers.push(lib_item_container.clone());
+    // State as saved before versioning, settings and history were added.
+    const UNVERSIONED_STATE: &str = r#"{
+        "library": {
+            "paths": [{ "id": 7, "path": "/music", "status": "Imported" }],
+            "items": [{ "library_id": 7, "path": "/music/song.mp3", "title": "Song" }],
+            "library_view": { "view_type": "Album", "containers": [] }
+        },
+        "playlists": [
+            {
+                "name": "Road trip",
+                "tracks": [{ "library_id": 7, "path": "/music/song.mp3", "title": "Song", "key": 1 }],
+                "selected": null
             }
+        ],
+        "current_playlist_idx": 3
+    }"#;
 
-            lib_cmd_tx
-                .send(LibraryCommand::AddView(library_view))
-                .expect("Failed to send library view");
+    #[test]
+    fn loads_and_migrates_unversioned_state() {
+        let mut app: App = serde_json::from_str(UNVERSIONED_STATE).unwrap();
+        assert_eq!(app.version, 0);
 
-            lib_cmd_tx
-                .send(LibraryCommand::AddPathId(path_id))
-                .expect("Failed to send library view");
-            //lib_path.set_imported();
+        app.migrate();
+
+        assert_eq!(app.version, STATE_VERSION);
+        assert_eq!(app.library.items().len(), 1);
+        assert_eq!(app.playlists.len(), 1);
+        assert_eq!(app.playlists[0].get_name(), Some("Road trip".to_string()));
+        assert_eq!(app.playlists[0].tracks[0].title(), Some("Song".to_string()));
+        assert_eq!(app.playlists[0].tracks[0].key(), 1);
+        assert_eq!(app.current_playlist_idx, None);
+        assert_eq!(app.settings, Settings::default());
+        for view_type in [
+            ViewType::Album,
+            ViewType::Artist,
+            ViewType::Genre,
+            ViewType::Year,
+        ] {
+            assert_eq!(app.library.view(view_type).unwrap().containers.len(), 1);
+        }
+    }
+
+    fn playlist(name: &str) -> Playlist {
+        let mut playlist = Playlist::new();
+        playlist.set_name(name.to_string());
+        playlist.add(LibraryItem::new(
+            PathBuf::from(format!("{name}.mp3")),
+            LibraryPathId::new(0),
+        ));
+        playlist
+    }
+
+    // Two playlists linked to each other, playing the first one's only track.
+    fn linked_app() -> (App, Receiver<AudioCommand>) {
+        let (audio_tx, audio_rx) = channel();
+        let (_ui_tx, ui_rx) = channel();
+        let mut app = App {
+            player: Some(Player::new(audio_tx, ui_rx, Arc::new(AtomicU32::new(0)))),
+            ..Default::default()
+        };
+
+        let mut first = playlist("first");
+        let mut second = playlist("second");
+        first.next_playlist = Some(second.id());
+        second.next_playlist = Some(first.id());
+        app.playlists = vec![first, second];
+        app.current_playlist_idx = Some(0);
+
+        let track = app.playlists[0].tracks[0].clone();
+        app.player
+            .as_mut()
+            .unwrap()
+            .select_track(Some(track))
+            .unwrap();
+
+        (app, audio_rx)
+    }
+
+    // Paths of the tracks it was told about, in order.
+    struct RecordingObserver(std::rc::Rc<std::cell::RefCell<Vec<PathBuf>>>);
+
+    impl PlaybackObserver for RecordingObserver {
+        fn track_started(&mut self, track: &LibraryItem) {
+            self.0.borrow_mut().push(track.path());
+        }
+
+        fn track_ended(&mut self, track: &LibraryItem) {
+            self.0.borrow_mut().push(track.path());
+        }
+
+        fn paused(&mut self, track: &LibraryItem) {
+            self.0.borrow_mut().push(track.path());
+        }
+    }
+
+    #[test]
+    fn observers_hear_about_the_track_the_event_is_for() {
+        let (mut app, _audio_rx) = linked_app();
+        let reported = std::rc::Rc::default();
+        app.register_observer(Box::new(RecordingObserver(std::rc::Rc::clone(&reported))));
+        let first = app.playlists[0].tracks[0].path();
+        let second = app.playlists[1].tracks[0].clone();
+        app.player
+            .as_mut()
+            .unwrap()
+            .select_track(Some(second.clone()))
+            .unwrap();
+
+        app.notify_observers(PlaybackEvent::Ended(first.clone()));
+        app.notify_observers(PlaybackEvent::Started(second.path()));
+        app.notify_observers(PlaybackEvent::Paused);
+
+        assert_eq!(
+            *reported.borrow(),
+            vec![first, second.path(), second.path()]
+        );
+    }
+
+    #[test]
+    fn removing_playlists_keeps_a_sensible_one_current() {
+        let mut app = App {
+            playlists: vec![playlist("first"), playlist("second"), playlist("third")],
+            ..Default::default()
+        };
+        app.playlists[2].next_playlist = Some(app.playlists[0].id());
+
+        // One before the current playlist shifts it down.
+        app.current_playlist_idx = Some(2);
+        app.remove_playlist(0);
+        assert_eq!(app.current_playlist_idx, Some(1));
+        assert_eq!(app.playlists[1].next_playlist, None);
+
+        // The current one is replaced by its neighbour.
+        app.current_playlist_idx = Some(0);
+        app.remove_playlist(0);
+        assert_eq!(app.current_playlist_idx, Some(0));
+        assert_eq!(app.playlists[0].get_name().unwrap(), "third");
+
+        app.remove_playlist(0);
+        assert_eq!(app.current_playlist_idx, None);
+
+        app.new_playlist();
+        app.new_playlist();
+        assert_eq!(app.current_playlist_idx, Some(1));
+        assert_eq!(app.playlists[1].get_name().unwrap(), "New Playlist (0)");
+    }
+
+    #[test]
+    fn linked_playlists_stop_instead_of_looping() {
+        let (mut app, _audio_rx) = linked_app();
+
+        app.advance_playback(None);
+        assert_eq!(app.current_playlist_idx, Some(1));
+
+        app.advance_playback(None);
+        assert_eq!(app.current_playlist_idx, Some(1));
+        assert_eq!(
+            app.player.as_ref().unwrap().selected_track,
+            Some(app.playlists[1].tracks[0].clone())
+        );
+        assert!(app.player.as_ref().unwrap().is_stopped());
+    }
+
+    #[test]
+    fn linked_playlists_loop_with_repeat_all() {
+        let (mut app, _audio_rx) = linked_app();
+        app.settings.repeat = RepeatMode::All;
+
+        app.advance_playback(None);
+        app.advance_playback(None);
+
+        assert_eq!(app.current_playlist_idx, Some(0));
+    }
+
+    #[test]
+    fn repeat_one_loads_the_finished_track_again() {
+        let (mut app, audio_rx) = linked_app();
+        app.cycle_repeat();
+        app.cycle_repeat();
+        assert_eq!(app.settings.repeat, RepeatMode::One);
+        audio_rx.try_iter().for_each(drop);
+
+        app.advance_playback(None);
+
+        assert_eq!(app.current_playlist_idx, Some(0));
+        assert_eq!(
+            app.player.as_ref().unwrap().selected_track,
+            Some(app.playlists[0].tracks[0].clone())
+        );
+        assert!(audio_rx
+            .try_iter()
+            .any(|cmd| matches!(cmd, AudioCommand::LoadFile(path) if path == app.playlists[0].tracks[0].path())));
+    }
+
+    #[test]
+    fn repeat_all_is_carried_over_from_version_1() {
+        let mut app: App =
+            serde_json::from_str(r#"{ "version": 1, "settings": { "repeat_all": true } }"#)
+                .unwrap();
+
+        app.migrate();
+
+        assert_eq!(app.settings.repeat, RepeatMode::All);
+        assert!(!app.settings.repeat_all);
+    }
+
+    #[test]
+    fn removing_the_playing_track_goes_on_with_the_next_one() {
+        let (mut app, _audio_rx) = linked_app();
+        let second = LibraryItem::new(PathBuf::from("second.mp3"), LibraryPathId::new(0));
+        app.playlists[0].add(second.clone());
+        app.player.as_mut().unwrap().play().unwrap();
+
+        app.remove_from_playlist(0, 0);
+
+        assert_eq!(app.playlists[0].tracks, vec![second.clone()]);
+        assert_eq!(app.player.as_ref().unwrap().selected_track, Some(second));
+
+        app.remove_from_playlist(0, 0);
+
+        assert!(app.playlists[0].tracks.is_empty());
+        assert_eq!(app.player.as_ref().unwrap().selected_track, None);
+        assert!(app.player.as_ref().unwrap().is_stopped());
+    }
+
+    // Imports the first library path and applies the results once the import thread is done.
+    fn import_first_path(app: &mut App) {
+        let lib_path = app.library.paths()[0].clone();
+        app.import_progress.insert(lib_path.id(), (0, 0));
+        app.import_library_paths(&lib_path);
+
+        let started = std::time::Instant::now();
+        while app.import_progress.contains_key(&lib_path.id()) {
+            assert!(started.elapsed() < std::time::Duration::from_secs(10));
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            app.handle_library_commands();
+        }
+    }
+
+    #[test]
+    fn rescanning_only_reads_new_and_changed_files() {
+        let dir = std::env::temp_dir().join(format!("rescan_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("kept.mp3"), b"").unwrap();
+        std::fs::write(dir.join("deleted.mp3"), b"").unwrap();
+
+        let (lib_cmd_tx, lib_cmd_rx) = channel();
+        let (audio_tx, _audio_rx) = channel();
+        let (_ui_tx, ui_rx) = channel();
+        let mut app = App {
+            player: Some(Player::new(audio_tx, ui_rx, Arc::new(AtomicU32::new(0)))),
+            library_cmd_tx: Some(lib_cmd_tx),
+            library_cmd_rx: Some(lib_cmd_rx),
+            ..Default::default()
+        };
+        app.library.add_path(dir.clone());
+        import_first_path(&mut app);
+        assert_eq!(app.library.items().len(), 2);
+
+        // Unchanged files aren't read again, so this stays.
+        app.update_track(&dir.join("kept.mp3"), |item| {
+            item.set_title(Some("Renamed"));
         });
+        std::fs::remove_file(dir.join("deleted.mp3")).unwrap();
+        std::fs::write(dir.join("added.mp3"), b"").unwrap();
+        import_first_path(&mut app);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let titles = app
+            .library
+            .items()
+            .iter()
+            .filter_map(|item| item.title())
+            .sorted()
+            .collect_vec();
+        assert_eq!(titles, ["Renamed", "added"]);
+        assert_eq!(
+            app.library.view(ViewType::Album).unwrap().containers[0]
+                .items
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn removed_library_paths_take_their_tracks_along() {
+        let dir = std::env::temp_dir().join(format!("remove_path_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("song.mp3"), b"").unwrap();
+
+        let (lib_cmd_tx, lib_cmd_rx) = channel();
+        let mut app = App {
+            library_cmd_tx: Some(lib_cmd_tx),
+            library_cmd_rx: Some(lib_cmd_rx),
+            ..Default::default()
+        };
+        app.library.add_path(dir.clone());
+        import_first_path(&mut app);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(app.library.items().len(), 1);
+
+        app.lib_config_selections
+            .insert(app.library.paths()[0].id());
+        app.remove_selected_library_paths();
+        assert!(app.library.paths().is_empty());
+        assert!(app.library.items().is_empty());
+        assert!(app
+            .library
+            .view(ViewType::Album)
+            .is_none_or(|view| view.containers.is_empty()));
+        assert!(app.lib_config_selections.is_empty());
+    }
+
+    #[test]
+    fn added_files_go_into_the_playlist_once() {
+        let dir = std::env::temp_dir().join(format!("add_paths_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("song.mp3"), b"").unwrap();
+        std::fs::write(dir.join("notes.txt"), b"").unwrap();
+
+        let (audio_tx, audio_rx) = channel();
+        let (_ui_tx, ui_rx) = channel();
+        let mut app = App {
+            player: Some(Player::new(audio_tx, ui_rx, Arc::new(AtomicU32::new(0)))),
+            ..Default::default()
+        };
+
+        let files = vec![dir.join("song.mp3"), dir.join("notes.txt")];
+        assert!(app.add_paths(files.clone()).is_some());
+        app.add_paths(files);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(app.playlists[0].tracks.len(), 1);
+        // Only opening plays.
+        assert!(audio_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn opened_folders_are_imported_and_opened_files_played() {
+        let dir = std::env::temp_dir().join(format!("open_paths_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dir = std::fs::canonicalize(dir).unwrap();
+        std::fs::write(dir.join("song.mp3"), b"").unwrap();
+
+        let (lib_cmd_tx, lib_cmd_rx) = channel();
+        let (audio_tx, audio_rx) = channel();
+        let (_ui_tx, ui_rx) = channel();
+        let mut app = App {
+            player: Some(Player::new(audio_tx, ui_rx, Arc::new(AtomicU32::new(0)))),
+            library_cmd_tx: Some(lib_cmd_tx),
+            library_cmd_rx: Some(lib_cmd_rx),
+            ..Default::default()
+        };
+
+        app.open_paths(vec![dir.clone()]);
+        assert_eq!(*app.library.paths()[0].path(), dir);
+        import_first_path(&mut app);
+
+        // The library's copy is played, not a second one.
+        app.open_paths(vec![dir.join("song.mp3")]);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let library_track = app.library.items()[0].clone();
+        assert_eq!(
+            app.player.as_ref().unwrap().selected_track.as_ref(),
+            Some(&library_track)
+        );
+        assert_eq!(app.playlists[0].tracks, [library_track]);
+        assert!(audio_rx.try_iter().any(
+            |cmd| matches!(cmd, AudioCommand::LoadFile(path) if path == dir.join("song.mp3"))
+        ));
+    }
+
+    #[test]
+    fn volume_follows_the_output_device() {
+        let (mut app, _audio_rx) = linked_app();
+        app.player.as_mut().unwrap().volume = 0.9;
+
+        app.switch_output_device("Speakers".to_string());
+        assert_eq!(app.player.as_ref().unwrap().volume, DEFAULT_DEVICE_VOLUME);
+        app.player.as_mut().unwrap().volume = 0.8;
+
+        app.switch_output_device("Headphones".to_string());
+        assert_eq!(app.player.as_ref().unwrap().volume, DEFAULT_DEVICE_VOLUME);
+        app.player.as_mut().unwrap().volume = 0.2;
+
+        app.switch_output_device("Speakers".to_string());
+        assert_eq!(app.player.as_ref().unwrap().volume, 0.8);
+        assert_eq!(app.device_volumes["Headphones"], 0.2);
+    }
+
+    #[test]
+    fn files_with_unreadable_tags_are_named_or_skipped() {
+        let path =
+            std::env::temp_dir().join(format!("music-player-{}-Untagged.mp3", std::process::id()));
+        std::fs::write(&path, b"not an mp3").unwrap();
+
+        let read = |unreadable_tags| {
+            let settings = Settings {
+                unreadable_tags,
+                ..Settings::default()
+            };
+            read_library_item(&path, LibraryPathId::new(0), &ImportOptions::new(&settings))
+        };
+        let named = read(UnreadableTags::UseFileName);
+        let skipped = read(UnreadableTags::Skip);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            named.unwrap().title(),
+            Some(format!("music-player-{}-Untagged", std::process::id()))
+        );
+        assert!(skipped.is_none());
+    }
+
+    #[test]
+    fn albums_are_added_in_disc_and_track_order() {
+        let (mut app, _audio_rx) = linked_app();
+        let track = |name: &str, disc: u32, number: Option<u32>| {
+            LibraryItem::new(PathBuf::from(name), LibraryPathId::new(0))
+                .set_disc_number(Some(disc))
+                .set_track_number(number)
+        };
+        let album = vec![
+            track("2-1.mp3", 2, Some(1)),
+            track("untagged.mp3", 1, None),
+            track("1-2.mp3", 1, Some(2)),
+            track("1-1.mp3", 1, Some(1)),
+        ];
+
+        app.add_album(album, AlbumAction::ReplaceAndPlay);
+
+        let paths = app.playlists[0]
+            .tracks
+            .iter()
+            .map(|track| track.path())
+            .collect_vec();
+        assert_eq!(
+            paths,
+            ["1-1.mp3", "1-2.mp3", "untagged.mp3", "2-1.mp3"].map(PathBuf::from)
+        );
+        assert_eq!(
+            app.player.as_ref().unwrap().selected_track,
+            Some(app.playlists[0].tracks[0].clone())
+        );
     }
 }
@@ -0,0 +1,107 @@
+use crate::app::library::LibraryItem;
+use crate::app::settings::Settings;
+use std::path::PathBuf;
+
+/// Playback events reported by the audio thread as they happen.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlaybackEvent {
+    Started(PathBuf),
+    /// The track played through to its end.
+    Ended(PathBuf),
+    Paused,
+    Resumed,
+    Seeked(u64),
+}
+
+/// Extension point for features that follow along with playback, like scrobbling,
+/// notifications or stats. Observers are registered with `App::register_observer` and called on
+/// the UI thread, so they should hand anything slow off to a thread of their own.
+pub trait PlaybackObserver {
+    /// Called once on registration and after every settings change.
+    fn settings_changed(&mut self, _settings: &Settings) {}
+    fn track_started(&mut self, _track: &LibraryItem) {}
+    fn track_ended(&mut self, _track: &LibraryItem) {}
+    fn paused(&mut self, _track: &LibraryItem) {}
+    fn resumed(&mut self, _track: &LibraryItem) {}
+    fn seeked(&mut self, _track: &LibraryItem, _timestamp: u64) {}
+}
+
+/// Keeps a text file with the playing track's artist and title up to date, e.g. for a stream
+/// overlay to read. The file is emptied while nothing is playing.
+#[derive(Default)]
+pub struct NowPlayingFile {
+    path: Option<PathBuf>,
+}
+
+impl NowPlayingFile {
+    fn write(&self, contents: &str) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        if let Err(err) = std::fs::write(path, contents) {
+            tracing::warn!("Couldn't write now playing file {:?}: {}", path, err);
+        }
+    }
+
+    fn write_track(&self, track: &LibraryItem) {
+        self.write(&format!(
+            "{} - {}",
+            track.artist().unwrap_or("unknown artist".to_string()),
+            track.title().unwrap_or("unknown title".to_string())
+        ));
+    }
+}
+
+impl PlaybackObserver for NowPlayingFile {
+    fn settings_changed(&mut self, settings: &Settings) {
+        self.path.clone_from(&settings.now_playing_file);
+    }
+
+    fn track_started(&mut self, track: &LibraryItem) {
+        self.write_track(track);
+    }
+
+    fn track_ended(&mut self, _track: &LibraryItem) {
+        self.write("");
+    }
+
+    fn paused(&mut self, _track: &LibraryItem) {
+        self.write("");
+    }
+
+    fn resumed(&mut self, track: &LibraryItem) {
+        self.write_track(track);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::library::LibraryPathId;
+
+    #[test]
+    fn now_playing_file_follows_playback() {
+        let path = std::env::temp_dir().join(format!(
+            "music-player-{}-now-playing.txt",
+            std::process::id()
+        ));
+        let track = LibraryItem::new(PathBuf::from("song.mp3"), LibraryPathId::new(0))
+            .set_artist(Some("Artist"))
+            .set_title(Some("Song"));
+
+        let mut observer = NowPlayingFile::default();
+        observer.settings_changed(&Settings {
+            now_playing_file: Some(path.clone()),
+            ..Default::default()
+        });
+
+        observer.track_started(&track);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "Artist - Song");
+
+        observer.paused(&track);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
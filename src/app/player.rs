@@ -1,9 +1,40 @@
+use crate::app::history::History;
 use crate::app::library::LibraryItem;
 use crate::app::playlist::Playlist;
-use crate::{AudioCommand, UiCommand};
+use crate::app::settings::{
+    AudioThreadPriority, NetworkRetry, NormalizationMode, PlayThreshold, QueueEndBehavior,
+    RepeatMode, SeekPrecision, ShuffleMode,
+};
+use crate::app::shuffle::{self, ShuffleOrder};
+use crate::dsp::{FadeCurve, Limiter, NightMode, PanLaw};
+use crate::{
+    AudioCommand, Chapter, PlaybackStatus, ReaderOptions, StreamInfo, TrackFormat, UiCommand,
+};
+use std::collections::{HashSet, VecDeque};
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
-use std::sync::mpsc::{Receiver, Sender};
+use std::sync::mpsc::{Receiver, SendError, Sender};
 use std::sync::Arc;
+use symphonia::core::units::Time;
+
+/// A playback command that didn't reach the audio thread. It has exited, e.g. after a fatal
+/// error, and nothing more can be played until the app is restarted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayerError;
+
+impl std::fmt::Display for PlayerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the audio thread has stopped")
+    }
+}
+
+impl std::error::Error for PlayerError {}
+
+impl<T> From<SendError<T>> for PlayerError {
+    fn from(_: SendError<T>) -> Self {
+        PlayerError
+    }
+}
 
 pub struct Player {
     pub track_state: TrackState,
@@ -11,9 +42,49 @@ pub struct Player {
     pub audio_tx: Sender<AudioCommand>,
     pub ui_rx: Receiver<UiCommand>,
     pub volume: f32,
+    // The volume from before muting, while muted.
+    muted_volume: Option<f32>,
+    pub balance: f32,
     pub seek_to_timestamp: u64,
+    // A seek was sent and the audio thread hasn't confirmed it yet. Positions it sends meanwhile
+    // are from before the seek.
+    is_seek_pending: bool,
     pub duration: u64,
     pub cursor: Arc<AtomicU32>, // This can "overflow"
+    pub chapters: Vec<Chapter>,
+    pub stream_info: Option<StreamInfo>,
+    pub track_format: Option<TrackFormat>,
+    // The playing track changed sample rate or channels partway through.
+    pub stream_changed: bool,
+    pub is_loading: bool,
+    pub playback_status: PlaybackStatus,
+    pub is_limiting: bool,
+    // Retry attempt while a track on a slow or unavailable path is being opened.
+    pub connecting_attempt: Option<u32>,
+    pub max_open_retries: u32,
+    // Backend the audio thread last opened an output on.
+    pub output_host: Option<String>,
+    // Device the audio thread last opened an output on.
+    pub output_device: Option<String>,
+    // Device the next selected track should play on, `None` for the default.
+    pub routed_device: Option<String>,
+    // Tracks the audio thread couldn't play, e.g. empty or truncated files.
+    pub errored_tracks: HashSet<PathBuf>,
+    pub queue: VecDeque<LibraryItem>,
+    // Queued tracks kept before the oldest are dropped.
+    pub max_queue_len: usize,
+    pub shuffle_mode: ShuffleMode,
+    pub repeat_mode: RepeatMode,
+    normalization: NormalizationMode,
+    // The playlist track that was playing when the queue took over, used to resume the playlist
+    // when queued tracks aren't part of it.
+    queue_resume_track: Option<LibraryItem>,
+    is_playing_from_queue: bool,
+    listen_progress: ListenProgress,
+    // What the audio thread was last told comes next.
+    announced_next: Option<PathBuf>,
+    // Order `ShuffleMode::Uniform` walks the current playlist in.
+    shuffle_order: Option<ShuffleOrder>,
 }
 
 impl Player {
@@ -28,119 +99,652 @@ impl Player {
             audio_tx: audio_cmd_tx,
             ui_rx: ui_cmd_rx,
             volume: 1.0,
+            muted_volume: None,
+            balance: 0.0,
             seek_to_timestamp: 0, // TODO: This should have subsecond precision, but is okay for now.
+            is_seek_pending: false,
             duration: 0,
             cursor,
+            chapters: Vec::new(),
+            stream_info: None,
+            track_format: None,
+            stream_changed: false,
+            is_loading: false,
+            playback_status: PlaybackStatus::default(),
+            is_limiting: false,
+            connecting_attempt: None,
+            max_open_retries: 0,
+            output_host: None,
+            output_device: None,
+            routed_device: None,
+            errored_tracks: HashSet::new(),
+            queue: VecDeque::new(),
+            max_queue_len: usize::MAX,
+            shuffle_mode: ShuffleMode::Off,
+            repeat_mode: RepeatMode::Off,
+            normalization: NormalizationMode::Off,
+            queue_resume_track: None,
+            is_playing_from_queue: false,
+            listen_progress: ListenProgress::default(),
+            announced_next: None,
+            shuffle_order: None,
         }
     }
 
-    pub fn select_track(&mut self, track: Option<LibraryItem>) {
-        self.selected_track = track;
+    pub fn select_track(&mut self, track: Option<LibraryItem>) -> Result<(), PlayerError> {
+        self.set_selected_track(track);
+        // Loading a file makes the audio thread forget the next track.
+        self.announced_next = None;
 
         if let Some(track) = &self.selected_track {
+            // Give previously failed tracks another chance, they're marked again if it still fails.
+            self.errored_tracks.remove(&track.path());
+            self.audio_tx.send(AudioCommand::SetTrackGain(
+                track.normalization_gain(self.normalization),
+            ))?;
             self.audio_tx
-                .send(AudioCommand::LoadFile(track.path()))
-                .expect("Failed to send select to audio thread");
+                .send(AudioCommand::SetOutputDevice(self.routed_device.clone()))?;
+            self.audio_tx.send(AudioCommand::LoadFile(track.path()))?;
+        }
+
+        Ok(())
+    }
+
+    fn set_selected_track(&mut self, track: Option<LibraryItem>) {
+        self.selected_track = track;
+        self.is_seek_pending = false;
+        self.listen_progress = ListenProgress::default();
+        self.chapters.clear();
+        self.stream_info = None;
+        self.track_format = None;
+        self.stream_changed = false;
+    }
+
+    /// The track that follows the selected one, for the audio thread to go straight on with.
+    /// Only the playlist order is known ahead of time, the queue and shuffle pick once the track
+    /// ends. A repeated track is loaded again instead.
+    pub fn upcoming_track(&self, playlist: &Playlist) -> Option<LibraryItem> {
+        if self.shuffle_mode != ShuffleMode::Off
+            || self.repeat_mode == RepeatMode::One
+            || self.is_playing_from_queue
+            || !self.queue.is_empty()
+        {
+            return None;
+        }
+
+        self.following_track(playlist).cloned()
+    }
+
+    pub fn announce_next(&mut self, track: Option<LibraryItem>) -> Result<(), PlayerError> {
+        let path = track.as_ref().map(LibraryItem::path);
+        if self.announced_next != path {
+            self.announced_next.clone_from(&path);
+            let gain = track.map_or(1.0, |track| track.normalization_gain(self.normalization));
+            self.audio_tx
+                .send(AudioCommand::EnqueueNext { path, gain })?;
+        }
+
+        Ok(())
+    }
+
+    /// The audio thread went on with the announced track by itself, so it's selected without
+    /// being loaded again.
+    pub fn continued_with(&mut self, path: &std::path::Path, playlist: &Playlist) {
+        self.announced_next = None;
+        match self.following_track(playlist) {
+            Some(track) if track.path() == path => self.set_selected_track(Some(track.clone())),
+            _ => tracing::warn!("Continued with {:?}, which isn't next any more", path),
+        }
+    }
+
+    // A new order is shuffled for another playlist, or when tracks were taken out of this one
+    // without `track_removed`. Turning shuffle off and on again also starts over from the current
+    // track.
+    fn shuffle_order(&mut self, playlist: &Playlist, current: Option<usize>) -> &ShuffleOrder {
+        let len = playlist.tracks.len();
+        match &mut self.shuffle_order {
+            Some(order) if order.is_for(playlist.id(), len) => order.extend(len, current),
+            _ => {
+                self.shuffle_order = Some(ShuffleOrder::new(
+                    playlist.id(),
+                    len,
+                    current,
+                    rand::random(),
+                ))
+            }
+        }
+
+        self.shuffle_order.as_ref().unwrap()
+    }
+
+    /// Keeps the shuffled order of `playlist_id` when one of its tracks was moved.
+    pub fn track_moved(&mut self, playlist_id: u64, from: usize, to: usize) {
+        if let Some(order) = self
+            .shuffle_order
+            .as_mut()
+            .filter(|order| order.playlist_id() == playlist_id)
+        {
+            order.moved(from, to);
         }
     }
 
+    pub fn track_removed(&mut self, playlist_id: u64, idx: usize) {
+        if let Some(order) = self
+            .shuffle_order
+            .as_mut()
+            .filter(|order| order.playlist_id() == playlist_id)
+        {
+            order.removed(idx);
+        }
+    }
+
+    fn following_track<'a>(&self, playlist: &'a Playlist) -> Option<&'a LibraryItem> {
+        let position = playlist.get_pos(self.selected_track.as_ref()?)?;
+        playlist.tracks.get(position + 1)
+    }
+
     pub fn is_stopped(&self) -> bool {
         matches!(self.track_state, TrackState::Stopped)
     }
 
-    pub fn seek_to(&mut self, seek_to_timestamp: u64) {
+    pub fn seek_to(&mut self, seek_to_timestamp: u64) -> Result<(), PlayerError> {
         self.seek_to_timestamp = seek_to_timestamp;
-        self.audio_tx
-            .send(AudioCommand::Seek(seek_to_timestamp))
-            .expect("Failed to send seek to audio thread");
+        self.is_seek_pending = true;
+        // Jumping around the track isn't listening to it.
+        self.listen_progress.last_update = None;
+        self.audio_tx.send(AudioCommand::Seek(seek_to_timestamp))?;
+        Ok(())
+    }
+
+    /// Reopens the selected track at the current position, e.g. after the file was re-encoded.
+    pub fn reload(&mut self) -> Result<(), PlayerError> {
+        self.audio_tx.send(AudioCommand::Reload)?;
+        Ok(())
     }
 
-    // TODO: Should return Result
-    pub fn stop(&mut self) {
+    pub fn stop(&mut self) -> Result<(), PlayerError> {
         match &self.track_state {
             TrackState::Playing | TrackState::Paused => {
                 self.track_state = TrackState::Stopped;
-                self.audio_tx
-                    .send(AudioCommand::Stop)
-                    .expect("Failed to send stop to audio thread");
+                self.is_seek_pending = false;
+                self.audio_tx.send(AudioCommand::Stop)?;
             }
             _ => (),
         }
+
+        Ok(())
     }
 
-    // TODO: Should return Result
-    pub fn play(&mut self) {
-        if let Some(_selected_track) = &self.selected_track {
-            match self.track_state {
-                TrackState::Unstarted | TrackState::Stopped | TrackState::Playing => {
-                    self.track_state = TrackState::Playing;
+    pub fn play(&mut self) -> Result<(), PlayerError> {
+        if self.selected_track.is_some() {
+            self.track_state = TrackState::Playing;
+            self.audio_tx.send(AudioCommand::Play)?;
+        }
 
-                    self.audio_tx
-                        .send(AudioCommand::Play)
-                        .expect("Failed to send play to audio thread");
-                }
-                TrackState::Paused => {
-                    self.track_state = TrackState::Playing;
-                    self.audio_tx
-                        .send(AudioCommand::Play)
-                        .expect("Failed to send play to audio thread");
-                }
+        Ok(())
+    }
+
+    /// Starts playback when nothing is selected, from the next queued track or else the first
+    /// track of the playlist. Returns `false` when there's nothing to play.
+    pub fn start(&mut self, playlist: Option<&Playlist>) -> Result<bool, PlayerError> {
+        let track = match self.queue.pop_front() {
+            Some(queued_track) => {
+                self.is_playing_from_queue = true;
+                self.queue_resume_track = None;
+                queued_track
             }
-        }
+            None => match playlist.and_then(|playlist| playlist.tracks.first()) {
+                Some(first_track) => first_track.clone(),
+                None => return Ok(false),
+            },
+        };
+
+        self.select_track(Some(track))?;
+        self.play()?;
+        Ok(true)
     }
 
-    // TODO: Should return result
-    pub fn pause(&mut self) {
+    pub fn pause(&mut self) -> Result<(), PlayerError> {
         match self.track_state {
             TrackState::Playing => {
                 self.track_state = TrackState::Paused;
-                self.audio_tx
-                    .send(AudioCommand::Pause)
-                    .expect("Failed to send pause to audio thread");
+                self.audio_tx.send(AudioCommand::Pause)?;
             }
             TrackState::Paused => {
                 self.track_state = TrackState::Playing;
-                self.audio_tx
-                    .send(AudioCommand::Play)
-                    .expect("Failed to send play to audio thread");
+                self.audio_tx.send(AudioCommand::Play)?;
             }
             _ => (),
         }
+
+        Ok(())
     }
 
-    pub fn previous(&mut self, playlist: &Playlist) {
+    pub fn previous(&mut self, playlist: &Playlist) -> Result<(), PlayerError> {
+        if self.shuffle_mode == ShuffleMode::Uniform {
+            let current = self
+                .selected_track
+                .as_ref()
+                .and_then(|track| playlist.get_pos(track));
+            let previous_idx = current
+                .and_then(|current| self.shuffle_order(playlist, Some(current)).before(current));
+            if let Some(previous_idx) = previous_idx {
+                self.select_track(Some(playlist.tracks[previous_idx].clone()))?;
+                self.play()?;
+            }
+            return Ok(());
+        }
+
         if let Some(selected_track) = &self.selected_track {
             if let Some(current_track_position) = playlist.get_pos(selected_track) {
                 if current_track_position > 0 {
                     let previous_track = &playlist.tracks[current_track_position - 1];
-                    self.select_track(Some((*previous_track).clone()));
-                    self.play();
+                    self.select_track(Some((*previous_track).clone()))?;
+                    self.play()?;
                 }
             }
         }
+
+        Ok(())
     }
 
-    pub fn next(&mut self, playlist: &Playlist) {
-        if let Some(selected_track) = &self.selected_track {
-            if let Some(current_track_position) = playlist.get_pos(selected_track) {
-                if current_track_position < playlist.tracks.len() - 1 {
-                    let next_track = &playlist.tracks[current_track_position + 1];
-                    self.select_track(Some((*next_track).clone()));
-                    self.play();
+    /// Moves to the next track of the playlist, or a random one when shuffling. Returns `false`
+    /// when there isn't one.
+    pub fn next(&mut self, playlist: &Playlist, history: &History) -> Result<bool, PlayerError> {
+        if self.shuffle_mode != ShuffleMode::Off {
+            let current = self
+                .selected_track
+                .as_ref()
+                .and_then(|track| playlist.get_pos(track));
+            let next_idx = match self.shuffle_mode {
+                ShuffleMode::Uniform => self.shuffle_order(playlist, current).after(current),
+                _ => shuffle::pick_track(
+                    &playlist.tracks,
+                    self.selected_track.as_ref(),
+                    history,
+                    &mut rand::thread_rng(),
+                ),
+            };
+
+            return match next_idx {
+                Some(next_idx) => {
+                    self.select_track(Some(playlist.tracks[next_idx].clone()))?;
+                    self.play()?;
+                    Ok(true)
                 }
+                None => Ok(false),
+            };
+        }
+
+        match self.following_track(playlist) {
+            Some(next_track) => {
+                self.select_track(Some(next_track.clone()))?;
+                self.play()?;
+                Ok(true)
             }
+            None => Ok(false),
         }
     }
 
+    /// Seeks relative to the current position, clamped to the track. Does nothing until the
+    /// track's time base is known.
+    pub fn seek_by(&mut self, secs: f64) -> Result<(), PlayerError> {
+        let Some(stream_info) = self.stream_info else {
+            return Ok(());
+        };
+
+        let delta = stream_info.time_base.calc_timestamp(Time::from(secs.abs()));
+        let timestamp = if secs >= 0.0 {
+            self.seek_to_timestamp
+                .saturating_add(delta)
+                .min(self.duration)
+        } else {
+            self.seek_to_timestamp.saturating_sub(delta)
+        };
+
+        self.seek_to(timestamp)
+    }
+
+    /// Formats a track timestamp as `m:ss`, or the raw timestamp when the time base isn't known
+    /// yet.
+    pub fn format_timestamp(&self, timestamp: u64) -> String {
+        match self.stream_info {
+            Some(stream_info) => {
+                let secs = stream_info.time_base.calc_time(timestamp).seconds;
+                format!("{}:{:02}", secs / 60, secs % 60)
+            }
+            None => timestamp.to_string(),
+        }
+    }
+
+    fn current_chapter_idx(&self) -> Option<usize> {
+        self.chapters
+            .iter()
+            .rposition(|chapter| chapter.start_ts <= self.seek_to_timestamp)
+    }
+
+    pub fn current_chapter(&self) -> Option<&Chapter> {
+        self.current_chapter_idx().map(|idx| &self.chapters[idx])
+    }
+
+    /// Jumps to the start of the next chapter, or to the next track when there isn't one.
+    pub fn next_chapter(
+        &mut self,
+        playlist: &Playlist,
+        history: &History,
+    ) -> Result<(), PlayerError> {
+        let next_idx = self.current_chapter_idx().map_or(0, |idx| idx + 1);
+
+        match self.chapters.get(next_idx) {
+            Some(chapter) => {
+                let start_ts = chapter.start_ts;
+                self.seek_to(start_ts)
+            }
+            None => self.next(playlist, history).map(|_| ()),
+        }
+    }
+
+    /// Jumps to the start of the previous chapter, or to the previous track when already in the
+    /// first one.
+    pub fn previous_chapter(&mut self, playlist: &Playlist) -> Result<(), PlayerError> {
+        match self.current_chapter_idx() {
+            Some(idx) if idx > 0 => {
+                let start_ts = self.chapters[idx - 1].start_ts;
+                self.seek_to(start_ts)
+            }
+            _ => self.previous(playlist),
+        }
+    }
+
+    /// Adds a track to the "play next" queue. Queued tracks take priority over the playlist
+    /// when the current track finishes.
+    pub fn enqueue(&mut self, track: LibraryItem) {
+        self.queue.push_back(track);
+        self.trim_queue();
+    }
+
+    pub fn set_shuffle_mode(&mut self, shuffle_mode: ShuffleMode) {
+        if self.shuffle_mode != shuffle_mode {
+            self.shuffle_mode = shuffle_mode;
+            self.shuffle_order = None;
+        }
+    }
+
+    /// Takes effect on the playing track right away. The next track is announced again with its
+    /// new gain.
+    pub fn set_normalization(
+        &mut self,
+        normalization: NormalizationMode,
+    ) -> Result<(), PlayerError> {
+        if self.normalization == normalization {
+            return Ok(());
+        }
+        self.normalization = normalization;
+        self.announced_next = None;
+
+        if let Some(track) = &self.selected_track {
+            self.audio_tx.send(AudioCommand::SetTrackGain(
+                track.normalization_gain(normalization),
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn set_repeat_mode(&mut self, repeat_mode: RepeatMode) {
+        self.repeat_mode = repeat_mode;
+    }
+
+    pub fn set_max_queue_len(&mut self, max_queue_len: usize) {
+        self.max_queue_len = max_queue_len;
+        self.trim_queue();
+    }
+
+    /// Drops the oldest queued tracks beyond `max_queue_len`.
+    pub fn trim_queue(&mut self) {
+        if let Some(excess) = self.queue.len().checked_sub(self.max_queue_len) {
+            self.queue.drain(..excess);
+        }
+    }
+
+    /// Advances playback after the current track has finished on its own, draining the queue
+    /// before falling back to the playlist, or plays it again when repeating one track. Returns
+    /// `false` when the end of the playlist was reached.
+    pub fn track_finished(
+        &mut self,
+        playlist: &Playlist,
+        history: &History,
+        queue_end_behavior: QueueEndBehavior,
+    ) -> Result<bool, PlayerError> {
+        if self.repeat_mode == RepeatMode::One && self.selected_track.is_some() {
+            self.select_track(self.selected_track.clone())?;
+            self.play()?;
+            return Ok(true);
+        }
+
+        if let Some(queued_track) = self.queue.pop_front() {
+            if !self.is_playing_from_queue {
+                self.queue_resume_track = self.selected_track.clone();
+                self.is_playing_from_queue = true;
+            }
+
+            self.select_track(Some(queued_track))?;
+            self.play()?;
+            return Ok(true);
+        }
+
+        if !self.is_playing_from_queue {
+            return self.next(playlist, history);
+        }
+
+        // The last queued track just finished.
+        self.is_playing_from_queue = false;
+        let resume_track = self.queue_resume_track.take();
+
+        match queue_end_behavior {
+            QueueEndBehavior::Stop => {
+                self.stop()?;
+                Ok(true)
+            }
+            QueueEndBehavior::ContinuePlaylist => {
+                let last_queued_in_playlist = self
+                    .selected_track
+                    .as_ref()
+                    .is_some_and(|track| playlist.get_pos(track).is_some());
+
+                if !last_queued_in_playlist {
+                    self.selected_track = resume_track;
+                }
+
+                self.next(playlist, history)
+            }
+        }
+    }
+
+    /// Marks a track the audio thread couldn't play and skips to whatever would play next. Returns
+    /// `false` when the end of the playlist was reached.
+    pub fn track_failed(
+        &mut self,
+        path: PathBuf,
+        playlist: &Playlist,
+        history: &History,
+        queue_end_behavior: QueueEndBehavior,
+    ) -> Result<bool, PlayerError> {
+        self.errored_tracks.insert(path);
+
+        // The audio thread is already idle.
+        self.track_state = TrackState::Stopped;
+        self.track_finished(playlist, history, queue_end_behavior)
+    }
+
     // TODO - Need to only send message when volume has changed
-    pub fn set_volume(&mut self, volume: f32, is_processing_ui_change: &Arc<AtomicBool>) {
+    pub fn set_volume(
+        &mut self,
+        volume: f32,
+        is_processing_ui_change: &Arc<AtomicBool>,
+    ) -> Result<(), PlayerError> {
         if !is_processing_ui_change.load(Ordering::Acquire) {
             is_processing_ui_change.store(true, Ordering::Release);
+            // Turning the volume up or down by hand is unmuting.
+            self.muted_volume = None;
             self.volume = volume;
-            self.audio_tx
-                .send(AudioCommand::SetVolume(volume))
-                .expect("Failed to send play to audio thread");
+            self.audio_tx.send(AudioCommand::SetVolume(volume))?;
         }
+
+        Ok(())
+    }
+
+    /// Sets the volume without going through the UI change throttle, for volumes the app picks
+    /// itself rather than the user dragging the slider.
+    pub fn restore_volume(&mut self, volume: f32) -> Result<(), PlayerError> {
+        self.muted_volume = None;
+        self.volume = volume;
+        self.audio_tx.send(AudioCommand::SetVolume(volume))?;
+        Ok(())
+    }
+
+    /// Silences the output, or brings back the volume it had before it was muted.
+    pub fn toggle_mute(&mut self) -> Result<(), PlayerError> {
+        let volume = match self.muted_volume.take() {
+            Some(volume) => volume,
+            None => {
+                self.muted_volume = Some(self.volume);
+                0.0
+            }
+        };
+
+        self.volume = volume;
+        self.audio_tx.send(AudioCommand::SetVolume(volume))?;
+        Ok(())
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted_volume.is_some()
+    }
+
+    /// The volume the output plays at once it's unmuted.
+    pub fn unmuted_volume(&self) -> f32 {
+        self.muted_volume.unwrap_or(self.volume)
+    }
+
+    pub fn set_balance(&mut self, balance: f32) -> Result<(), PlayerError> {
+        self.balance = balance;
+        self.audio_tx.send(AudioCommand::SetBalance(balance))?;
+        Ok(())
+    }
+
+    pub fn set_pan_law(&mut self, pan_law: PanLaw) -> Result<(), PlayerError> {
+        self.audio_tx.send(AudioCommand::SetPanLaw(pan_law))?;
+        Ok(())
+    }
+
+    pub fn set_reader_options(&mut self, reader_options: ReaderOptions) -> Result<(), PlayerError> {
+        self.audio_tx
+            .send(AudioCommand::SetReaderOptions(reader_options))?;
+        Ok(())
+    }
+
+    pub fn set_track_gap(&mut self, gap_ms: u64) -> Result<(), PlayerError> {
+        self.audio_tx.send(AudioCommand::SetTrackGap(gap_ms))?;
+        Ok(())
+    }
+
+    pub fn set_crossfade(&mut self, secs: f32) -> Result<(), PlayerError> {
+        self.audio_tx.send(AudioCommand::SetCrossfade(secs))?;
+        Ok(())
+    }
+
+    pub fn set_crossfade_curve(&mut self, curve: FadeCurve) -> Result<(), PlayerError> {
+        self.audio_tx.send(AudioCommand::SetCrossfadeCurve(curve))?;
+        Ok(())
+    }
+
+    pub fn set_seek_precision(&mut self, seek_precision: SeekPrecision) -> Result<(), PlayerError> {
+        self.audio_tx
+            .send(AudioCommand::SetSeekPrecision(seek_precision))?;
+        Ok(())
+    }
+
+    pub fn set_night_mode(&mut self, night_mode: NightMode) -> Result<(), PlayerError> {
+        self.audio_tx.send(AudioCommand::SetNightMode(night_mode))?;
+        Ok(())
+    }
+
+    pub fn set_eq_band(&mut self, band: usize, gain_db: f32) -> Result<(), PlayerError> {
+        self.audio_tx
+            .send(AudioCommand::SetEqBand { band, gain_db })?;
+        Ok(())
+    }
+
+    pub fn set_fade_curve(&mut self, fade_curve: FadeCurve) -> Result<(), PlayerError> {
+        self.audio_tx.send(AudioCommand::SetFadeCurve(fade_curve))?;
+        Ok(())
+    }
+
+    pub fn set_limiter(&mut self, limiter: Limiter) -> Result<(), PlayerError> {
+        self.audio_tx.send(AudioCommand::SetLimiter(limiter))?;
+        Ok(())
+    }
+
+    pub fn set_thread_priority(
+        &mut self,
+        priority: AudioThreadPriority,
+    ) -> Result<(), PlayerError> {
+        self.audio_tx
+            .send(AudioCommand::SetThreadPriority(priority))?;
+        Ok(())
+    }
+
+    pub fn set_network_retry(&mut self, network_retry: NetworkRetry) -> Result<(), PlayerError> {
+        self.max_open_retries = network_retry.retries;
+        self.audio_tx
+            .send(AudioCommand::SetNetworkRetry(network_retry))?;
+        Ok(())
+    }
+
+    /// Turns the playing track down, e.g. while a preview plays over it.
+    pub fn set_ducked(&mut self, is_ducked: bool) -> Result<(), PlayerError> {
+        self.audio_tx.send(AudioCommand::SetDucked(is_ducked))?;
+        Ok(())
+    }
+
+    pub fn set_scope_window(&mut self, window_ms: u64) -> Result<(), PlayerError> {
+        self.audio_tx
+            .send(AudioCommand::SetScopeWindow(window_ms))?;
+        Ok(())
+    }
+
+    pub fn set_output_host(&mut self, output_host: Option<String>) -> Result<(), PlayerError> {
+        self.audio_tx
+            .send(AudioCommand::SetOutputHost(output_host))?;
+        Ok(())
+    }
+
+    /// The effective gain applied to the playing track, in dB: its ReplayGain in the current
+    /// normalization mode, held back by its peak, on top of the volume. Returns `None` when the
+    /// output is fully muted.
+    pub fn gain_db(&self) -> Option<f32> {
+        let track_gain = self
+            .selected_track
+            .as_ref()
+            .map_or(1.0, |track| track.normalization_gain(self.normalization));
+        let gain = self.volume * track_gain;
+        (gain > 0.0).then(|| 20.0 * gain.log10())
+    }
+
+    /// Takes a position reported by the audio thread, unless it's stale because a seek is under
+    /// way. Returns whether it was taken.
+    pub fn update_position(&mut self, timestamp: u64) -> bool {
+        if self.is_seek_pending {
+            return false;
+        }
+        self.seek_to_timestamp = timestamp;
+        true
+    }
+
+    /// The audio thread reached the position seeked to.
+    pub fn seek_finished(&mut self) {
+        self.is_seek_pending = false;
     }
 
     pub fn set_seek_to_timestamp(&mut self, seek_to_timestamp: u64) {
@@ -150,6 +754,43 @@ impl Player {
     pub fn set_duration(&mut self, duration: u64) {
         self.duration = duration;
     }
+
+    /// Feeds a playhead update from the audio thread into the listen tracking. Returns `true`
+    /// exactly once per track, when it crosses the play threshold.
+    pub fn update_listen_progress(&mut self, timestamp: u64, threshold: &PlayThreshold) -> bool {
+        let now = std::time::Instant::now();
+        let progress = &mut self.listen_progress;
+
+        if let Some((last_timestamp, last_instant)) = progress.last_update {
+            if timestamp >= last_timestamp && matches!(self.track_state, TrackState::Playing) {
+                progress.listened_ts += timestamp - last_timestamp;
+                progress.listened += now - last_instant;
+            }
+        }
+        progress.last_update = Some((timestamp, now));
+
+        if progress.is_counted || self.duration == 0 {
+            return false;
+        }
+
+        let fraction_played = progress.listened_ts as f32 / self.duration as f32;
+        if threshold.is_met(fraction_played, progress.listened.as_secs_f32()) {
+            progress.is_counted = true;
+            return true;
+        }
+
+        false
+    }
+}
+
+/// How much of the selected track has actually been heard, measured from the playhead
+/// updates sent by the audio thread.
+#[derive(Default)]
+struct ListenProgress {
+    listened_ts: u64,
+    listened: std::time::Duration,
+    last_update: Option<(u64, std::time::Instant)>,
+    is_counted: bool,
 }
 
 pub enum TrackState {
@@ -169,3 +810,291 @@ impl std::fmt::Display for TrackState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::library::LibraryPathId;
+    use std::path::PathBuf;
+    use std::sync::mpsc::channel;
+
+    // The audio receiver is returned so sends to the "audio thread" keep succeeding.
+    fn player() -> (Player, Receiver<AudioCommand>) {
+        let (audio_tx, audio_rx) = channel();
+        let (_ui_tx, ui_rx) = channel();
+        let player = Player::new(audio_tx, ui_rx, Arc::new(AtomicU32::new(0)));
+        (player, audio_rx)
+    }
+
+    fn track(name: &str) -> LibraryItem {
+        LibraryItem::new(PathBuf::from(name), LibraryPathId::new(0))
+    }
+
+    fn playlist(tracks: &[LibraryItem]) -> Playlist {
+        let mut playlist = Playlist::new();
+        for track in tracks {
+            playlist.add(track.clone());
+        }
+        playlist
+    }
+
+    #[test]
+    fn gapless_continuation_selects_the_next_track_without_loading_it() {
+        let playlist = playlist(&[track("1.mp3"), track("2.mp3")]);
+
+        let (mut player, audio_rx) = player();
+        player
+            .select_track(Some(playlist.tracks[0].clone()))
+            .unwrap();
+        assert_eq!(
+            player.upcoming_track(&playlist).map(|track| track.path()),
+            Some(PathBuf::from("2.mp3"))
+        );
+
+        // Whatever is queued is only picked once the track ends.
+        player.enqueue(track("queued.mp3"));
+        assert_eq!(player.upcoming_track(&playlist), None);
+        player.queue.clear();
+
+        while audio_rx.try_recv().is_ok() {}
+        player.continued_with(std::path::Path::new("2.mp3"), &playlist);
+        assert_eq!(player.selected_track, Some(playlist.tracks[1].clone()));
+        assert!(audio_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn positions_from_before_a_seek_are_ignored() {
+        let (mut player, _audio_rx) = player();
+        player.update_position(100);
+
+        player.seek_to(5000).unwrap();
+        assert!(!player.update_position(120));
+        assert_eq!(player.seek_to_timestamp, 5000);
+
+        player.seek_finished();
+        assert!(player.update_position(5010));
+        assert_eq!(player.seek_to_timestamp, 5010);
+    }
+
+    #[test]
+    fn previous_retraces_the_shuffled_order() {
+        let playlist = playlist(&[track("1.mp3"), track("2.mp3"), track("3.mp3")]);
+        let history = History::default();
+
+        let (mut player, _audio_rx) = player();
+        player.set_shuffle_mode(ShuffleMode::Uniform);
+        player
+            .select_track(Some(playlist.tracks[0].clone()))
+            .unwrap();
+
+        assert!(player.next(&playlist, &history).unwrap());
+        let second = player.selected_track.clone();
+        assert!(player.next(&playlist, &history).unwrap());
+        // Every track was played once.
+        assert!(!player.next(&playlist, &history).unwrap());
+
+        player.previous(&playlist).unwrap();
+        assert_eq!(player.selected_track, second);
+        player.previous(&playlist).unwrap();
+        assert_eq!(player.selected_track, Some(playlist.tracks[0].clone()));
+    }
+
+    #[test]
+    fn queue_plays_before_playlist() {
+        let tracks = [track("1.mp3"), track("2.mp3"), track("3.mp3")];
+        let playlist = playlist(&tracks);
+        let queued = track("queued.mp3");
+
+        let (mut player, _audio_rx) = player();
+        player.select_track(Some(tracks[0].clone())).unwrap();
+        player.enqueue(queued.clone());
+
+        player
+            .track_finished(
+                &playlist,
+                &History::default(),
+                QueueEndBehavior::ContinuePlaylist,
+            )
+            .unwrap();
+
+        assert_eq!(player.selected_track, Some(queued));
+        assert!(player.queue.is_empty());
+    }
+
+    #[test]
+    fn queue_continues_playlist_from_last_queued_track() {
+        let tracks = [track("1.mp3"), track("2.mp3"), track("3.mp3")];
+        let playlist = playlist(&tracks);
+
+        let (mut player, _audio_rx) = player();
+        player.select_track(Some(tracks[0].clone())).unwrap();
+        player.enqueue(tracks[1].clone());
+
+        player
+            .track_finished(
+                &playlist,
+                &History::default(),
+                QueueEndBehavior::ContinuePlaylist,
+            )
+            .unwrap();
+        assert_eq!(player.selected_track, Some(tracks[1].clone()));
+
+        player
+            .track_finished(
+                &playlist,
+                &History::default(),
+                QueueEndBehavior::ContinuePlaylist,
+            )
+            .unwrap();
+        assert_eq!(player.selected_track, Some(tracks[2].clone()));
+    }
+
+    #[test]
+    fn queue_resumes_playlist_when_queued_track_is_external() {
+        let tracks = [track("1.mp3"), track("2.mp3"), track("3.mp3")];
+        let playlist = playlist(&tracks);
+
+        let (mut player, _audio_rx) = player();
+        player.select_track(Some(tracks[0].clone())).unwrap();
+        player.enqueue(track("queued.mp3"));
+
+        player
+            .track_finished(
+                &playlist,
+                &History::default(),
+                QueueEndBehavior::ContinuePlaylist,
+            )
+            .unwrap();
+        player
+            .track_finished(
+                &playlist,
+                &History::default(),
+                QueueEndBehavior::ContinuePlaylist,
+            )
+            .unwrap();
+
+        assert_eq!(player.selected_track, Some(tracks[1].clone()));
+    }
+
+    #[test]
+    fn play_is_counted_once_after_threshold() {
+        let threshold = PlayThreshold {
+            percent: 50.0,
+            seconds: u64::MAX,
+        };
+
+        let (mut player, _audio_rx) = player();
+        player.select_track(Some(track("1.mp3"))).unwrap();
+        player.play().unwrap();
+        player.set_duration(100);
+
+        assert!(!player.update_listen_progress(0, &threshold));
+        assert!(!player.update_listen_progress(30, &threshold));
+        assert!(player.update_listen_progress(60, &threshold));
+        assert!(!player.update_listen_progress(90, &threshold));
+    }
+
+    #[test]
+    fn seeking_does_not_count_towards_play() {
+        let threshold = PlayThreshold {
+            percent: 50.0,
+            seconds: u64::MAX,
+        };
+
+        let (mut player, _audio_rx) = player();
+        player.select_track(Some(track("1.mp3"))).unwrap();
+        player.play().unwrap();
+        player.set_duration(100);
+
+        player.update_listen_progress(0, &threshold);
+        player.seek_to(80).unwrap();
+
+        assert!(!player.update_listen_progress(80, &threshold));
+        assert!(!player.update_listen_progress(90, &threshold));
+    }
+
+    #[test]
+    fn queue_end_can_stop_playback() {
+        let tracks = [track("1.mp3"), track("2.mp3")];
+        let playlist = playlist(&tracks);
+        let queued = track("queued.mp3");
+
+        let (mut player, _audio_rx) = player();
+        player.select_track(Some(tracks[0].clone())).unwrap();
+        player.enqueue(queued.clone());
+
+        player
+            .track_finished(&playlist, &History::default(), QueueEndBehavior::Stop)
+            .unwrap();
+        player
+            .track_finished(&playlist, &History::default(), QueueEndBehavior::Stop)
+            .unwrap();
+
+        assert_eq!(player.selected_track, Some(queued));
+        assert!(player.is_stopped());
+    }
+
+    #[test]
+    fn start_prefers_queue_over_playlist() {
+        let tracks = [track("1.mp3"), track("2.mp3")];
+        let playlist = playlist(&tracks);
+        let queued = track("queued.mp3");
+        let (mut player, _audio_rx) = player();
+
+        assert!(!player.start(Some(&Playlist::new())).unwrap());
+        assert_eq!(player.selected_track, None);
+
+        player.enqueue(queued.clone());
+        assert!(player.start(Some(&playlist)).unwrap());
+        assert_eq!(player.selected_track, Some(queued));
+
+        player.select_track(None).unwrap();
+        assert!(player.start(Some(&playlist)).unwrap());
+        assert_eq!(player.selected_track, Some(tracks[0].clone()));
+    }
+
+    #[test]
+    fn commands_fail_once_the_audio_thread_is_gone() {
+        let (mut player, audio_rx) = player();
+        player.select_track(Some(track("1.mp3"))).unwrap();
+        drop(audio_rx);
+
+        assert_eq!(player.play(), Err(PlayerError));
+        assert_eq!(player.seek_to(10), Err(PlayerError));
+        assert_eq!(player.select_track(Some(track("2.mp3"))), Err(PlayerError));
+        assert_eq!(player.announce_next(Some(track("3.mp3"))), Err(PlayerError));
+        assert_eq!(player.set_balance(0.5), Err(PlayerError));
+    }
+
+    #[test]
+    fn gain_includes_the_replay_gain_of_the_selected_track() {
+        let (mut player, _audio_rx) = player();
+        player.restore_volume(0.5).unwrap();
+        player
+            .select_track(Some(track("1.mp3").set_track_gain(Some(-6.0), Some(0.5))))
+            .unwrap();
+        let volume_db = 20.0 * 0.5f32.log10();
+        assert_eq!(player.gain_db(), Some(volume_db));
+
+        player.set_normalization(NormalizationMode::Track).unwrap();
+        let gain_db = player.gain_db().unwrap();
+        assert!((gain_db - (volume_db - 6.0)).abs() < 0.01, "{gain_db}");
+
+        player.toggle_mute().unwrap();
+        assert_eq!(player.gain_db(), None);
+    }
+
+    #[test]
+    fn unmuting_brings_back_the_volume_from_before() {
+        let (mut player, _audio_rx) = player();
+        player.restore_volume(0.4).unwrap();
+
+        player.toggle_mute().unwrap();
+        assert_eq!(player.volume, 0.0);
+        assert_eq!(player.unmuted_volume(), 0.4);
+
+        player.toggle_mute().unwrap();
+        assert_eq!(player.volume, 0.4);
+        assert!(!player.is_muted());
+    }
+}
@@ -1,13 +1,33 @@
+use crate::app::library::LibraryPathId;
+use crate::app::tags;
 use crate::app::LibraryItem;
 use crate::AudioCommand;
 use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::Sender;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Playlist {
+    // Stable identity for links between playlists, which can be reordered or removed.
+    id: u64,
     name: Option<String>,
     pub tracks: Vec<LibraryItem>,
     pub selected: Option<LibraryItem>,
+    /// Playlist to continue with once this one has played through.
+    pub next_playlist: Option<u64>,
+    /// Where the playlist table was scrolled to when last shown.
+    pub scroll_offset: [f32; 2],
+    /// Pinned playlists are shown before the others in the tabs.
+    pub pinned: bool,
+    /// Device this playlist's tracks play on instead of the default one.
+    pub output_device: Option<String>,
+}
+
+fn random_id() -> u64 {
+    use rand::Rng;
+    rand::thread_rng().gen()
 }
 
 impl Default for Playlist {
@@ -19,9 +39,14 @@ impl Default for Playlist {
 impl Playlist {
     pub fn new() -> Self {
         Self {
+            id: random_id(),
             name: None,
             tracks: vec![],
             selected: None,
+            next_playlist: None,
+            scroll_offset: [0.0, 0.0],
+            pinned: false,
+            output_device: None,
         }
     }
 
@@ -33,13 +58,22 @@ impl Playlist {
         self.name.clone()
     }
 
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
     pub fn add(&mut self, track: LibraryItem) {
         self.tracks.push(track);
     }
 
-    // TODO - should probably return a Result
-    pub fn remove(&mut self, idx: usize) {
-        self.tracks.remove(idx);
+    /// Takes the track at `idx` out, and out of the selection if it was selected.
+    pub fn remove(&mut self, idx: usize) -> LibraryItem {
+        let track = self.tracks.remove(idx);
+        if self.selected.as_ref() == Some(&track) {
+            self.selected = None;
+        }
+
+        track
     }
 
     // TODO - should probably return a Result
@@ -63,6 +97,88 @@ impl Playlist {
     pub fn get_pos(&self, track: &LibraryItem) -> Option<usize> {
         self.tracks.iter().position(|t| t == track)
     }
+
+    /// Writes the tracks as an extended M3U playlist. The length isn't kept in the library, so
+    /// it's written as unknown.
+    pub fn export_m3u(&self, path: &Path) -> io::Result<()> {
+        let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(file, "#EXTM3U")?;
+        for track in &self.tracks {
+            let title = track.title().unwrap_or_else(|| file_stem(&track.path()));
+            match track.artist() {
+                Some(artist) => writeln!(file, "#EXTINF:-1,{artist} - {title}")?,
+                None => writeln!(file, "#EXTINF:-1,{title}")?,
+            }
+            writeln!(file, "{}", track.path().display())?;
+        }
+
+        file.flush()
+    }
+
+    /// Reads an M3U playlist, named after the file. Tags are read from the tracks again, files
+    /// that are gone are kept with what the `#EXTINF` line says about them.
+    pub fn import_m3u(path: &Path) -> io::Result<Playlist> {
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        let mut playlist = Playlist::new();
+        playlist.set_name(file_stem(path));
+
+        let mut ext_inf = None;
+        for line in io::BufReader::new(std::fs::File::open(path)?).lines() {
+            let line = line?;
+            let line = line.trim();
+            if let Some(info) = line.strip_prefix("#EXTINF:") {
+                ext_inf = info.split_once(',').map(|(_, display)| display.to_string());
+                continue;
+            }
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            // Relative entries are relative to the playlist file.
+            let track_path = base_dir.join(line);
+            playlist.add(read_m3u_track(track_path, ext_inf.take().as_deref()));
+        }
+
+        Ok(playlist)
+    }
+}
+
+// Tracks from a playlist file aren't part of any library path.
+fn read_m3u_track(path: PathBuf, ext_inf: Option<&str>) -> LibraryItem {
+    let mut track = LibraryItem::new(path.clone(), LibraryPathId::NONE);
+    if let Some(display) = ext_inf {
+        track = match display.split_once(" - ") {
+            Some((artist, title)) => track.set_artist(Some(artist)).set_title(Some(title)),
+            None => track.set_title(Some(display)),
+        };
+    }
+
+    match tags::read(&path) {
+        Some(tags) => track
+            .set_title(tags.title.as_deref())
+            .set_artist(tags.artist.as_deref())
+            .set_album(tags.album.as_deref())
+            .set_year(tags.year)
+            .set_genre(tags.genre.as_deref())
+            .set_track_number(tags.track_number)
+            .set_disc_number(tags.disc_number)
+            .set_compilation(tags.is_compilation),
+        None => track,
+    }
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Indices of `playlists` in the order their tabs are shown: pinned ones first, otherwise in
+/// their usual order. The indices themselves stay valid for `App::current_playlist_idx`.
+pub fn tab_order(playlists: &[Playlist]) -> Vec<usize> {
+    let mut order = (0..playlists.len()).collect::<Vec<_>>();
+    order.sort_by_key(|idx| !playlists[*idx].pinned);
+    order
 }
 
 #[cfg(test)]
@@ -70,7 +186,6 @@ mod tests {
     use crate::app::library::LibraryPathId;
 
     use super::*;
-    use std::path::PathBuf;
 
     #[test]
     fn create_playlist() {
@@ -81,6 +196,14 @@ mod tests {
         assert_eq!(playlist.selected, None);
     }
 
+    #[test]
+    fn pinned_playlists_come_first() {
+        let mut playlists = vec![Playlist::new(), Playlist::new(), Playlist::new()];
+        playlists[2].pinned = true;
+
+        assert_eq!(tab_order(&playlists), vec![2, 0, 1]);
+    }
+
     #[test]
     fn set_name() {
         let mut playlist = Playlist::new();
@@ -115,6 +238,7 @@ mod tests {
                 LibraryItem::new(path3.clone(), LibraryPathId::new(2)),
             ],
             selected: None,
+            ..Playlist::new()
         };
 
         assert_eq!(playlist.tracks.len(), 3);
@@ -140,6 +264,7 @@ mod tests {
                 LibraryItem::new(path3.clone(), LibraryPathId::new(2)),
             ],
             selected: None,
+            ..Playlist::new()
         };
 
         assert_eq!(playlist.tracks.len(), 3);
@@ -152,6 +277,35 @@ mod tests {
         assert_eq!(playlist.tracks[2].path(), path1);
     }
 
+    #[test]
+    fn m3u_export_and_import_keep_the_tracks() {
+        let dir = std::env::temp_dir().join(format!("m3u_round_trip_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Road trip.m3u");
+
+        let mut playlist = Playlist::new();
+        playlist.add(
+            LibraryItem::new(dir.join("gone.mp3"), LibraryPathId::new(0))
+                .set_artist(Some("Artist"))
+                .set_title(Some("Song")),
+        );
+        playlist.add(LibraryItem::new(
+            dir.join("untagged.mp3"),
+            LibraryPathId::new(0),
+        ));
+        playlist.export_m3u(&path).unwrap();
+
+        let imported = Playlist::import_m3u(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(imported.get_name(), Some("Road trip".to_string()));
+        assert_eq!(imported.tracks.len(), 2);
+        assert_eq!(imported.tracks[0].path(), dir.join("gone.mp3"));
+        assert_eq!(imported.tracks[0].artist(), Some("Artist".to_string()));
+        assert_eq!(imported.tracks[0].title(), Some("Song".to_string()));
+        assert_eq!(imported.tracks[1].title(), Some("untagged".to_string()));
+    }
+
     // #[test]
     // fn select_track() {
     //     let track1 = LibraryItem::new(PathBuf::from(r"C:\music\song1.mp3"));
@@ -0,0 +1,211 @@
+//! Loudness normalization: measuring tracks that have no ReplayGain tags the way ReplayGain 2.0
+//! does, from their EBU R128 integrated loudness.
+
+use crate::equalizer::{Biquad, BiquadState};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+/// Loudness every track is brought to, as in ReplayGain 2.0.
+pub const REFERENCE_LUFS: f32 = -18.0;
+
+// Gating blocks are 400ms long and start every 100ms.
+const STEP_MS: u64 = 100;
+const STEPS_PER_BLOCK: usize = 4;
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+// Blocks this far below the ungated loudness are left out, so quiet passages don't drag it down.
+const RELATIVE_GATE_DB: f64 = -10.0;
+
+/// Integrated loudness of the playing track per EBU R128, in LUFS, and its sample peak. Fed from
+/// the same sample stream as the scope. All channels are weighted alike, surround channels
+/// aren't given the extra weight the standard asks for.
+pub struct LoudnessMeter {
+    // Track the measurement belongs to.
+    pub track: Option<PathBuf>,
+    // Part of the track was skipped, so the figure isn't worth keeping.
+    pub is_partial: bool,
+    channels: usize,
+    // The two stages of the K-weighting filter.
+    filters: [Biquad; 2],
+    // Filter history by channel, then stage.
+    states: Vec<[BiquadState; 2]>,
+    step_len: usize,
+    // Mean square of the last few steps, summed over the channels.
+    steps: VecDeque<f64>,
+    // Mean square of each gating block.
+    blocks: Vec<f64>,
+    sum_squares: f64,
+    count: usize,
+    peak: f32,
+}
+
+impl Default for LoudnessMeter {
+    fn default() -> Self {
+        Self::new(48000, 2)
+    }
+}
+
+impl LoudnessMeter {
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        let channels = channels.max(1);
+        Self {
+            track: None,
+            is_partial: false,
+            channels,
+            filters: k_weighting(sample_rate),
+            states: vec![Default::default(); channels],
+            step_len: (sample_rate as u64 * STEP_MS / 1000).max(1) as usize * channels,
+            steps: VecDeque::with_capacity(STEPS_PER_BLOCK),
+            blocks: Vec::new(),
+            sum_squares: 0.0,
+            count: 0,
+            peak: 0.0,
+        }
+    }
+
+    pub fn set_format(&mut self, sample_rate: u32, channels: usize) {
+        *self = Self {
+            track: self.track.take(),
+            is_partial: self.is_partial,
+            blocks: std::mem::take(&mut self.blocks),
+            peak: self.peak,
+            ..Self::new(sample_rate, channels)
+        };
+    }
+
+    /// Starts measuring `track` from scratch.
+    pub fn start(&mut self, track: Option<PathBuf>) {
+        self.track = track;
+        self.is_partial = false;
+        self.states.fill(Default::default());
+        self.steps.clear();
+        self.blocks.clear();
+        self.sum_squares = 0.0;
+        self.count = 0;
+        self.peak = 0.0;
+    }
+
+    /// Takes interleaved samples in the format last set.
+    pub fn push_samples(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.channels) {
+            for (sample, states) in frame.iter().zip(&mut self.states) {
+                self.peak = self.peak.max(sample.abs());
+                let weighted = self
+                    .filters
+                    .iter()
+                    .zip(states.iter_mut())
+                    .fold(*sample, |sample, (filter, state)| {
+                        filter.process(sample, state)
+                    });
+                self.sum_squares += (weighted as f64).powi(2);
+            }
+            self.count += self.channels;
+
+            if self.count >= self.step_len {
+                if self.steps.len() == STEPS_PER_BLOCK {
+                    self.steps.pop_front();
+                }
+                // Summed over the channels rather than averaged, as the standard does.
+                self.steps
+                    .push_back(self.sum_squares * self.channels as f64 / self.count as f64);
+                if self.steps.len() == STEPS_PER_BLOCK {
+                    self.blocks
+                        .push(self.steps.iter().sum::<f64>() / STEPS_PER_BLOCK as f64);
+                }
+
+                self.sum_squares = 0.0;
+                self.count = 0;
+            }
+        }
+    }
+
+    /// Gated loudness in LUFS, once a block above the absolute gate has been measured.
+    pub fn integrated(&self) -> Option<f32> {
+        let absolute_gate = from_lufs(ABSOLUTE_GATE_LUFS);
+        let relative_gate = mean(self.blocks.iter().filter(|block| **block > absolute_gate))?
+            * 10.0f64.powf(RELATIVE_GATE_DB / 10.0);
+        let gated = mean(
+            self.blocks
+                .iter()
+                .filter(|block| **block > absolute_gate && **block > relative_gate),
+        )?;
+
+        Some(to_lufs(gated) as f32)
+    }
+
+    /// Highest sample level so far, 1.0 being full scale.
+    pub fn peak(&self) -> f32 {
+        self.peak
+    }
+}
+
+fn mean<'a>(values: impl Iterator<Item = &'a f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+    (count > 0).then_some(sum / count as f64)
+}
+
+fn to_lufs(mean_square: f64) -> f64 {
+    -0.691 + 10.0 * mean_square.log10()
+}
+
+fn from_lufs(lufs: f64) -> f64 {
+    10.0f64.powf((lufs + 0.691) / 10.0)
+}
+
+// A shelf boosting the highs by about 4dB, then a high pass rolling off the lows, as given in
+// ITU-R BS.1770 for 48kHz and redesigned for other rates the way libebur128 does.
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let rate = sample_rate.max(1) as f64;
+
+    let (f0, gain_db, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (std::f64::consts::PI * f0 / rate).tan();
+    let vh = 10.0f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad::new(
+        [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (std::f64::consts::PI * f0 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad::new(
+        [1.0, -2.0, 1.0],
+        [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    [shelf, high_pass]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sine_at_minus_20db_measures_minus_20_lufs() {
+        let sample_rate = 48000;
+        let mut meter = LoudnessMeter::new(sample_rate, 2);
+        assert_eq!(meter.integrated(), None);
+
+        // Stereo, the same 1kHz sine on both channels, five seconds of it.
+        let samples = (0..sample_rate as usize * 5)
+            .flat_map(|n| {
+                let phase = 2.0 * std::f32::consts::PI * 1000.0 * n as f32 / sample_rate as f32;
+                [0.1 * phase.sin(); 2]
+            })
+            .collect::<Vec<_>>();
+        meter.push_samples(&samples);
+
+        let lufs = meter.integrated().unwrap();
+        assert!((lufs + 20.0).abs() < 0.2, "{lufs}");
+        assert!((meter.peak() - 0.1).abs() < 1e-3);
+
+        // Silence is gated out rather than pulling the figure down.
+        meter.push_samples(&vec![0.0; sample_rate as usize * 10]);
+        assert!((meter.integrated().unwrap() + 20.0).abs() < 0.5);
+    }
+}
@@ -1,6 +1,20 @@
+use crate::app::spectrum::Spectrum;
+
+/// What the scope panel draws.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScopeMode {
+    #[default]
+    Waveform,
+    Spectrum,
+}
+
 pub struct Scope {
     pub write_idx: usize,
     pub buffer: Vec<f32>,
+    pub mode: ScopeMode,
+    /// Rate of the samples in `buffer`, the output's.
+    pub sample_rate: u32,
+    pub spectrum: Spectrum,
 }
 
 impl Default for Scope {
@@ -9,12 +23,25 @@ impl Default for Scope {
     }
 }
 
+/// Number of interleaved samples in `window_ms` of audio, so the scope covers the same stretch of
+/// time whatever the output rate.
+pub fn window_len(sample_rate: u32, channels: usize, window_ms: u64) -> usize {
+    let frames = (sample_rate as u64 * window_ms / 1000).max(1) as usize;
+    frames * channels.max(1)
+}
+
 impl Scope {
-    // TODO - take in ms and figure out buffer size
     pub fn new() -> Self {
+        Self::with_len(48000)
+    }
+
+    pub fn with_len(len: usize) -> Self {
         Self {
             write_idx: 0,
-            buffer: vec![0.0f32; 48000],
+            buffer: vec![0.0f32; len.max(1)],
+            mode: ScopeMode::default(),
+            sample_rate: 48000,
+            spectrum: Spectrum::new(),
         }
     }
 
@@ -28,19 +55,16 @@ impl Scope {
     }
 
     pub fn write_samples(&mut self, samples: &[f32]) {
-        // if slice can fit from write pointer to end, then write all at once.
-        // Otherwise, write from write idx to end of buf.
-        // then update write idx to beginning and write the remaining of slice.
-        if !samples.is_empty() && samples.len() <= self.buffer.len() - self.write_idx {
-            self.buffer[self.write_idx..(samples.len() + self.write_idx)].copy_from_slice(samples);
-            self.write_idx += samples.len();
-        } else {
-            let remaining = self.buffer.len() - self.write_idx;
-            self.buffer[self.write_idx..].copy_from_slice(&samples[..remaining]);
-            self.write_idx = 0;
-            self.buffer[self.write_idx..(samples.len() - remaining)]
-                .copy_from_slice(&samples[remaining..]);
-        }
+        // Only the newest samples fit when there are more than the buffer holds.
+        let len = self.buffer.len();
+        let samples = &samples[samples.len().saturating_sub(len)..];
+
+        // Write from the write idx to the end of the buffer, and the rest from the beginning.
+        let write_idx = self.write_idx % len;
+        let until_end = samples.len().min(len - write_idx);
+        self.buffer[write_idx..write_idx + until_end].copy_from_slice(&samples[..until_end]);
+        self.buffer[..samples.len() - until_end].copy_from_slice(&samples[until_end..]);
+        self.write_idx = (write_idx + samples.len()) % len;
     }
 }
 
@@ -49,19 +73,17 @@ impl<'a> IntoIterator for &'a Scope {
     type IntoIter = ScopeIterator<'a>;
 
     fn into_iter(self) -> Self::IntoIter {
-        let mut index = self.write_idx + 1;
-
-        if index >= self.buffer.len() {
-            index -= &self.buffer.len();
-        }
-
         ScopeIterator {
             scope: self,
-            index,
+            index: self.write_idx % self.buffer.len(),
             counter: 0,
         }
     }
 }
+
+/// Every sample in the buffer once, from the oldest to the one written last. Until the buffer
+/// fills up for the first time the oldest ones are silence, and when no audio comes in the last
+/// drawn samples are shown again.
 pub struct ScopeIterator<'a> {
     scope: &'a Scope,
     index: usize,
@@ -72,18 +94,46 @@ impl Iterator for ScopeIterator<'_> {
     type Item = f32;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.index += 1;
+        if self.counter == self.scope.buffer.len() {
+            return None;
+        }
+
+        let sample = self.scope.buffer[self.index];
+        self.index = (self.index + 1) % self.scope.buffer.len();
         self.counter += 1;
 
-        if self.index >= self.scope.buffer.len() {
-            self.index -= self.scope.buffer.len();
-        }
+        Some(sample)
+    }
+}
 
-        // I bet this is the cause of the issue. Entering an infinite loop
-        if self.index == self.scope.write_idx || self.counter > self.scope.buffer.len() {
-            return None;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_covers_the_same_time_at_any_rate() {
+        assert_eq!(window_len(44100, 2, 100), 8820);
+        assert_eq!(window_len(48000, 2, 100), 9600);
+        assert_eq!(window_len(96000, 1, 100), 9600);
+        assert_eq!(window_len(0, 0, 100), 1);
+    }
+
+    #[test]
+    fn iterates_every_sample_oldest_first() {
+        let mut scope = Scope::with_len(4);
+        for sample in [1.0, 2.0, 3.0, 4.0, 5.0, 6.0] {
+            scope.write_sample(sample);
         }
+        assert_eq!(scope.into_iter().collect::<Vec<_>>(), [3.0, 4.0, 5.0, 6.0]);
+
+        scope.write_samples(&[]);
+        scope.write_samples(&[7.0, 8.0, 9.0]);
+        assert_eq!(scope.into_iter().collect::<Vec<_>>(), [6.0, 7.0, 8.0, 9.0]);
 
-        Some(self.scope.buffer[self.index])
+        scope.write_samples(&[10.0, 11.0, 12.0, 13.0, 14.0]);
+        assert_eq!(
+            scope.into_iter().collect::<Vec<_>>(),
+            [11.0, 12.0, 13.0, 14.0]
+        );
     }
 }
@@ -0,0 +1,123 @@
+use crate::app::library::LibraryItem;
+use crate::app::player::{Player, PlayerError, TrackState};
+use serde::{Deserialize, Serialize};
+
+/// What was playing when the app was closed, so the next start picks up where it left off.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub track: Option<LibraryItem>,
+    pub position: u64,
+    pub queue: Vec<LibraryItem>,
+    // Kept apart from the mute, so a muted session comes back muted at the same level.
+    pub volume: f32,
+    pub is_muted: bool,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            track: None,
+            position: 0,
+            queue: Vec::new(),
+            volume: 1.0,
+            is_muted: false,
+        }
+    }
+}
+
+impl Session {
+    pub fn capture(player: &Player) -> Self {
+        let is_started = matches!(player.track_state, TrackState::Playing | TrackState::Paused);
+
+        Self {
+            track: player.selected_track.clone(),
+            position: if is_started {
+                player.seek_to_timestamp
+            } else {
+                0
+            },
+            queue: player.queue.iter().cloned().collect(),
+            volume: player.unmuted_volume(),
+            is_muted: player.is_muted(),
+        }
+    }
+
+    /// Loads the saved track at its saved position, paused unless `auto_play` is set. Tracks
+    /// whose files are gone are dropped.
+    pub fn restore(self, player: &mut Player, auto_play: bool) -> Result<(), PlayerError> {
+        player.restore_volume(self.volume)?;
+        if self.is_muted {
+            player.toggle_mute()?;
+        }
+
+        player.queue = self
+            .queue
+            .into_iter()
+            .filter(|track| track.path().exists())
+            .collect();
+        player.trim_queue();
+
+        let Some(track) = self.track.filter(|track| track.path().exists()) else {
+            return Ok(());
+        };
+
+        tracing::info!("Resuming {:?} at {}", track.path(), self.position);
+        player.select_track(Some(track))?;
+
+        // Seeking starts playback on the audio thread.
+        player.seek_to(self.position)?;
+        player.track_state = TrackState::Playing;
+        if !auto_play {
+            player.pause()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::library::LibraryPathId;
+    use std::path::PathBuf;
+    use std::sync::atomic::AtomicU32;
+    use std::sync::mpsc::channel;
+    use std::sync::Arc;
+
+    #[test]
+    fn restore_skips_missing_files() {
+        let existing =
+            std::env::temp_dir().join(format!("music-player-{}-session.mp3", std::process::id()));
+        std::fs::write(&existing, []).unwrap();
+        let existing = LibraryItem::new(existing, LibraryPathId::new(0));
+        let missing = LibraryItem::new(PathBuf::from("/does/not/exist.mp3"), LibraryPathId::new(0));
+
+        let (audio_tx, _audio_rx) = channel();
+        let (_ui_tx, ui_rx) = channel();
+        let mut player = Player::new(audio_tx, ui_rx, Arc::new(AtomicU32::new(0)));
+
+        let session = Session {
+            track: Some(existing.clone()),
+            position: 1000,
+            queue: vec![missing.clone(), existing.clone()],
+            ..Default::default()
+        };
+        session.restore(&mut player, false).unwrap();
+
+        assert_eq!(player.selected_track, Some(existing.clone()));
+        assert_eq!(player.seek_to_timestamp, 1000);
+        assert!(matches!(player.track_state, TrackState::Paused));
+        assert_eq!(player.queue.len(), 1);
+        assert_eq!(player.queue[0], existing);
+
+        let session = Session {
+            track: Some(missing),
+            ..Default::default()
+        };
+        session.restore(&mut player, true).unwrap();
+        assert_eq!(player.queue, []);
+
+        std::fs::remove_file(existing.path()).unwrap();
+    }
+}
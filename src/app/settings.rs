@@ -0,0 +1,349 @@
+use crate::app::keybindings::Keybindings;
+use crate::dsp::{FadeCurve, Limiter, NightMode, PanLaw};
+use crate::equalizer::BAND_COUNT;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// User facing preferences. Every field has a default so older config files keep loading when
+/// new settings are added.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub queue_end_behavior: QueueEndBehavior,
+    pub play_threshold: PlayThreshold,
+    pub watch_config_file: bool,
+    pub pan_law: PanLaw,
+    pub gapless: bool,
+    pub verify_decoding: bool,
+    /// Audio backend to play through, `None` for the platform default.
+    pub output_host: Option<String>,
+    /// Silence inserted when one track finishes and the next starts on its own.
+    pub track_gap_ms: u64,
+    /// How long the end of a track overlaps the start of the next one, 0 for a hard cut.
+    pub crossfade_secs: f32,
+    /// Shape of the fades where one track overlaps the next.
+    pub crossfade_curve: FadeCurve,
+    pub seek_precision: SeekPrecision,
+    pub repeat: RepeatMode,
+    /// Replaced by `repeat`, only read so `App::migrate` can carry it over.
+    #[serde(skip_serializing)]
+    pub repeat_all: bool,
+    pub keybindings: Keybindings,
+    /// How much audio the oscilloscope shows.
+    pub scope_window_ms: u64,
+    /// How fast the spectrum's bars drop back down, in dB per second.
+    pub spectrum_fall_off_db: f32,
+    pub shuffle: ShuffleMode,
+    /// Pressing play with nothing selected starts the queue or the current playlist.
+    pub play_starts_playlist: bool,
+    /// Show a button to preview the next track over the playing one.
+    pub audition_enabled: bool,
+    /// Map numeric ID3v1 genre codes to their names on import.
+    pub normalize_genres: bool,
+    /// Group albums, artists and genres whose names only differ in case or spacing, like "Abbey
+    /// Road" and "abbey road".
+    pub merge_spellings: bool,
+    /// Fill in tags missing from files from the folders they're in, laid out as `folder_pattern`.
+    pub infer_tags_from_folders: bool,
+    pub folder_pattern: String,
+    /// How many folders deep an import looks below a library path, `None` for no limit.
+    pub import_max_depth: Option<usize>,
+    /// Follow symbolic links while importing. Loops are detected and skipped.
+    pub import_follow_symlinks: bool,
+    pub unreadable_tags: UnreadableTags,
+    /// Start playing right away when the last session is restored, instead of paused.
+    pub resume_autoplay: bool,
+    /// Closing the window hides it and playback carries on, while there's a tray icon to bring it
+    /// back.
+    pub close_to_tray: bool,
+    pub night_mode: NightMode,
+    /// Shape of the fade when playback starts or resumes.
+    pub fade_curve: FadeCurve,
+    pub limiter: Limiter,
+    /// Boost or cut of each equalizer band in dB, the lowest band first.
+    pub eq_gains_db: [f32; BAND_COUNT],
+    /// Evens out the loudness of tracks by their ReplayGain tags.
+    pub normalization: NormalizationMode,
+    /// How far the skip back and skip forward buttons jump.
+    pub skip_backward_secs: u64,
+    pub skip_forward_secs: u64,
+    /// File kept up to date with the playing track, for stream overlays and the like.
+    pub now_playing_file: Option<PathBuf>,
+    /// Longest the listening history and the "play next" queue may grow, the oldest entries are
+    /// dropped past that.
+    pub max_history_entries: usize,
+    pub max_queue_len: usize,
+    /// What double-clicking an album in the library does.
+    pub album_double_click: AlbumAction,
+    /// Image shown for tracks without any cover art of their own.
+    pub default_cover: Option<PathBuf>,
+    pub audio_thread_priority: AudioThreadPriority,
+    pub network_retry: NetworkRetry,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            queue_end_behavior: QueueEndBehavior::ContinuePlaylist,
+            play_threshold: PlayThreshold::default(),
+            watch_config_file: false,
+            pan_law: PanLaw::EqualPower,
+            gapless: true,
+            verify_decoding: true,
+            output_host: None,
+            track_gap_ms: 0,
+            crossfade_secs: 0.0,
+            crossfade_curve: FadeCurve::EqualPower,
+            seek_precision: SeekPrecision::Accurate,
+            repeat: RepeatMode::Off,
+            repeat_all: false,
+            keybindings: Keybindings::default(),
+            scope_window_ms: 100,
+            spectrum_fall_off_db: 60.0,
+            shuffle: ShuffleMode::Off,
+            play_starts_playlist: true,
+            audition_enabled: false,
+            normalize_genres: true,
+            merge_spellings: true,
+            infer_tags_from_folders: false,
+            folder_pattern: "{artist}/{album}/{track} {title}".to_string(),
+            import_max_depth: None,
+            import_follow_symlinks: false,
+            unreadable_tags: UnreadableTags::UseFileName,
+            resume_autoplay: false,
+            close_to_tray: false,
+            night_mode: NightMode::default(),
+            fade_curve: FadeCurve::Linear,
+            limiter: Limiter::default(),
+            eq_gains_db: [0.0; BAND_COUNT],
+            normalization: NormalizationMode::Off,
+            skip_backward_secs: 10,
+            skip_forward_secs: 30,
+            now_playing_file: None,
+            max_history_entries: 10_000,
+            max_queue_len: 1_000,
+            album_double_click: AlbumAction::Add,
+            default_cover: None,
+            audio_thread_priority: AudioThreadPriority::High,
+            network_retry: NetworkRetry::default(),
+        }
+    }
+}
+
+/// How much of a track has to be heard before it counts as played. Whichever limit is reached
+/// first wins.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayThreshold {
+    pub percent: f32,
+    pub seconds: u64,
+}
+
+impl Default for PlayThreshold {
+    // The last.fm scrobbling rules.
+    fn default() -> Self {
+        Self {
+            percent: 50.0,
+            seconds: 240,
+        }
+    }
+}
+
+impl PlayThreshold {
+    pub fn is_met(&self, fraction_played: f32, seconds_played: f32) -> bool {
+        fraction_played * 100.0 >= self.percent || seconds_played >= self.seconds as f32
+    }
+}
+
+/// How long opening a track may take, e.g. on a slow network share, and how often it's tried
+/// again before the track is skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkRetry {
+    pub timeout_secs: u64,
+    pub retries: u32,
+}
+
+impl Default for NetworkRetry {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 5,
+            retries: 3,
+        }
+    }
+}
+
+/// What an import does with files whose tags can't be read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UnreadableTags {
+    /// Import them anyway, titled after their file name.
+    #[default]
+    UseFileName,
+    Skip,
+}
+
+impl std::fmt::Display for UnreadableTags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnreadableTags::UseFileName => write!(f, "Import, titled after the file name"),
+            UnreadableTags::Skip => write!(f, "Skip"),
+        }
+    }
+}
+
+/// Ways of putting a whole album from the library into the current playlist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AlbumAction {
+    Add,
+    AddAndPlay,
+    /// Clear the playlist first.
+    ReplaceAndPlay,
+}
+
+impl std::fmt::Display for AlbumAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AlbumAction::Add => write!(f, "Add to playlist"),
+            AlbumAction::AddAndPlay => write!(f, "Add and play"),
+            AlbumAction::ReplaceAndPlay => write!(f, "Replace playlist and play"),
+        }
+    }
+}
+
+/// What happens once the last track of the "play next" queue has finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QueueEndBehavior {
+    /// Pick the playlist back up from the last queued track's position.
+    ContinuePlaylist,
+    Stop,
+}
+
+impl std::fmt::Display for QueueEndBehavior {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            QueueEndBehavior::ContinuePlaylist => write!(f, "Continue playlist"),
+            QueueEndBehavior::Stop => write!(f, "Stop"),
+        }
+    }
+}
+
+/// Trade-off between landing exactly on the requested position and seeking quickly.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SeekPrecision {
+    /// Decode up to the exact requested sample.
+    #[default]
+    Accurate,
+    /// Jump to the nearest point the format can seek to cheaply, which may be a keyframe
+    /// boundary before the requested position. Much faster on slow media.
+    Coarse,
+}
+
+impl std::fmt::Display for SeekPrecision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SeekPrecision::Accurate => write!(f, "Accurate"),
+            SeekPrecision::Coarse => write!(f, "Fast (coarse)"),
+        }
+    }
+}
+
+/// Scheduling priority asked for the audio thread, so decoding keeps up on a busy system.
+///
+/// Raising it may not be permitted: on Linux it needs `CAP_SYS_NICE` or a suitable
+/// `RLIMIT_RTPRIO`/`RLIMIT_NICE`, in which case the thread keeps its normal priority. Going back
+/// to `Normal` only takes effect after a restart.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AudioThreadPriority {
+    /// Leave the thread as the OS created it.
+    Normal,
+    #[default]
+    High,
+    /// The highest priority the OS allows, real-time scheduling where available.
+    Highest,
+}
+
+impl std::fmt::Display for AudioThreadPriority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AudioThreadPriority::Normal => write!(f, "Normal"),
+            AudioThreadPriority::High => write!(f, "High"),
+            AudioThreadPriority::Highest => write!(f, "Highest"),
+        }
+    }
+}
+
+/// What happens when a track or playlist finishes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RepeatMode {
+    /// Stop at the end of the playlist, or chain of linked playlists.
+    #[default]
+    Off,
+    /// Play the finished track again.
+    One,
+    /// Start over when the end of the playlist, or chain of linked playlists, is reached.
+    All,
+}
+
+impl RepeatMode {
+    /// The mode after this one, for a button that steps through them.
+    pub fn cycled(self) -> Self {
+        match self {
+            RepeatMode::Off => RepeatMode::All,
+            RepeatMode::All => RepeatMode::One,
+            RepeatMode::One => RepeatMode::Off,
+        }
+    }
+}
+
+impl std::fmt::Display for RepeatMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RepeatMode::Off => write!(f, "Off"),
+            RepeatMode::One => write!(f, "Repeat track"),
+            RepeatMode::All => write!(f, "Repeat all"),
+        }
+    }
+}
+
+/// Which ReplayGain figure tracks are played back at.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NormalizationMode {
+    #[default]
+    Off,
+    /// Every track at the same loudness.
+    Track,
+    /// Albums at the same loudness, keeping the differences between their tracks. Tracks without
+    /// an album gain fall back to their track gain.
+    Album,
+}
+
+impl std::fmt::Display for NormalizationMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NormalizationMode::Off => write!(f, "Off"),
+            NormalizationMode::Track => write!(f, "Track"),
+            NormalizationMode::Album => write!(f, "Album"),
+        }
+    }
+}
+
+/// How the next track is picked when moving on through a playlist.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ShuffleMode {
+    /// Play the playlist in order.
+    #[default]
+    Off,
+    Uniform,
+    /// Favor tracks with fewer plays, to surface music that rarely gets heard.
+    LeastPlayed,
+}
+
+impl std::fmt::Display for ShuffleMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShuffleMode::Off => write!(f, "Off"),
+            ShuffleMode::Uniform => write!(f, "Shuffle"),
+            ShuffleMode::LeastPlayed => write!(f, "Shuffle, favor less played"),
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use crate::app::history::History;
+use crate::app::library::LibraryItem;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+/// A shuffled play order of a playlist's tracks, by index, for `ShuffleMode::Uniform`. It's made
+/// once from a seed, so going back and forth through it plays the same tracks, and every track
+/// comes up once before the order runs out.
+#[derive(Debug, Clone)]
+pub struct ShuffleOrder {
+    playlist_id: u64,
+    order: Vec<usize>,
+    rng: StdRng,
+}
+
+impl ShuffleOrder {
+    /// Starts with `current`, the other tracks follow in random order.
+    pub fn new(playlist_id: u64, len: usize, current: Option<usize>, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let current = current.filter(|current| *current < len);
+        let mut order = (0..len)
+            .filter(|idx| Some(*idx) != current)
+            .collect::<Vec<_>>();
+        order.shuffle(&mut rng);
+        if let Some(current) = current {
+            order.insert(0, current);
+        }
+
+        Self {
+            playlist_id,
+            order,
+            rng,
+        }
+    }
+
+    /// Whether the order can still be used for the playlist, which only ever had tracks appended
+    /// since.
+    pub fn is_for(&self, playlist_id: u64, len: usize) -> bool {
+        self.playlist_id == playlist_id && self.order.len() <= len
+    }
+
+    pub fn playlist_id(&self) -> u64 {
+        self.playlist_id
+    }
+
+    /// Folds tracks appended to the playlist into the part of the order still to come after
+    /// `current`, so they don't push anything already heard back in.
+    pub fn extend(&mut self, len: usize, current: Option<usize>) {
+        let start = current
+            .and_then(|current| self.position(current))
+            .map_or(0, |position| position + 1);
+        for idx in self.order.len()..len {
+            let at = self.rng.gen_range(start..=self.order.len());
+            self.order.insert(at, idx);
+        }
+    }
+
+    /// The track after `current`, or the first one when `current` isn't in the playlist.
+    pub fn after(&self, current: Option<usize>) -> Option<usize> {
+        match current.and_then(|current| self.position(current)) {
+            Some(position) => self.order.get(position + 1).copied(),
+            None => self.order.first().copied(),
+        }
+    }
+
+    /// Follows a track moved from `from` to `to` in the playlist, so every track keeps its turn.
+    pub fn moved(&mut self, from: usize, to: usize) {
+        for idx in &mut self.order {
+            if *idx == from {
+                *idx = to;
+            } else if from < to && (from + 1..=to).contains(idx) {
+                *idx -= 1;
+            } else if to < from && (to..from).contains(idx) {
+                *idx += 1;
+            }
+        }
+    }
+
+    /// Drops a track taken out of the playlist, the ones after it move up.
+    pub fn removed(&mut self, removed: usize) {
+        self.order.retain(|idx| *idx != removed);
+        for idx in &mut self.order {
+            if *idx > removed {
+                *idx -= 1;
+            }
+        }
+    }
+
+    pub fn before(&self, current: usize) -> Option<usize> {
+        let position = self.position(current)?;
+        self.order.get(position.checked_sub(1)?).copied()
+    }
+
+    fn position(&self, idx: usize) -> Option<usize> {
+        self.order.iter().position(|order_idx| *order_idx == idx)
+    }
+}
+
+/// Picks the index of a random track to play after `current` for `ShuffleMode::LeastPlayed`.
+/// Each track is weighed by the inverse of its play count, so rarely heard tracks come up more
+/// often. `current` itself is never picked, so this returns `None` when it's the only track or
+/// there are no tracks at all.
+pub fn pick_track(
+    tracks: &[LibraryItem],
+    current: Option<&LibraryItem>,
+    history: &History,
+    rng: &mut impl Rng,
+) -> Option<usize> {
+    let candidates = tracks
+        .iter()
+        .enumerate()
+        .filter(|(_, track)| Some(*track) != current)
+        .map(|(idx, _)| idx)
+        .collect::<Vec<_>>();
+
+    if candidates.is_empty() {
+        return None;
+    }
+
+    let play_counts = candidates
+        .iter()
+        .map(|idx| history.play_count(&tracks[*idx].path()))
+        .collect::<Vec<_>>();
+
+    // Nothing has been played yet, so there's nothing to bias towards.
+    let weighted = if play_counts.iter().all(|count| *count == 0) {
+        None
+    } else {
+        WeightedIndex::new(play_counts.iter().map(|count| 1.0 / (*count as f64 + 1.0))).ok()
+    };
+
+    let choice = match weighted {
+        Some(weighted) => weighted.sample(rng),
+        None => rng.gen_range(0..candidates.len()),
+    };
+
+    Some(candidates[choice])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::library::LibraryPathId;
+    use std::path::PathBuf;
+
+    fn track(name: &str) -> LibraryItem {
+        LibraryItem::new(PathBuf::from(name), LibraryPathId::new(0))
+    }
+
+    #[test]
+    fn least_played_favors_neglected_tracks() {
+        let tracks = [track("current.mp3"), track("new.mp3"), track("worn.mp3")];
+        let mut history = History::default();
+        for _ in 0..9 {
+            history.record_play(&tracks[2]);
+        }
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut picks = [0; 3];
+        for _ in 0..1000 {
+            let idx = pick_track(&tracks, Some(&tracks[0]), &history, &mut rng).unwrap();
+            picks[idx] += 1;
+        }
+
+        assert_eq!(picks[0], 0);
+        // Weighted 1 to 1/10.
+        assert!(picks[1] > picks[2] * 5, "{picks:?}");
+    }
+
+    #[test]
+    fn shuffled_order_is_reproducible_and_takes_in_new_tracks() {
+        let order = ShuffleOrder::new(1, 10, Some(3), 7);
+        assert_eq!(order.order, ShuffleOrder::new(1, 10, Some(3), 7).order);
+        assert_eq!(order.order[0], 3);
+
+        let mut walked = vec![3];
+        while let Some(next) = order.after(walked.last().copied()) {
+            assert_eq!(order.before(next), walked.last().copied());
+            walked.push(next);
+        }
+        walked.sort_unstable();
+        assert_eq!(walked, (0..10).collect::<Vec<_>>());
+
+        // Two tracks were heard, then three more were added.
+        let mut order = ShuffleOrder::new(1, 10, Some(3), 7);
+        let heard = order.after(Some(3));
+        order.extend(13, heard);
+        assert!(order.is_for(1, 13));
+        assert_eq!(order.order[..2], [3, heard.unwrap()]);
+        assert_eq!(order.order.len(), 13);
+    }
+
+    #[test]
+    fn shuffled_order_follows_moved_and_removed_tracks() {
+        let tracks = ["a", "b", "c", "d"];
+        let mut playlist = tracks.to_vec();
+        let mut order = ShuffleOrder::new(1, 4, None, 7);
+        let walked = |order: &ShuffleOrder, playlist: &[&'static str]| {
+            order
+                .order
+                .iter()
+                .map(|idx| playlist[*idx])
+                .collect::<Vec<_>>()
+        };
+        let before = walked(&order, &playlist);
+
+        let moved = playlist.remove(0);
+        playlist.insert(2, moved);
+        order.moved(0, 2);
+        assert_eq!(walked(&order, &playlist), before);
+
+        let moved = playlist.remove(3);
+        playlist.insert(1, moved);
+        order.moved(3, 1);
+        assert_eq!(walked(&order, &playlist), before);
+
+        let removed = playlist.remove(1);
+        order.removed(1);
+        let mut expected = before.clone();
+        expected.retain(|track| *track != removed);
+        assert_eq!(walked(&order, &playlist), expected);
+    }
+
+    #[test]
+    fn never_repeats_the_only_track() {
+        let tracks = [track("only.mp3")];
+        let mut rng = StdRng::seed_from_u64(7);
+
+        let idx = pick_track(&tracks, Some(&tracks[0]), &History::default(), &mut rng);
+
+        assert_eq!(idx, None);
+    }
+}
@@ -0,0 +1,130 @@
+//! The scope's frequency view: levels of the played audio in log-spaced bands, like a graphic
+//! equalizer display.
+
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use std::sync::Arc;
+
+// Samples transformed at once, about 43ms at 48kHz. Bands narrower than a bin show the nearest
+// bin, so the lowest bars move together.
+const FFT_LEN: usize = 2048;
+pub const BAR_COUNT: usize = 48;
+pub const MIN_HZ: f32 = 20.0;
+const MAX_HZ: f32 = 20_000.0;
+/// The quietest level shown, bars at this level are empty.
+pub const FLOOR_DB: f32 = -80.0;
+
+pub struct Spectrum {
+    fft: Arc<dyn Fft<f32>>,
+    buffer: Vec<Complex<f32>>,
+    /// Level of each bar in dB, the lowest band first.
+    pub bars: Vec<f32>,
+}
+
+impl Default for Spectrum {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Spectrum {
+    pub fn new() -> Self {
+        Self {
+            fft: FftPlanner::new().plan_fft_forward(FFT_LEN),
+            buffer: vec![Complex::default(); FFT_LEN],
+            bars: vec![FLOOR_DB; BAR_COUNT],
+        }
+    }
+
+    /// Transforms the newest of `samples`, a single channel at `sample_rate`. Bars jump up to
+    /// louder levels straight away but fall by at most `fall_off_db` per second, so they don't
+    /// flicker from one frame to the next.
+    pub fn update(&mut self, samples: &[f32], sample_rate: u32, fall_off_db: f32, elapsed: f32) {
+        let samples = &samples[samples.len().saturating_sub(FFT_LEN)..];
+
+        // A Hann window keeps loud bands from leaking into their quiet neighbours. Short scope
+        // windows are padded with silence.
+        let window = hann_window(samples.len());
+        let window_sum = window.iter().sum::<f32>().max(f32::EPSILON);
+        self.buffer.fill(Complex::default());
+        for ((bin, sample), weight) in self.buffer.iter_mut().zip(samples).zip(&window) {
+            *bin = Complex::new(sample * weight, 0.0);
+        }
+        self.fft.process(&mut self.buffer);
+
+        let bin_hz = sample_rate as f32 / FFT_LEN as f32;
+        let nyquist_bin = FFT_LEN / 2;
+        for ((low_hz, high_hz), bar) in band_edges(sample_rate).into_iter().zip(&mut self.bars) {
+            let low_bin = ((low_hz / bin_hz).ceil() as usize).min(nyquist_bin);
+            let high_bin = ((high_hz / bin_hz).ceil() as usize).min(nyquist_bin);
+            let bins = if low_bin < high_bin {
+                low_bin..high_bin
+            } else {
+                let center_bin = ((low_hz * high_hz).sqrt() / bin_hz).round() as usize;
+                center_bin.min(nyquist_bin)..center_bin.min(nyquist_bin) + 1
+            };
+
+            // Scaled so a full scale sine reads 0dB.
+            let magnitude = self.buffer[bins]
+                .iter()
+                .map(|bin| bin.norm() * 2.0 / window_sum)
+                .fold(0.0, f32::max);
+            let level = (20.0 * magnitude.max(f32::MIN_POSITIVE).log10()).clamp(FLOOR_DB, 0.0);
+
+            *bar = level.max(*bar - fall_off_db * elapsed).max(FLOOR_DB);
+        }
+    }
+}
+
+/// The highest frequency shown at `sample_rate`.
+pub fn max_hz(sample_rate: u32) -> f32 {
+    (sample_rate as f32 / 2.0).min(MAX_HZ)
+}
+
+/// Lower and upper edge in Hz of each bar, evenly spaced on a log scale.
+pub fn band_edges(sample_rate: u32) -> Vec<(f32, f32)> {
+    let ratio = (max_hz(sample_rate) / MIN_HZ).max(1.0);
+    let edge = |bar: usize| MIN_HZ * ratio.powf(bar as f32 / BAR_COUNT as f32);
+
+    (0..BAR_COUNT)
+        .map(|bar| (edge(bar), edge(bar + 1)))
+        .collect()
+}
+
+fn hann_window(len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|n| {
+            let phase = 2.0 * std::f32::consts::PI * n as f32 / len.saturating_sub(1).max(1) as f32;
+            0.5 - 0.5 * phase.cos()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_sine_lights_up_its_band() {
+        let sample_rate = 48000;
+        let sine = (0..FFT_LEN)
+            .map(|n| (2.0 * std::f32::consts::PI * 1000.0 * n as f32 / sample_rate as f32).sin())
+            .collect::<Vec<_>>();
+
+        let mut spectrum = Spectrum::new();
+        spectrum.update(&sine, sample_rate, 60.0, 0.0);
+
+        let loudest = (0..BAR_COUNT)
+            .max_by(|a, b| spectrum.bars[*a].total_cmp(&spectrum.bars[*b]))
+            .unwrap();
+        let (low_hz, high_hz) = band_edges(sample_rate)[loudest];
+        assert!((low_hz..high_hz).contains(&1000.0), "{low_hz}..{high_hz}");
+        assert!(spectrum.bars[loudest] > -3.0);
+        assert!(spectrum.bars[0] < -60.0);
+
+        // Silence lets the bars fall at the given rate.
+        let level = spectrum.bars[loudest];
+        spectrum.update(&[0.0; FFT_LEN], sample_rate, 60.0, 0.1);
+        assert!((spectrum.bars[loudest] - (level - 6.0)).abs() < 1e-3);
+    }
+}
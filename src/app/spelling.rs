@@ -0,0 +1,101 @@
+use std::collections::{BTreeMap, HashMap};
+
+/// Key shared by spellings of a name that only differ in case or spacing, e.g. "rock", "Rock"
+/// and " ROCK".
+pub fn canonical_key(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// The spelling to show for each canonical key. The most common spelling wins, ties go to the
+/// title cased one, so "Rock" beats "rock" and "ROCK".
+pub fn canonical_names<'a>(names: impl IntoIterator<Item = &'a str>) -> HashMap<String, String> {
+    spellings(names)
+        .into_iter()
+        .map(|(key, spellings)| (key, preferred(&spellings).to_string()))
+        .collect()
+}
+
+/// Names written more than one way, as the shown spelling and every spelling it stands for.
+pub fn merged_spellings<'a>(
+    names: impl IntoIterator<Item = &'a str>,
+) -> Vec<(String, Vec<String>)> {
+    spellings(names)
+        .into_values()
+        .filter(|spellings| spellings.len() > 1)
+        .map(|spellings| {
+            (
+                preferred(&spellings).to_string(),
+                spellings.into_keys().collect(),
+            )
+        })
+        .collect()
+}
+
+// Counts of each spelling, by canonical key.
+fn spellings<'a>(
+    names: impl IntoIterator<Item = &'a str>,
+) -> BTreeMap<String, BTreeMap<String, usize>> {
+    let mut spellings = BTreeMap::<String, BTreeMap<String, usize>>::new();
+    for name in names {
+        let spelling = name.split_whitespace().collect::<Vec<_>>().join(" ");
+        *spellings
+            .entry(canonical_key(&spelling))
+            .or_default()
+            .entry(spelling)
+            .or_default() += 1;
+    }
+
+    spellings
+}
+
+fn preferred(spellings: &BTreeMap<String, usize>) -> &str {
+    spellings
+        .iter()
+        // `max_by_key` keeps the last of equal elements, go backwards to prefer the first.
+        .rev()
+        .max_by_key(|(spelling, count)| (**count, is_title_case(spelling)))
+        .map(|(spelling, _)| spelling.as_str())
+        .unwrap_or_default()
+}
+
+fn is_title_case(name: &str) -> bool {
+    name.split_whitespace().all(|word| {
+        let mut chars = word.chars();
+        !chars.next().is_some_and(|first| first.is_lowercase()) && !chars.any(|c| c.is_uppercase())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spellings_differing_in_case_are_merged() {
+        let names = ["rock", "Rock", "ROCK", " Rock ", "Jazz"];
+
+        let canonical = canonical_names(names);
+        assert_eq!(canonical[&canonical_key("rock")], "Rock");
+        assert_eq!(canonical[&canonical_key("jazz")], "Jazz");
+
+        assert_eq!(
+            merged_spellings(names),
+            [(
+                "Rock".to_string(),
+                vec!["ROCK".to_string(), "Rock".to_string(), "rock".to_string()]
+            )]
+        );
+    }
+
+    #[test]
+    fn title_case_wins_ties() {
+        let canonical = canonical_names(["the beatles", "The Beatles"]);
+        assert_eq!(canonical["the beatles"], "The Beatles");
+
+        // Unless another spelling is more common.
+        let canonical = canonical_names(["AC/DC", "AC/DC", "Ac/dc"]);
+        assert_eq!(canonical["ac/dc"], "AC/DC");
+    }
+}
@@ -0,0 +1,98 @@
+//! Reading the tags the library keeps, whichever format the file is tagged in.
+
+use lofty::file::TaggedFileExt;
+use lofty::tag::{Accessor, ItemKey};
+use std::path::Path;
+
+/// Extensions of the files imported into the library, lowercase.
+pub const SUPPORTED_EXTENSIONS: &[&str] = &["mp3", "flac", "ogg", "wav", "m4a", "aac"];
+
+pub fn is_supported(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| {
+        SUPPORTED_EXTENSIONS
+            .iter()
+            .any(|supported| extension.eq_ignore_ascii_case(supported))
+    })
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub year: Option<i32>,
+    pub genre: Option<String>,
+    pub track_number: Option<u32>,
+    pub disc_number: Option<u32>,
+    // iTunes' compilation flag, set for albums by various artists.
+    pub is_compilation: bool,
+    // ReplayGain adjustments in dB, and the peaks they were worked out with, 1.0 being full scale.
+    pub track_gain_db: Option<f32>,
+    pub track_peak: Option<f32>,
+    pub album_gain_db: Option<f32>,
+    pub album_peak: Option<f32>,
+}
+
+/// `None` when the file has no tags that can be read.
+pub fn read(path: &Path) -> Option<Tags> {
+    let tagged_file = lofty::read_from_path(path).ok()?;
+    // ID3v2 for MP3s, Vorbis comments for FLAC and Ogg, and so on. Files tagged in an older or
+    // unusual way still have theirs read.
+    let tag = tagged_file
+        .primary_tag()
+        .or_else(|| tagged_file.first_tag())?;
+
+    Some(Tags {
+        title: tag.title().map(String::from),
+        artist: tag.artist().map(String::from),
+        album: tag.album().map(String::from),
+        year: tag.year().and_then(|year| i32::try_from(year).ok()),
+        genre: tag.genre().map(String::from),
+        track_number: tag.track(),
+        disc_number: tag.disk(),
+        is_compilation: tag
+            .get_string(&ItemKey::FlagCompilation)
+            .is_some_and(|flag| matches!(flag.trim(), "1" | "true")),
+        track_gain_db: read_number(tag, &ItemKey::ReplayGainTrackGain),
+        track_peak: read_number(tag, &ItemKey::ReplayGainTrackPeak),
+        album_gain_db: read_number(tag, &ItemKey::ReplayGainAlbumGain),
+        album_peak: read_number(tag, &ItemKey::ReplayGainAlbumPeak),
+    })
+}
+
+fn read_number(tag: &lofty::tag::Tag, key: &ItemKey) -> Option<f32> {
+    tag.get_string(key).and_then(parse_number)
+}
+
+// ReplayGain values are written as text, gains usually with their unit, like "-6.54 dB".
+fn parse_number(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let value = value
+        .strip_suffix("dB")
+        .or_else(|| value.strip_suffix("db"))
+        .unwrap_or(value);
+    value
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|number| number.is_finite())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extensions_are_matched_regardless_of_case() {
+        assert!(is_supported(Path::new("/music/Song.FLAC")));
+        assert!(!is_supported(Path::new("/music/cover.jpg")));
+    }
+
+    #[test]
+    fn replay_gain_values_are_read_with_or_without_a_unit() {
+        assert_eq!(parse_number("-6.54 dB"), Some(-6.54));
+        assert_eq!(parse_number("+2.10dB"), Some(2.1));
+        assert_eq!(parse_number("0.988831"), Some(0.988831));
+        assert_eq!(parse_number("loud"), None);
+    }
+}
@@ -0,0 +1,81 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+/// Window size used when none was saved.
+pub const DEFAULT_SIZE: [f32; 2] = [1024.0, 768.0];
+// Smallest a saved window is brought back at.
+const MIN_SIZE: [f32; 2] = [320.0, 240.0];
+
+/// Where the window was on screen and how big, in points, so the next start opens it the same.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    /// Top left corner of the window, including its decorations.
+    pub position: [f32; 2],
+    /// Size of the window's contents.
+    pub size: [f32; 2],
+}
+
+impl WindowGeometry {
+    /// Reads the geometry of the window `ctx` draws into. `None` while it's minimized or
+    /// fullscreen, or the platform doesn't tell.
+    pub fn of_window(ctx: &egui::Context) -> Option<Self> {
+        ctx.input(|input| {
+            let viewport = input.viewport();
+            if viewport.minimized == Some(true) || viewport.fullscreen == Some(true) {
+                return None;
+            }
+
+            Some(Self {
+                position: viewport.outer_rect?.min.into(),
+                size: viewport.inner_rect?.size().into(),
+            })
+        })
+    }
+
+    pub fn viewport(&self, builder: egui::ViewportBuilder) -> egui::ViewportBuilder {
+        builder
+            .with_inner_size(self.size)
+            .with_position(self.position)
+    }
+
+    /// Moved and shrunk as needed to fit a display of `display_size` with its top left corner at
+    /// the origin, e.g. when it was last shown on a monitor that's been unplugged since.
+    pub fn clamped_to(&self, display_size: [f32; 2]) -> Self {
+        let mut clamped = *self;
+        for axis in 0..2 {
+            clamped.size[axis] = self.size[axis].min(display_size[axis]).max(MIN_SIZE[axis]);
+            clamped.position[axis] = self.position[axis]
+                .min(display_size[axis] - clamped.size[axis])
+                .max(0.0);
+        }
+        clamped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_off_screen_are_brought_back() {
+        let display = [1920.0, 1080.0];
+        let on_screen = WindowGeometry {
+            position: [100.0, 50.0],
+            size: [1024.0, 768.0],
+        };
+        assert_eq!(on_screen.clamped_to(display), on_screen);
+
+        // Last shown on a second monitor to the right.
+        let off_screen = WindowGeometry {
+            position: [2500.0, -40.0],
+            size: [3000.0, 768.0],
+        };
+        assert_eq!(
+            off_screen.clamped_to(display),
+            WindowGeometry {
+                position: [0.0, 0.0],
+                size: [1920.0, 768.0],
+            }
+        );
+    }
+}
@@ -0,0 +1,107 @@
+//! Checking that audio can be played at all, without involving any file.
+
+use std::thread::JoinHandle;
+
+use rb::{SpscRb, RB};
+use symphonia::core::audio::{AsAudioBufferRef, AudioBuffer, Channels, Signal, SignalSpec};
+
+use crate::dsp::SignalChain;
+use crate::output;
+
+const TONE_HZ: f32 = 440.0;
+// About -20dB, audible without being startling.
+const TONE_AMPLITUDE: f32 = 0.1;
+const TONE_SECS: f32 = 0.5;
+const SAMPLE_RATE: u32 = 48000;
+const FRAMES_PER_WRITE: usize = 1024;
+
+/// Plays a short tone on a fresh output stream in the background and reports how it went.
+pub struct AudioCheck {
+    handle: Option<JoinHandle<Result<String, String>>>,
+    result: Option<Result<String, String>>,
+}
+
+impl AudioCheck {
+    pub fn start(output_host: Option<String>) -> Self {
+        let handle = std::thread::spawn(move || play_tone(output_host.as_deref()));
+
+        Self {
+            handle: Some(handle),
+            result: None,
+        }
+    }
+
+    /// `None` while the tone is still playing. Otherwise a description of the output it played
+    /// on, or what went wrong.
+    pub fn result(&mut self) -> Option<&Result<String, String>> {
+        if self
+            .handle
+            .as_ref()
+            .is_some_and(|handle| handle.is_finished())
+        {
+            let handle = self.handle.take().unwrap();
+            self.result = Some(
+                handle
+                    .join()
+                    .unwrap_or_else(|_| Err("the check crashed".to_string())),
+            );
+        }
+
+        self.result.as_ref()
+    }
+}
+
+fn play_tone(output_host: Option<&str>) -> Result<String, String> {
+    let spec = SignalSpec::new(SAMPLE_RATE, Channels::FRONT_LEFT | Channels::FRONT_RIGHT);
+    let mut audio_output = output::try_open(spec, FRAMES_PER_WRITE as u64, output_host, None)
+        .map_err(|err| format!("couldn't open the output: {err:?}"))?;
+
+    // The tone doesn't show up in the scope, its samples are just dropped.
+    let scope_ring_buf = SpscRb::new(1);
+    let scope_producer = scope_ring_buf.producer();
+    let mut signal_chain = SignalChain::default();
+
+    let total_frames = (TONE_SECS * SAMPLE_RATE as f32) as usize;
+    let mut buffer = AudioBuffer::<f32>::new(FRAMES_PER_WRITE as u64, spec);
+    for start in (0..total_frames).step_by(FRAMES_PER_WRITE) {
+        let frames = FRAMES_PER_WRITE.min(total_frames - start);
+        buffer.clear();
+        buffer.render_reserved(Some(frames));
+        for channel in 0..spec.channels.count() {
+            for (i, sample) in buffer.chan_mut(channel).iter_mut().enumerate() {
+                *sample = tone_sample(start + i, total_frames);
+            }
+        }
+
+        audio_output
+            .write(
+                buffer.as_audio_buffer_ref(),
+                &scope_producer,
+                &mut signal_chain,
+            )
+            .map_err(|err| format!("couldn't write to the output: {err:?}"))?;
+    }
+
+    audio_output.drain(std::time::Duration::from_secs(1));
+    audio_output.flush();
+
+    let (rate, channels) = audio_output.device_config();
+    Ok(format!(
+        "{} on {}, {} Hz, {} ch",
+        audio_output.device_name(),
+        audio_output.host_name(),
+        rate,
+        channels
+    ))
+}
+
+// A sine with 10ms fades at either end, so the tone itself doesn't click.
+fn tone_sample(frame: usize, total_frames: usize) -> f32 {
+    let fade_frames = SAMPLE_RATE as f32 * 0.01;
+    let envelope = (frame as f32 / fade_frames)
+        .min((total_frames - frame) as f32 / fade_frames)
+        .min(1.0);
+    let phase = 2.0 * std::f32::consts::PI * TONE_HZ * frame as f32 / SAMPLE_RATE as f32;
+
+    TONE_AMPLITUDE * envelope * phase.sin()
+}
@@ -0,0 +1,145 @@
+//! Mixing the start of the next track into the end of the playing one.
+
+use std::collections::VecDeque;
+
+use symphonia::core::audio::{AudioBuffer, AudioBufferRef, Signal, SignalSpec};
+
+use crate::dsp::FadeCurve;
+
+/// Fades the playing track out while the next one fades in, both along the same curve. With
+/// equal power curves the overlap doesn't dip in loudness halfway through.
+pub struct CrossfadeMixer {
+    spec: SignalSpec,
+    // Decoded audio of the incoming track that isn't mixed in yet, by channel.
+    incoming: Vec<VecDeque<f32>>,
+    mixed_frames: usize,
+    fade_frames: usize,
+    // Level of the incoming track relative to the outgoing one, for loudness normalization.
+    incoming_gain: f32,
+    curve: FadeCurve,
+}
+
+impl CrossfadeMixer {
+    pub fn new(spec: SignalSpec, fade_frames: usize, incoming_gain: f32, curve: FadeCurve) -> Self {
+        Self {
+            spec,
+            incoming: vec![VecDeque::new(); spec.channels.count()],
+            mixed_frames: 0,
+            fade_frames,
+            incoming_gain,
+            curve,
+        }
+    }
+
+    pub fn spec(&self) -> SignalSpec {
+        self.spec
+    }
+
+    /// Queues decoded audio of the incoming track, which must have the mixer's spec.
+    pub fn push(&mut self, decoded: &AudioBufferRef<'_>) {
+        let mut converted = decoded.make_equivalent::<f32>();
+        decoded.convert(&mut converted);
+        for (channel, queue) in self.incoming.iter_mut().enumerate() {
+            queue.extend(
+                converted
+                    .chan(channel)
+                    .iter()
+                    .map(|sample| sample * self.incoming_gain),
+            );
+        }
+    }
+
+    pub fn queued_frames(&self) -> usize {
+        self.incoming.first().map_or(0, VecDeque::len)
+    }
+
+    /// Fades `outgoing` out and mixes the queued incoming audio into it. Where the incoming
+    /// track runs short, the outgoing one fades out over silence.
+    pub fn mix(&mut self, outgoing: &mut AudioBuffer<f32>) {
+        for (channel, queue) in self.incoming.iter_mut().enumerate() {
+            for (frame, sample) in outgoing.chan_mut(channel).iter_mut().enumerate() {
+                let (out_gain, in_gain) =
+                    gains(self.mixed_frames + frame, self.fade_frames, self.curve);
+                *sample = *sample * out_gain + queue.pop_front().unwrap_or(0.0) * in_gain;
+            }
+        }
+
+        self.mixed_frames += outgoing.frames();
+    }
+
+    /// Everything still queued once the outgoing track ended. If it ended before the fade was
+    /// over, the rest of the fade in is applied.
+    pub fn take_rest(&mut self) -> AudioBuffer<f32> {
+        let frames = self.queued_frames();
+        let mut rest = AudioBuffer::new(frames as u64, self.spec);
+        rest.render_reserved(Some(frames));
+
+        for (channel, queue) in self.incoming.iter_mut().enumerate() {
+            let samples = rest.chan_mut(channel).iter_mut().zip(queue.drain(..));
+            for (frame, (sample, queued)) in samples.enumerate() {
+                *sample = queued * gains(self.mixed_frames + frame, self.fade_frames, self.curve).1;
+            }
+        }
+
+        self.mixed_frames += frames;
+        rest
+    }
+}
+
+// Gains of the outgoing and incoming track, `frame` frames into the fade.
+fn gains(frame: usize, fade_frames: usize, curve: FadeCurve) -> (f32, f32) {
+    let progress = if fade_frames == 0 {
+        1.0
+    } else {
+        frame as f32 / fade_frames as f32
+    };
+
+    (curve.gain(1.0 - progress), curve.gain(progress))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+    use symphonia::core::audio::Channels;
+
+    fn buffer(spec: SignalSpec, samples: &[f32]) -> AudioBuffer<f32> {
+        let mut buffer = AudioBuffer::new(samples.len() as u64, spec);
+        buffer.render_reserved(Some(samples.len()));
+        buffer.chan_mut(0).copy_from_slice(samples);
+        buffer
+    }
+
+    #[test]
+    fn overlap_keeps_constant_power() {
+        let spec = SignalSpec::new(48000, Channels::FRONT_LEFT);
+        let mut mixer = CrossfadeMixer::new(spec, 4, 1.0, FadeCurve::EqualPower);
+        mixer.push(&AudioBufferRef::F32(Cow::Owned(buffer(spec, &[1.0; 6]))));
+
+        let mut outgoing = buffer(spec, &[1.0; 4]);
+        mixer.mix(&mut outgoing);
+
+        assert_eq!(outgoing.chan(0)[0], 1.0);
+        // Uncorrelated tracks sum in power, so each is at -3dB halfway through.
+        let (out_gain, in_gain) = gains(2, 4, FadeCurve::EqualPower);
+        assert!((out_gain.powi(2) + in_gain.powi(2) - 1.0).abs() < 1e-6);
+        assert!((out_gain - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+
+        // The rest of the incoming track plays at full volume.
+        let rest = mixer.take_rest();
+        assert_eq!(rest.chan(0), &[1.0, 1.0]);
+        assert_eq!(mixer.queued_frames(), 0);
+    }
+    #[test]
+    fn linear_curve_crossfades_linearly() {
+        let spec = SignalSpec::new(48000, Channels::FRONT_LEFT);
+        let mut mixer = CrossfadeMixer::new(spec, 4, 1.0, FadeCurve::Linear);
+        mixer.push(&AudioBufferRef::F32(Cow::Owned(buffer(spec, &[1.0; 4]))));
+
+        // Silence going out shows the fade in alone.
+        let mut outgoing = buffer(spec, &[0.0; 4]);
+        mixer.mix(&mut outgoing);
+        assert_eq!(outgoing.chan(0), &[0.0, 0.25, 0.5, 0.75]);
+        assert_eq!(gains(1, 4, FadeCurve::Linear), (0.75, 0.25));
+    }
+}
@@ -0,0 +1,532 @@
+//! Processing applied to decoded audio on the way to the output.
+
+use crate::equalizer::Equalizer;
+use serde::{Deserialize, Serialize};
+
+/// Gain stages run over every interleaved block of samples before it is written to the
+/// output device. The equalizer and the night mode compressor work on the signal as decoded,
+/// then the track gain, volume, ducking, night mode ramp and balance are applied as one gain per channel,
+/// and the limiter catches whatever ends up too loud.
+pub struct SignalChain {
+    pub volume: f32,
+    /// Loudness normalization of the playing track, which may also boost it.
+    pub track_gain: f32,
+    /// -1.0 is hard left, 1.0 is hard right.
+    pub balance: f32,
+    pub pan_law: PanLaw,
+    /// Turned down while something else, like a preview, plays over it.
+    pub is_ducked: bool,
+    pub night_mode: NightMode,
+    pub fade_curve: FadeCurve,
+    pub limiter: Limiter,
+    pub equalizer: Equalizer,
+    // Gain of the night mode ramp, climbing back to 1.0 after playback starts.
+    ramp_gain: f32,
+    // How far along the current fade in is, from 0.0 to 1.0, and how long it takes.
+    fade_progress: f32,
+    fade_secs: f32,
+    compressor: Compressor,
+    // Gain the limiter currently holds the signal down by, 1.0 while it isn't limiting.
+    limiter_gain: f32,
+}
+
+// About -10dB, enough to hear a preview over the playing track.
+const DUCK_GAIN: f32 = 0.3;
+
+impl Default for SignalChain {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            track_gain: 1.0,
+            balance: 0.0,
+            pan_law: PanLaw::EqualPower,
+            is_ducked: false,
+            night_mode: NightMode::default(),
+            fade_curve: FadeCurve::Linear,
+            ramp_gain: 1.0,
+            fade_progress: 1.0,
+            fade_secs: OUTPUT_FADE_IN_SECS,
+            compressor: Compressor::default(),
+            limiter: Limiter::default(),
+            equalizer: Equalizer::default(),
+            limiter_gain: 1.0,
+        }
+    }
+}
+
+impl SignalChain {
+    /// Called when playback starts or resumes. Playback fades in, and with night mode on the
+    /// volume is also pulled down and ramps back up from there.
+    pub fn start_playback(&mut self) {
+        self.fade_in(PLAY_FADE_IN_SECS);
+        if self.night_mode.enabled && self.night_mode.ramp_secs > 0.0 {
+            self.ramp_gain = NIGHT_MODE_START_GAIN;
+        }
+    }
+
+    /// Fades in over the next `secs` seconds along `fade_curve`. Used when playback resumes or
+    /// a new output stream is opened, since a stream that starts on a non-zero sample clicks.
+    pub fn fade_in(&mut self, secs: f32) {
+        // A fade already under way, e.g. right after resuming, is restarted but not shortened.
+        self.fade_secs = if self.fade_progress < 1.0 {
+            self.fade_secs.max(secs)
+        } else {
+            secs
+        };
+        self.fade_progress = 0.0;
+    }
+
+    pub fn process(&mut self, samples: &mut [f32], channels: usize, sample_rate: u32) {
+        if channels == 0 {
+            return;
+        }
+
+        self.equalizer.process(samples, channels, sample_rate);
+
+        if self.night_mode.enabled && self.night_mode.compress {
+            self.compressor.process(samples, channels, sample_rate);
+        }
+
+        let volume = if self.is_ducked {
+            self.volume * DUCK_GAIN
+        } else {
+            self.volume
+        } * self.track_gain;
+
+        let mut channel_gains = vec![volume; channels];
+        if channels >= 2 {
+            let (left, right) = self.pan_law.gains(self.balance);
+            channel_gains[0] *= left;
+            channel_gains[1] *= right;
+        }
+
+        // Turning night mode off mid-ramp jumps straight to full volume.
+        if !self.night_mode.enabled {
+            self.ramp_gain = 1.0;
+        }
+        let ramp_step = if self.night_mode.ramp_secs > 0.0 && sample_rate > 0 {
+            (1.0 - NIGHT_MODE_START_GAIN) / (self.night_mode.ramp_secs * sample_rate as f32)
+        } else {
+            1.0
+        };
+        let fade_step = if self.fade_secs > 0.0 && sample_rate > 0 {
+            1.0 / (self.fade_secs * sample_rate as f32)
+        } else {
+            1.0
+        };
+
+        for frame in samples.chunks_exact_mut(channels) {
+            let fade_gain = self.fade_curve.gain(self.fade_progress);
+            for (sample, gain) in frame.iter_mut().zip(&channel_gains) {
+                *sample *= gain * self.ramp_gain * fade_gain;
+            }
+
+            if self.ramp_gain < 1.0 {
+                self.ramp_gain = (self.ramp_gain + ramp_step).min(1.0);
+            }
+            if self.fade_progress < 1.0 {
+                self.fade_progress = (self.fade_progress + fade_step).min(1.0);
+            }
+        }
+
+        // Rounding can leave the pan laws a hair above unity at the center.
+        let is_boosted =
+            channel_gains.iter().any(|gain| *gain > 1.001) || self.equalizer.max_gain_db() > 0.0;
+        if self.limiter.is_active(is_boosted) {
+            self.limit(samples, channels, sample_rate);
+        } else {
+            self.limiter_gain = 1.0;
+        }
+    }
+
+    /// Whether the limiter is holding the signal down right now.
+    pub fn is_limiting(&self) -> bool {
+        // Ignores the last few hundredths of a dB of the release.
+        self.limiter_gain < 0.999
+    }
+
+    // Brickwall peak limiter: the gain drops at once to keep every frame under the threshold,
+    // then recovers over the release time.
+    fn limit(&mut self, samples: &mut [f32], channels: usize, sample_rate: u32) {
+        let threshold = 10.0f32.powf(self.limiter.threshold_db.min(0.0) / 20.0);
+        let release = if self.limiter.release_ms > 0.0 && sample_rate > 0 {
+            (-1000.0 / (self.limiter.release_ms * sample_rate as f32)).exp()
+        } else {
+            0.0
+        };
+
+        for frame in samples.chunks_exact_mut(channels) {
+            self.limiter_gain = 1.0 - (1.0 - self.limiter_gain) * release;
+
+            let peak = frame
+                .iter()
+                .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+            if peak * self.limiter_gain > threshold {
+                self.limiter_gain = threshold / peak;
+            }
+
+            for sample in frame.iter_mut() {
+                *sample *= self.limiter_gain;
+            }
+        }
+    }
+}
+
+// Short enough not to be heard as a fade, long enough to smooth over the first sample.
+pub const OUTPUT_FADE_IN_SECS: f32 = 0.005;
+// Fade when pressing play or resuming.
+const PLAY_FADE_IN_SECS: f32 = 0.1;
+
+/// Shape of a fade, as gain over how far along the fade is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FadeCurve {
+    Linear,
+    /// Even steps in loudness, starting from -60dB.
+    Logarithmic,
+    /// Constant power when a fade in overlaps the matching fade out.
+    #[default]
+    EqualPower,
+    /// Eases in and out of the fade.
+    SCurve,
+}
+
+impl FadeCurve {
+    /// Fade in gain at `progress`, from 0.0 at the start to 1.0 at the end. A fade out is the
+    /// same curve run backwards.
+    pub fn gain(&self, progress: f32) -> f32 {
+        let progress = progress.clamp(0.0, 1.0);
+
+        match self {
+            FadeCurve::Linear => progress,
+            FadeCurve::Logarithmic if progress == 0.0 => 0.0,
+            FadeCurve::Logarithmic => 10.0f32.powf(3.0 * (progress - 1.0)),
+            FadeCurve::EqualPower => (progress * std::f32::consts::FRAC_PI_2).sin(),
+            FadeCurve::SCurve => 0.5 - 0.5 * (progress * std::f32::consts::PI).cos(),
+        }
+    }
+}
+
+impl std::fmt::Display for FadeCurve {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FadeCurve::Linear => write!(f, "Linear"),
+            FadeCurve::Logarithmic => write!(f, "Logarithmic"),
+            FadeCurve::EqualPower => write!(f, "Equal power"),
+            FadeCurve::SCurve => write!(f, "S-curve"),
+        }
+    }
+}
+
+/// Last stage of the chain, keeping peaks from going over the threshold and clipping.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Limiter {
+    pub mode: LimiterMode,
+    pub threshold_db: f32,
+    /// How long the gain takes to come back up after a peak.
+    pub release_ms: f32,
+}
+
+impl Default for Limiter {
+    fn default() -> Self {
+        Self {
+            mode: LimiterMode::Auto,
+            threshold_db: -0.3,
+            release_ms: 100.0,
+        }
+    }
+}
+
+impl Limiter {
+    /// Whether the limiter runs, given whether some stage of the chain raises the level.
+    fn is_active(&self, is_boosted: bool) -> bool {
+        match self.mode {
+            LimiterMode::Auto => is_boosted,
+            LimiterMode::On => true,
+            LimiterMode::Off => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LimiterMode {
+    /// Only while a stage boosts the signal, like an equalizer band turned up or the balance
+    /// pushing one channel above unity.
+    Auto,
+    On,
+    Off,
+}
+
+impl std::fmt::Display for LimiterMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LimiterMode::Auto => write!(f, "When boosting"),
+            LimiterMode::On => write!(f, "Always"),
+            LimiterMode::Off => write!(f, "Off"),
+        }
+    }
+}
+
+// Where the night mode ramp starts, about -20dB.
+const NIGHT_MODE_START_GAIN: f32 = 0.1;
+
+/// Gentler playback for late-night listening.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NightMode {
+    pub enabled: bool,
+    /// How long playback takes to come back up to full volume after starting or resuming.
+    pub ramp_secs: f32,
+    /// Compress loud peaks so quiet and loud passages end up closer together.
+    pub compress: bool,
+}
+
+impl Default for NightMode {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ramp_secs: 5.0,
+            compress: true,
+        }
+    }
+}
+
+// Feed-forward peak compressor with the channels linked, so the stereo image doesn't shift.
+#[derive(Default)]
+struct Compressor {
+    // Smoothed peak level, linear.
+    envelope: f32,
+}
+
+const COMPRESSOR_THRESHOLD_DB: f32 = -20.0;
+const COMPRESSOR_RATIO: f32 = 4.0;
+const COMPRESSOR_ATTACK_SECS: f32 = 0.01;
+const COMPRESSOR_RELEASE_SECS: f32 = 0.2;
+
+impl Compressor {
+    fn process(&mut self, samples: &mut [f32], channels: usize, sample_rate: u32) {
+        if sample_rate == 0 {
+            return;
+        }
+
+        let attack = (-1.0 / (COMPRESSOR_ATTACK_SECS * sample_rate as f32)).exp();
+        let release = (-1.0 / (COMPRESSOR_RELEASE_SECS * sample_rate as f32)).exp();
+
+        for frame in samples.chunks_exact_mut(channels) {
+            let peak = frame
+                .iter()
+                .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+            let coefficient = if peak > self.envelope {
+                attack
+            } else {
+                release
+            };
+            self.envelope = coefficient * self.envelope + (1.0 - coefficient) * peak;
+
+            let level_db = 20.0 * self.envelope.max(1e-6).log10();
+            if level_db > COMPRESSOR_THRESHOLD_DB {
+                let reduction_db =
+                    (COMPRESSOR_THRESHOLD_DB - level_db) * (1.0 - 1.0 / COMPRESSOR_RATIO);
+                let gain = 10.0f32.powf(reduction_db / 20.0);
+                for sample in frame.iter_mut() {
+                    *sample *= gain;
+                }
+            }
+        }
+    }
+}
+
+/// How the left/right gains follow the balance control. All laws are unity on both channels
+/// at the center position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PanLaw {
+    /// Classic balance: the far channel is turned down, the near one is left alone.
+    Linear,
+    /// -3dB law. Keeps the summed power constant, so perceived loudness doesn't change.
+    EqualPower,
+    /// -6dB law. Keeps the summed amplitude constant.
+    Minus6Db,
+}
+
+impl PanLaw {
+    pub fn gains(&self, balance: f32) -> (f32, f32) {
+        let balance = balance.clamp(-1.0, 1.0);
+
+        match self {
+            PanLaw::Linear => (1.0 - balance.max(0.0), 1.0 + balance.min(0.0)),
+            PanLaw::EqualPower => {
+                let angle = (balance + 1.0) * std::f32::consts::FRAC_PI_4;
+                (
+                    std::f32::consts::SQRT_2 * angle.cos(),
+                    std::f32::consts::SQRT_2 * angle.sin(),
+                )
+            }
+            PanLaw::Minus6Db => (1.0 - balance, 1.0 + balance),
+        }
+    }
+}
+
+impl std::fmt::Display for PanLaw {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PanLaw::Linear => write!(f, "Linear (0dB)"),
+            PanLaw::EqualPower => write!(f, "Equal power (-3dB)"),
+            PanLaw::Minus6Db => write!(f, "Linear sum (-6dB)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pan_laws_are_unity_at_center() {
+        for law in [PanLaw::Linear, PanLaw::EqualPower, PanLaw::Minus6Db] {
+            let (left, right) = law.gains(0.0);
+            assert!((left - 1.0).abs() < 1e-6, "{law}");
+            assert!((right - 1.0).abs() < 1e-6, "{law}");
+        }
+    }
+
+    #[test]
+    fn equal_power_keeps_power_constant() {
+        for balance in [-1.0, -0.5, 0.0, 0.3, 1.0] {
+            let (left, right) = PanLaw::EqualPower.gains(balance);
+            assert!((left * left + right * right - 2.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn hard_pan_silences_far_channel() {
+        let mut chain = SignalChain {
+            balance: 1.0,
+            ..Default::default()
+        };
+        let mut samples = [0.5, 0.5, 0.5, 0.5];
+
+        chain.process(&mut samples, 2, 44100);
+
+        assert!(samples[0].abs() < 1e-6);
+        assert!(samples[2].abs() < 1e-6);
+    }
+
+    #[test]
+    fn night_mode_ramps_up_after_starting() {
+        let mut chain = SignalChain {
+            night_mode: NightMode {
+                enabled: true,
+                ramp_secs: 1.0,
+                compress: false,
+            },
+            ..Default::default()
+        };
+        chain.start_playback();
+
+        // One second of mono audio at 100Hz. The play fade in is over after the first 10 samples.
+        let mut samples = [0.1; 100];
+        chain.process(&mut samples, 1, 100);
+        let ramp_step = (1.0 - NIGHT_MODE_START_GAIN) / 100.0;
+        assert!((samples[10] - 0.1 * (NIGHT_MODE_START_GAIN + 10.0 * ramp_step)).abs() < 1e-6);
+        assert!(samples[10] < samples[50] && samples[50] < samples[99]);
+
+        let mut samples = [0.1; 10];
+        chain.process(&mut samples, 1, 100);
+        assert!((samples[9] - 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn limiter_keeps_boosted_peaks_under_the_threshold() {
+        let mut chain = SignalChain {
+            balance: 1.0,
+            pan_law: PanLaw::Minus6Db,
+            ..Default::default()
+        };
+
+        // Full scale stereo, which the -6dB law doubles on the right.
+        let mut samples = [1.0, -1.0].repeat(100);
+        chain.process(&mut samples, 2, 1000);
+
+        let threshold = 10.0f32.powf(chain.limiter.threshold_db / 20.0);
+        assert!(samples.iter().all(|sample| sample.abs() <= threshold));
+        assert!(chain.is_limiting());
+
+        // Nothing boosts at the center, so it steps aside.
+        chain.balance = 0.0;
+        let mut samples = [0.5; 20];
+        chain.process(&mut samples, 2, 1000);
+        assert!((samples[19] - 0.5).abs() < 1e-6);
+        assert!(!chain.is_limiting());
+    }
+
+    #[test]
+    fn limiter_catches_equalizer_boosts() {
+        let mut chain = SignalChain::default();
+        assert_eq!(chain.limiter.mode, LimiterMode::Auto);
+        chain
+            .equalizer
+            .set_band_gain(5, crate::equalizer::MAX_GAIN_DB);
+
+        // A second of a full scale sine right on the boosted band.
+        let sample_rate = 44100;
+        let frequency = crate::equalizer::BAND_HZ[5];
+        let mut samples = (0..sample_rate)
+            .map(|idx| {
+                (2.0 * std::f32::consts::PI * frequency * idx as f32 / sample_rate as f32).sin()
+            })
+            .collect::<Vec<_>>();
+        chain.process(&mut samples, 1, sample_rate);
+
+        assert!(samples.iter().all(|sample| sample.abs() <= 1.0));
+        assert!(chain.is_limiting());
+    }
+
+    #[test]
+    fn new_output_is_faded_in() {
+        let mut chain = SignalChain::default();
+        chain.fade_in(OUTPUT_FADE_IN_SECS);
+
+        // 10ms of stereo audio at 1kHz, the fade is over after 5 frames.
+        let mut samples = [0.5; 20];
+        chain.process(&mut samples, 2, 1000);
+        assert_eq!(samples[0], 0.0);
+        assert_eq!(samples[0], samples[1]);
+        assert!(samples[2] > 0.0 && samples[2] < samples[4]);
+        assert!(samples[10..]
+            .iter()
+            .all(|sample| (*sample - 0.5).abs() < 1e-6));
+    }
+
+    #[test]
+    fn fade_curves_run_from_silence_to_unity() {
+        for curve in [
+            FadeCurve::Linear,
+            FadeCurve::Logarithmic,
+            FadeCurve::EqualPower,
+            FadeCurve::SCurve,
+        ] {
+            assert_eq!(curve.gain(0.0), 0.0, "{curve}");
+            assert!((curve.gain(1.0) - 1.0).abs() < 1e-6, "{curve}");
+            assert!(curve.gain(0.25) < curve.gain(0.5), "{curve}");
+            assert!(curve.gain(0.5) < curve.gain(0.75), "{curve}");
+        }
+
+        // Fading one track out while the next fades in keeps the power constant.
+        let fading_in = FadeCurve::EqualPower.gain(0.3);
+        let fading_out = FadeCurve::EqualPower.gain(0.7);
+        assert!((fading_in * fading_in + fading_out * fading_out - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn compressor_only_tames_loud_passages() {
+        let mut quiet = [0.05; 4410];
+        Compressor::default().process(&mut quiet, 1, 44100);
+        assert!(quiet.iter().all(|sample| (*sample - 0.05).abs() < 1e-6));
+
+        let mut loud = [1.0; 4410];
+        Compressor::default().process(&mut loud, 1, 44100);
+        // 0dBFS is 20dB over the threshold, which a 4:1 ratio brings down to 5dB over.
+        let settled_db = 20.0 * loud[4409].log10();
+        assert!((settled_db - -15.0).abs() < 0.5, "{settled_db}");
+    }
+}
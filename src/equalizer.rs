@@ -0,0 +1,174 @@
+//! A ten band graphic equalizer, run as part of the signal chain.
+
+/// Center frequency of each band in Hz, an octave apart.
+pub const BAND_HZ: [f32; BAND_COUNT] = [
+    31.0, 62.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0,
+];
+pub const BAND_COUNT: usize = 10;
+/// Bands boost or cut by at most this much.
+pub const MAX_GAIN_DB: f32 = 12.0;
+
+// About an octave wide, so neighbouring bands blend into a smooth curve.
+const Q: f32 = std::f32::consts::SQRT_2;
+// Bands this close to the Nyquist frequency can't be filtered and are left out.
+const MAX_BAND_RATIO: f32 = 0.45;
+
+/// Peaking filters, one per band, run one after another over each channel.
+pub struct Equalizer {
+    gains_db: [f32; BAND_COUNT],
+    sample_rate: u32,
+    // `None` for bands left flat, which are skipped.
+    filters: [Option<Biquad>; BAND_COUNT],
+    // Filter history by channel, then band.
+    states: Vec<[BiquadState; BAND_COUNT]>,
+}
+
+impl Default for Equalizer {
+    fn default() -> Self {
+        Self {
+            gains_db: [0.0; BAND_COUNT],
+            sample_rate: 0,
+            filters: [None; BAND_COUNT],
+            states: Vec::new(),
+        }
+    }
+}
+
+impl Equalizer {
+    pub fn set_band_gain(&mut self, band: usize, db: f32) {
+        let Some(gain_db) = self.gains_db.get_mut(band) else {
+            return;
+        };
+
+        *gain_db = db.clamp(-MAX_GAIN_DB, MAX_GAIN_DB);
+        self.filters[band] = Biquad::peaking(BAND_HZ[band], *gain_db, self.sample_rate);
+    }
+
+    /// Gain of the most boosted band, 0.0 or below when nothing is boosted.
+    pub fn max_gain_db(&self) -> f32 {
+        self.gains_db.iter().copied().fold(f32::MIN, f32::max)
+    }
+
+    /// Filters interleaved `samples` in place.
+    pub fn process(&mut self, samples: &mut [f32], channels: usize, sample_rate: u32) {
+        if channels == 0 {
+            return;
+        }
+
+        // The filters are designed for the rate they run at, a new output may have another.
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            for ((filter, center_hz), gain_db) in
+                self.filters.iter_mut().zip(BAND_HZ).zip(self.gains_db)
+            {
+                *filter = Biquad::peaking(center_hz, gain_db, sample_rate);
+            }
+            self.states.clear();
+        }
+        if self.filters.iter().all(Option::is_none) {
+            return;
+        }
+        self.states
+            .resize(channels, [BiquadState::default(); BAND_COUNT]);
+
+        for frame in samples.chunks_exact_mut(channels) {
+            for (sample, states) in frame.iter_mut().zip(&mut self.states) {
+                for (filter, state) in self.filters.iter().zip(states.iter_mut()) {
+                    if let Some(filter) = filter {
+                        *sample = filter.process(*sample, state);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Normalized coefficients of a second order filter.
+#[derive(Debug, Clone, Copy)]
+pub struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+/// What a `Biquad` remembers of the samples it filtered, one per channel.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BiquadState {
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    /// From the feedforward coefficients `b` and the feedback coefficients `a`.
+    pub fn new(b: [f64; 3], a: [f64; 3]) -> Self {
+        Self {
+            b0: (b[0] / a[0]) as f32,
+            b1: (b[1] / a[0]) as f32,
+            b2: (b[2] / a[0]) as f32,
+            a1: (a[1] / a[0]) as f32,
+            a2: (a[2] / a[0]) as f32,
+        }
+    }
+
+    // Peaking EQ from the Audio EQ Cookbook. `None` when the filter would do nothing.
+    fn peaking(center_hz: f32, gain_db: f32, sample_rate: u32) -> Option<Self> {
+        if gain_db == 0.0 || center_hz >= sample_rate as f32 * MAX_BAND_RATIO {
+            return None;
+        }
+
+        let a = 10.0f32.powf(gain_db / 40.0);
+        let w0 = 2.0 * std::f32::consts::PI * center_hz / sample_rate as f32;
+        let alpha = w0.sin() / (2.0 * Q);
+        let a0 = 1.0 + alpha / a;
+
+        Some(Self {
+            b0: (1.0 + alpha * a) / a0,
+            b1: -2.0 * w0.cos() / a0,
+            b2: (1.0 - alpha * a) / a0,
+            a1: -2.0 * w0.cos() / a0,
+            a2: (1.0 - alpha / a) / a0,
+        })
+    }
+
+    /// Filters one sample, in transposed direct form II.
+    pub fn process(&self, input: f32, state: &mut BiquadState) -> f32 {
+        let output = self.b0 * input + state.z1;
+        state.z1 = self.b1 * input - self.a1 * output + state.z2;
+        state.z2 = self.b2 * input - self.a2 * output;
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Peak level of a sine at `hz` after it's settled through the equalizer.
+    fn level_db(equalizer: &mut Equalizer, hz: f32) -> f32 {
+        let sample_rate = 48000;
+        let mut samples = (0..sample_rate)
+            .map(|n| (2.0 * std::f32::consts::PI * hz * n as f32 / sample_rate as f32).sin())
+            .collect::<Vec<_>>();
+        equalizer.process(&mut samples, 1, sample_rate);
+
+        let peak = samples[samples.len() / 2..]
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        20.0 * peak.log10()
+    }
+
+    #[test]
+    fn boosts_its_band_and_leaves_the_rest() {
+        let mut equalizer = Equalizer::default();
+        assert!(level_db(&mut equalizer, 1000.0).abs() < 0.01);
+
+        equalizer.set_band_gain(5, 6.0);
+        assert!((level_db(&mut equalizer, 1000.0) - 6.0).abs() < 0.1);
+        assert!(level_db(&mut equalizer, 100.0).abs() < 0.5);
+
+        equalizer.set_band_gain(5, 40.0);
+        assert_eq!(equalizer.gains_db[5], MAX_GAIN_DB);
+    }
+}
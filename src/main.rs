@@ -3,24 +3,54 @@ pub use crate::app::scope::Scope;
 pub use crate::app::App;
 pub use crate::app::*;
 
-use std::path::PathBuf;
-use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
-use std::sync::mpsc::{channel, Receiver};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::Arc;
 use std::thread;
 
+use app::observer::{NowPlayingFile, PlaybackEvent};
+use app::settings::{AudioThreadPriority, NetworkRetry, SeekPrecision};
+use crossfade::CrossfadeMixer;
+use dsp::{FadeCurve, SignalChain};
 use eframe::egui;
 use rb::*;
+use symphonia::core::audio::{AsAudioBufferRef, AudioBuffer, AudioBufferRef, Signal, SignalSpec};
 use symphonia::core::codecs::{DecoderOptions, FinalizeResult, CODEC_TYPE_NULL};
 use symphonia::core::errors::{Error, Result};
 use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo, Track};
 use symphonia::core::io::MediaSourceStream;
-use symphonia::core::meta::MetadataOptions;
+use symphonia::core::meta::{MetadataOptions, StandardTagKey};
 use symphonia::core::probe::Hint;
+use symphonia::core::units::{Time, TimeBase};
 
 mod app;
+mod audio_check;
+mod crossfade;
+mod dsp;
+mod equalizer;
+mod media_controls;
 mod output;
+mod preview;
 mod resampler;
+mod taskbar;
+mod tray;
+
+// Upper bound on how long the end of a track may take to play out before moving on.
+const OUTPUT_DRAIN_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(500);
+
+// Base delay before opening a track that timed out is tried again, multiplied by the attempt.
+const OPEN_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+
+// How long the audio thread may go without making progress before the watchdog steps in.
+const WATCHDOG_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+// How long the audio thread sleeps waiting for a command while there's nothing to play. Well
+// under `WATCHDOG_TIMEOUT`, so the heartbeat stays fresh.
+const IDLE_WAIT: std::time::Duration = std::time::Duration::from_secs(1);
+
+// Waiting on the source, rather than playing, once no audio was decoded for this long.
+const BUFFERING_AFTER: std::time::Duration = std::time::Duration::from_millis(500);
 
 fn main() {
     tracing_subscriber::fmt::init();
@@ -32,49 +62,143 @@ fn main() {
     let cursor = Arc::new(AtomicU32::new(0));
     let player = Player::new(audio_tx, ui_rx, cursor);
 
-    // Create a ring buffer with a capacity for up-to 200ms of audio.
-    // let ring_len = ((2 * config.sample_rate.0 as usize) / 1000) * num_channels;
-    let ring_len: usize = 4096;
+    // App setup
+    let is_processing_ui_change = Arc::new(AtomicBool::new(false));
+    let mut app = App::load().unwrap_or_default();
 
+    // Ring buffer feeding the scope, holding one scope window of audio. It starts out sized for
+    // 48kHz stereo and is reallocated by the audio thread once the real output config is known.
+    let ring_len = scope::window_len(48000, 2, app.settings.scope_window_ms);
     let gui_ring_buf = SpscRb::new(ring_len);
-    let (gui_ring_buf_producer, gui_ring_buf_consumer) =
+    let (mut gui_ring_buf_producer, gui_ring_buf_consumer) =
         (gui_ring_buf.producer(), gui_ring_buf.consumer());
 
-    // App setup
-    let is_processing_ui_change = Arc::new(AtomicBool::new(false));
-    let mut app = App::load().unwrap_or_default();
-    app.scope = Some(Scope::new());
-    app.temp_buf = Some(vec![0.0f32; 48000]);
+    app.scope = Some(Scope::with_len(ring_len / 2));
+    app.temp_buf = Some(vec![0.0f32; ring_len]);
     app.player = Some(player);
     app.library_cmd_tx = Some(lib_cmd_tx);
     app.library_cmd_rx = Some(lib_cmd_rx);
     app.played_audio_buffer = Some(gui_ring_buf_consumer);
     app.is_processing_ui_change = Some(is_processing_ui_change.clone());
+    app.apply_settings(app.settings.clone());
+    app.register_observer(Box::new(NowPlayingFile::default()));
+    app.media_controls = media_controls::MediaControls::start();
+    app.resume_session();
+    // Files and folders passed in, e.g. by the file manager with the app as the default player.
+    // They take over from the resumed session.
+    app.open_paths(std::env::args_os().skip(1).map(PathBuf::from).collect());
+
+    // Watchdog setup. The audio thread bumps the heartbeat every loop iteration; if it stops
+    // (e.g. blocked writing to a dead output stream) the UI is told playback stalled.
+    let watchdog_epoch = std::time::Instant::now();
+    let heartbeat = Arc::new(AtomicU64::new(0));
+    // Last `PlaybackStatus` sent to the UI, by the audio thread or the watchdog.
+    let reported_status = Arc::new(AtomicU8::new(PlaybackStatus::Stopped as u8));
+    {
+        let heartbeat = heartbeat.clone();
+        let reported_status = reported_status.clone();
+        let ui_tx = ui_tx.clone();
+        thread::spawn(move || {
+            let mut has_reported = false;
+            loop {
+                thread::sleep(std::time::Duration::from_millis(250));
+
+                let last_beat = std::time::Duration::from_millis(heartbeat.load(Ordering::Relaxed));
+                let silent_for = watchdog_epoch.elapsed().saturating_sub(last_beat);
+                let is_stalled = silent_for > WATCHDOG_TIMEOUT;
+
+                // The audio thread can't say so itself while a read from a slow source blocks it.
+                if silent_for > BUFFERING_AFTER
+                    && reported_status
+                        .compare_exchange(
+                            PlaybackStatus::Playing as u8,
+                            PlaybackStatus::Buffering as u8,
+                            Ordering::AcqRel,
+                            Ordering::Acquire,
+                        )
+                        .is_ok()
+                    && ui_tx
+                        .send(UiCommand::PlaybackStatus(PlaybackStatus::Buffering))
+                        .is_err()
+                {
+                    break;
+                }
+
+                if is_stalled && !has_reported {
+                    tracing::error!("Audio thread hasn't responded for {:?}", WATCHDOG_TIMEOUT);
+                    if ui_tx.send(UiCommand::PlaybackStalled).is_err() {
+                        break;
+                    }
+                }
+                has_reported = is_stalled;
+            }
+        });
+    }
 
     // Audio output setup
     let _audio_thread = thread::spawn(move || {
         let mut state = PlayerState::Unstarted;
 
-        let mut audio_engine_state = AudioEngineState {
-            reader: None,
-            audio_output: None,
-            track_num: None,
-            seek: None,
-            decode_opts: None,
-            track_info: None,
-            duration: 0,
-        };
+        let mut audio_engine_state = AudioEngineState::default();
 
         let mut decoder: Option<Box<dyn symphonia::core::codecs::Decoder>> = None;
-        let mut volume = 1.0;
         let mut current_track_path: Option<PathBuf> = None;
-        let mut timer = std::time::Instant::now();
+        // Last time a packet was decoded, or playback was (re)started.
+        let mut last_progress = std::time::Instant::now();
 
         loop {
-            process_audio_cmd(&audio_rx, &mut state, &mut volume, &is_processing_ui_change);
+            heartbeat.store(
+                watchdog_epoch.elapsed().as_millis() as u64,
+                Ordering::Relaxed,
+            );
+
+            // The thread is alive but may still be spinning without producing audio, e.g. a
+            // pathological file where every packet gets skipped.
+            if state != PlayerState::Playing {
+                last_progress = std::time::Instant::now();
+            } else if last_progress.elapsed() > WATCHDOG_TIMEOUT {
+                tracing::error!(
+                    "No audio decoded for {:?} while playing {:?}, stopping",
+                    WATCHDOG_TIMEOUT,
+                    current_track_path
+                );
+                state = PlayerState::Stopped;
+                audio_engine_state.has_failed = true;
+                ui_tx
+                    .send(UiCommand::PlaybackStalled)
+                    .expect("Failed to send stall to ui thread");
+            }
+
+            let status = playback_status(&state, &audio_engine_state, last_progress);
+            if reported_status.swap(status as u8, Ordering::AcqRel) != status as u8 {
+                ui_tx
+                    .send(UiCommand::PlaybackStatus(status))
+                    .expect("Failed to send playback status to ui thread");
+            }
+
+            // Stopping with a track loaded still has to rewind it, so it doesn't wait.
+            let is_idle = match state {
+                PlayerState::Paused | PlayerState::Unstarted => true,
+                PlayerState::Stopped => current_track_path.is_none(),
+                _ => false,
+            };
+            process_audio_cmd(
+                &audio_rx,
+                is_idle.then_some(IDLE_WAIT),
+                &mut state,
+                &mut audio_engine_state,
+                &is_processing_ui_change,
+                &ui_tx,
+            );
 
             match state {
                 PlayerState::Playing => {
+                    // Nothing to play, e.g. the selected track failed to load.
+                    if audio_engine_state.reader.is_none() || decoder.is_none() {
+                        state = PlayerState::Unstarted;
+                        continue;
+                    }
+
                     // decode the next packet.
                     let result: std::result::Result<(), symphonia::core::errors::Error> = 'once: {
                         if state != PlayerState::Playing {
@@ -90,13 +214,133 @@ fn main() {
                             Ok(packet) => packet,
                             Err(err) => {
                                 tracing::warn!("couldn't decode next packet");
+                                // A truncated file ends with some other IO error, treat it like
+                                // the end of the stream.
+                                if let Err(err) = ignore_end_of_stream_error(Err(err)) {
+                                    tracing::warn!("track ended early: {}", err);
+                                }
+
+                                // A crossfade already has the next track going, it takes over
+                                // where the mix left off.
+                                if let Some(mut crossfade) = audio_engine_state.crossfade.take() {
+                                    let rest = crossfade.mixer.take_rest();
+                                    if let Some(audio_output) =
+                                        audio_engine_state.audio_output.as_mut()
+                                    {
+                                        audio_output
+                                            .write(
+                                                rest.as_audio_buffer_ref(),
+                                                &gui_ring_buf_producer,
+                                                &mut audio_engine_state.signal_chain,
+                                            )
+                                            .unwrap();
+                                    }
+
+                                    swap_loaded_track(
+                                        &mut audio_engine_state,
+                                        &mut decoder,
+                                        &mut crossfade.track,
+                                    );
+                                    audio_engine_state.signal_chain.track_gain = crossfade.gain;
+                                    tracing::info!("Crossfaded into {:?}", crossfade.path);
+                                    send_continued(
+                                        &ui_tx,
+                                        &mut current_track_path,
+                                        crossfade.path,
+                                        &audio_engine_state,
+                                    );
+                                    audio_engine_state.has_decoded_audio = true;
+                                    audio_engine_state.is_stream_info_stale = true;
+                                    break 'once Ok(());
+                                }
+
+                                // For gapless playback the next track goes into the same output
+                                // while it still plays the end of this one.
+                                let next_track =
+                                    audio_engine_state.next_track.take().filter(|_| {
+                                        audio_engine_state.reader_options.gapless
+                                            && audio_engine_state.track_gap.is_zero()
+                                            && audio_engine_state.has_decoded_audio
+                                    });
+                                if let Some(next_track) = next_track {
+                                    let timeout = std::time::Duration::from_secs(
+                                        audio_engine_state.network_retry.timeout_secs,
+                                    );
+                                    let result = wait_for_file(&next_track.file, timeout)
+                                        .map_err(Error::IoError)
+                                        .and_then(|file| {
+                                            load_opened_file(
+                                                file,
+                                                &mut audio_engine_state,
+                                                &mut decoder,
+                                                0,
+                                            )
+                                        });
+
+                                    // Otherwise the UI moves on as usual, and finds out the next
+                                    // track doesn't play when it loads it.
+                                    if result.is_ok() {
+                                        tracing::info!(
+                                            "Continuing gaplessly with {:?}",
+                                            next_track.path
+                                        );
+                                        audio_engine_state.signal_chain.track_gain =
+                                            next_track.gain;
+                                        send_continued(
+                                            &ui_tx,
+                                            &mut current_track_path,
+                                            next_track.path,
+                                            &audio_engine_state,
+                                        );
+                                        audio_engine_state.has_decoded_audio = false;
+                                        audio_engine_state.is_stream_info_stale = true;
+                                        break 'once Ok(());
+                                    }
+                                }
+
+                                // Let the output play out the samples already written so the end
+                                // of the track isn't clipped when the next one is loaded.
+                                if let Some(audio_output) = audio_engine_state.audio_output.as_mut()
+                                {
+                                    audio_output.drain(OUTPUT_DRAIN_TIMEOUT);
+                                }
+
                                 // Track is over.. update the state to stopped and send message to
-                                // UI to play next track
-                                state = PlayerState::Stopped;
-                                ui_tx
-                                    .send(UiCommand::AudioFinished)
-                                    .expect("Failed to send play to ui thread");
-                                break 'once Err(err);
+                                // UI to play next track. A track that ends before any audio was
+                                // decoded (e.g. a header-only file) is reported as failed instead.
+                                if !audio_engine_state.has_decoded_audio {
+                                    state = PlayerState::Stopped;
+                                    ui_tx
+                                        .send(UiCommand::TrackFailed(
+                                            current_track_path.clone().unwrap_or_default(),
+                                        ))
+                                        .expect("Failed to send play to ui thread");
+                                } else if audio_engine_state.track_gap.is_zero() {
+                                    send_playback(
+                                        &ui_tx,
+                                        PlaybackEvent::Ended(
+                                            current_track_path.clone().unwrap_or_default(),
+                                        ),
+                                    );
+                                    state = PlayerState::Stopped;
+                                    ui_tx
+                                        .send(UiCommand::AudioFinished)
+                                        .expect("Failed to send play to ui thread");
+                                } else {
+                                    // Hold off on asking for the next track. The UI only hears
+                                    // about it once the gap is over.
+                                    send_playback(
+                                        &ui_tx,
+                                        PlaybackEvent::Ended(
+                                            current_track_path.clone().unwrap_or_default(),
+                                        ),
+                                    );
+                                    state = PlayerState::TrackGap {
+                                        until: std::time::Instant::now()
+                                            + audio_engine_state.track_gap,
+                                    };
+                                }
+                                break 'once Ok(());
                             }
                         };
 
@@ -106,43 +350,197 @@ fn main() {
                             break 'once Ok(());
                         }
 
-                        if timer.elapsed() > std::time::Duration::from_millis(500) {
-                            // Sending the timestamp every possible read spams the UI queue.
-                            // We only need to send this data twice a second or so...
+                        audio_engine_state.position = packet.ts();
+
+                        // Sending the timestamp for every packet spams the UI queue. The elapsed
+                        // time is shown in whole seconds, so it's only sent when those change.
+                        // Without a time base they can't be told apart and every packet is sent.
+                        let secs = audio_engine_state
+                            .time_base
+                            .map(|time_base| time_base.calc_time(packet.ts()).seconds);
+                        if secs.is_none() || secs != audio_engine_state.sent_secs {
+                            audio_engine_state.sent_secs = secs;
                             ui_tx
                                 .send(UiCommand::CurrentTimestamp(packet.ts))
                                 .expect("Failed to send play to ui thread");
-
-                            timer = std::time::Instant::now();
                         }
 
                         // Decode the packet into audio samples.
                         match decoder.as_mut().unwrap().decode(&packet) {
                             Ok(decoded) => {
+                                audio_engine_state.has_decoded_audio = true;
+                                last_progress = std::time::Instant::now();
+
+                                // Get the audio buffer specification. This is a description of the decoded
+                                // audio buffer's sample format and sample rate.
+                                let spec = *decoded.spec();
+
+                                // Get the capacity of the decoded buffer. Note that this is capacity, not
+                                // length! The capacity of the decoded buffer is constant for the life of the
+                                // decoder, but the length is not.
+                                let duration = decoded.capacity() as u64;
+
+                                // Some streams (e.g. chained Ogg or MP3s glued together) change spec
+                                // partway through. The open output converts for the spec it was opened
+                                // with, so reopen it instead of writing garbled audio.
+                                if needs_reopen(
+                                    audio_engine_state.output_spec.as_ref(),
+                                    spec,
+                                    duration,
+                                ) {
+                                    if let Some(mut stale_output) = audio_output.take() {
+                                        let stale_spec = audio_engine_state
+                                            .output_spec
+                                            .map_or(spec, |output_spec| output_spec.spec);
+                                        tracing::warn!(
+                                            "Stream changed from {} Hz, {} ch to {} Hz, {} ch, reopening the output",
+                                            stale_spec.rate,
+                                            stale_spec.channels.count(),
+                                            spec.rate,
+                                            spec.channels.count()
+                                        );
+                                        // Plays out what the old output still holds, including
+                                        // what's left in its resampler.
+                                        stale_output.drain(OUTPUT_DRAIN_TIMEOUT);
+                                    }
+                                }
+
                                 // If the audio output is not open, try to open it.
                                 if audio_output.is_none() {
-                                    // Get the audio buffer specification. This is a description of the decoded
-                                    // audio buffer's sample format and sample rate.
-                                    let spec = *decoded.spec();
-
-                                    // Get the capacity of the decoded buffer. Note that this is capacity, not
-                                    // length! The capacity of the decoded buffer is constant for the life of the
-                                    // decoder, but the length is not.
-                                    let duration = decoded.capacity() as u64;
+                                    // Try to open the audio output. Without one the track can't
+                                    // play, and the UI skips it like any other that fails.
+                                    let opened = match output::try_open(
+                                        spec,
+                                        duration,
+                                        audio_engine_state.output_host.as_deref(),
+                                        audio_engine_state.output_device.as_deref(),
+                                    ) {
+                                        Ok(opened) => opened,
+                                        Err(err) => {
+                                            tracing::error!(
+                                                "Couldn't open the audio output: {:?}",
+                                                err
+                                            );
+                                            break 'once Err(Error::IoError(
+                                                std::io::Error::other(
+                                                    "no audio output could be opened",
+                                                ),
+                                            ));
+                                        }
+                                    };
+                                    audio_output.replace(opened);
+                                    audio_engine_state.output_spec =
+                                        Some(OutputSpec { spec, duration });
+                                    audio_engine_state.is_stream_info_stale = true;
+                                    // A stream that starts mid-waveform clicks, e.g. after a seek.
+                                    audio_engine_state
+                                        .signal_chain
+                                        .fade_in(dsp::OUTPUT_FADE_IN_SECS);
+
+                                    if let Some(audio_output) = audio_output.as_ref() {
+                                        ui_tx
+                                            .send(UiCommand::OutputHost(
+                                                audio_output.host_name().to_string(),
+                                            ))
+                                            .expect("Failed to send output host to ui thread");
+                                        ui_tx
+                                            .send(UiCommand::OutputDevice(
+                                                audio_output.device_name().to_string(),
+                                            ))
+                                            .expect("Failed to send output device to ui thread");
+                                    }
+                                }
 
-                                    // Try to open the audio output.
-                                    audio_output.replace(output::try_open(spec, duration).unwrap());
-                                } else {
-                                    // TODO: Check the audio spec. and duration hasn't changed.
+                                // Also sent for a track continued into an output that was
+                                // already open.
+                                if audio_engine_state.is_stream_info_stale {
+                                    if let Some(audio_output) = audio_output.as_ref() {
+                                        let (output_rate, output_channels) =
+                                            audio_output.device_config();
+                                        // Some containers don't declare a time base, their
+                                        // timestamps count samples.
+                                        let time_base = *audio_engine_state
+                                            .time_base
+                                            .get_or_insert(TimeBase::new(1, spec.rate));
+                                        ui_tx
+                                            .send(UiCommand::StreamInfo(StreamInfo {
+                                                source: spec,
+                                                time_base,
+                                                output_rate,
+                                                output_channels,
+                                            }))
+                                            .expect("Failed to send stream info to ui thread");
+                                    }
+                                    audio_engine_state.is_stream_info_stale = false;
                                 }
 
-                                // Write the decoded audio samples to the audio output if the presentation timestamp
-                                // for the packet is >= the seeked position (0 if not seeking).
-                                if packet.ts() >= play_opts.seek_ts {
+                                // Write the decoded audio samples to the audio output, starting at the exact
+                                // sample seeked to (0 if not seeking).
+                                let skipped_frames = frames_before_seek(
+                                    packet.ts(),
+                                    play_opts.seek_ts,
+                                    audio_engine_state.time_base,
+                                    spec.rate,
+                                );
+                                if skipped_frames < decoded.frames() {
+                                    let trimmed;
+                                    let decoded = if skipped_frames > 0 {
+                                        trimmed = trim_start(&decoded, skipped_frames);
+                                        trimmed.as_audio_buffer_ref()
+                                    } else {
+                                        decoded
+                                    };
+
+                                    // Near the end the start of the next track is mixed in.
+                                    let mixed;
+                                    let decoded = match audio_engine_state.crossfade.as_mut() {
+                                        Some(crossfade)
+                                            if *decoded.spec() == crossfade.mixer.spec() =>
+                                        {
+                                            mixed = crossfade.mix(&decoded);
+                                            mixed.as_audio_buffer_ref()
+                                        }
+                                        _ => decoded,
+                                    };
+
                                     if let Some(audio_output) = audio_output {
+                                        let (output_rate, output_channels) =
+                                            audio_output.device_config();
+                                        let scope_len = scope::window_len(
+                                            output_rate,
+                                            output_channels,
+                                            audio_engine_state.scope_window_ms,
+                                        );
+                                        if scope_len != audio_engine_state.scope_len {
+                                            let gui_ring_buf = SpscRb::new(scope_len);
+                                            gui_ring_buf_producer = gui_ring_buf.producer();
+                                            audio_engine_state.scope_len = scope_len;
+                                            ui_tx
+                                                .send(UiCommand::ScopeBuffer(ScopeBuffer {
+                                                    consumer: gui_ring_buf.consumer(),
+                                                    sample_rate: output_rate,
+                                                    channels: output_channels,
+                                                    len: scope_len,
+                                                }))
+                                                .expect("Failed to send scope buffer to ui thread");
+                                        }
+
                                         audio_output
-                                            .write(decoded, &gui_ring_buf_producer, volume)
+                                            .write(
+                                                decoded,
+                                                &gui_ring_buf_producer,
+                                                &mut audio_engine_state.signal_chain,
+                                            )
                                             .unwrap();
+
+                                        let is_limiting =
+                                            audio_engine_state.signal_chain.is_limiting();
+                                        if is_limiting != audio_engine_state.is_limiting {
+                                            audio_engine_state.is_limiting = is_limiting;
+                                            ui_tx
+                                                .send(UiCommand::Limiting(is_limiting))
+                                                .expect("Failed to send limiting to ui thread");
+                                        }
                                     }
                                 }
 
@@ -160,12 +558,22 @@ fn main() {
                         //Ok(())
                     };
 
-                    // Return if a fatal error occured.
-                    ignore_end_of_stream_error(result)
-                        .expect("Encountered some other error than EoF");
+                    // Give up on the track if a fatal error occured.
+                    if let Err(err) = result {
+                        let path = current_track_path.take().unwrap_or_default();
+                        fail_track(&path, &err, &mut audio_engine_state, &mut decoder, &ui_tx);
+                        state = PlayerState::Unstarted;
+                        continue;
+                    }
 
                     // Finalize the decoder and return the verification result if it's been enabled.
                     _ = do_verification(decoder.as_mut().unwrap().finalize());
+
+                    if audio_engine_state.crossfade.is_none() && state == PlayerState::Playing {
+                        if let Some(fade_frames) = crossfade_frames(&audio_engine_state) {
+                            start_crossfade(&mut audio_engine_state, &mut decoder, fade_frames);
+                        }
+                    }
                 }
                 PlayerState::Stopped => {
                     // This is kind of a hack to get stopping to work. Flush the buffer so there is
@@ -184,7 +592,11 @@ fn main() {
 
                         audio_engine_state.audio_output = None;
 
-                        load_file(current_track_path, &mut audio_engine_state, &mut decoder, 0);
+                        if let Err(err) =
+                            load_file(current_track_path, &mut audio_engine_state, &mut decoder, 0)
+                        {
+                            tracing::warn!("failed to reload {:?}: {}", current_track_path, err);
+                        }
 
                         ui_tx
                             .send(UiCommand::CurrentTimestamp(0))
@@ -203,15 +615,116 @@ fn main() {
 
                         audio_engine_state.audio_output = None;
 
-                        load_file(
+                        send_loading(&ui_tx, true);
+                        let result = load_file(
                             current_track_path,
                             &mut audio_engine_state,
                             &mut decoder,
                             seek_timestamp,
                         );
-                        state = PlayerState::Playing;
+                        send_loading(&ui_tx, false);
+
+                        state = match result {
+                            Ok(()) => {
+                                send_playback(&ui_tx, PlaybackEvent::Seeked(seek_timestamp));
+                                PlayerState::Playing
+                            }
+                            Err(err) => {
+                                fail_track(
+                                    current_track_path,
+                                    &err,
+                                    &mut audio_engine_state,
+                                    &mut decoder,
+                                    &ui_tx,
+                                );
+                                PlayerState::Unstarted
+                            }
+                        };
                     }
                 }
+                PlayerState::Reload { timestamp, paused } => {
+                    tracing::info!("AudioThread Reloading at {}", timestamp);
+                    if let Some(ref current_track_path) = current_track_path {
+                        if let Some(audio_output) = audio_engine_state.audio_output.as_mut() {
+                            audio_output.flush()
+                        }
+
+                        audio_engine_state.audio_output = None;
+
+                        send_loading(&ui_tx, true);
+                        let result = load_file(
+                            current_track_path,
+                            &mut audio_engine_state,
+                            &mut decoder,
+                            timestamp,
+                        );
+                        send_loading(&ui_tx, false);
+
+                        match result {
+                            // The file may have been re-encoded since it was first opened.
+                            Ok(()) => {
+                                send_duration(&ui_tx, &audio_engine_state);
+                                send_track_format(&ui_tx, current_track_path, &audio_engine_state);
+                                ui_tx
+                                    .send(UiCommand::Chapters(read_chapters(
+                                        current_track_path,
+                                        &audio_engine_state,
+                                    )))
+                                    .expect("Failed to send chapters to ui thread");
+                            }
+                            Err(err) => fail_track(
+                                current_track_path,
+                                &err,
+                                &mut audio_engine_state,
+                                &mut decoder,
+                                &ui_tx,
+                            ),
+                        }
+                    }
+
+                    state = if audio_engine_state.reader.is_none() {
+                        PlayerState::Unstarted
+                    } else if paused {
+                        PlayerState::Paused
+                    } else {
+                        PlayerState::Playing
+                    };
+                }
+                PlayerState::SelectTrack { paused } => {
+                    tracing::info!(
+                        "AudioThread Selecting track {:?}",
+                        audio_engine_state.track_num
+                    );
+                    if let Some(audio_output) = audio_engine_state.audio_output.as_mut() {
+                        audio_output.flush()
+                    }
+
+                    // The other track may have another spec, the output is opened for it with the
+                    // first packet.
+                    audio_engine_state.audio_output = None;
+                    audio_engine_state.has_decoded_audio = false;
+                    audio_engine_state.seek = Some(SeekPosition::Timestamp(0));
+                    audio_engine_state.position = 0;
+
+                    state = match setup_track(&mut audio_engine_state, &mut decoder) {
+                        Ok(()) => {
+                            send_duration(&ui_tx, &audio_engine_state);
+                            if let Some(path) = &current_track_path {
+                                send_track_format(&ui_tx, path, &audio_engine_state);
+                            }
+                            if paused {
+                                PlayerState::Paused
+                            } else {
+                                PlayerState::Playing
+                            }
+                        }
+                        Err(err) => {
+                            let path = current_track_path.take().unwrap_or_default();
+                            fail_track(&path, &err, &mut audio_engine_state, &mut decoder, &ui_tx);
+                            PlayerState::Unstarted
+                        }
+                    };
+                }
                 PlayerState::LoadFile(ref path) => {
                     tracing::info!("AudioThread Loading File");
                     // Stop current playback
@@ -222,15 +735,75 @@ fn main() {
 
                     audio_engine_state.audio_output = None;
 
+                    audio_engine_state.has_decoded_audio = false;
+
+                    send_loading(&ui_tx, true);
+                    let result = load_file(path, &mut audio_engine_state, &mut decoder, 0);
+                    send_loading(&ui_tx, false);
+
+                    if let Err(err) = result {
+                        // Slow or unreachable network paths get a few more tries, spaced out
+                        // further each time.
+                        let is_timeout = matches!(&err, Error::IoError(err) if err.kind() == std::io::ErrorKind::TimedOut);
+                        let retry = audio_engine_state.network_retry;
+                        if is_timeout && audio_engine_state.open_attempts < retry.retries {
+                            audio_engine_state.open_attempts += 1;
+                            tracing::warn!(
+                                "Timed out opening {:?}, retry {} of {}",
+                                path,
+                                audio_engine_state.open_attempts,
+                                retry.retries
+                            );
+                            send_connecting(&ui_tx, Some(audio_engine_state.open_attempts));
+                            state = PlayerState::Connecting {
+                                path: (*path).clone(),
+                                retry_at: std::time::Instant::now()
+                                    + OPEN_RETRY_DELAY * audio_engine_state.open_attempts,
+                            };
+                            continue;
+                        }
+
+                        send_connecting(&ui_tx, None);
+                        fail_track(path, &err, &mut audio_engine_state, &mut decoder, &ui_tx);
+                        current_track_path = None;
+                        state = PlayerState::Unstarted;
+                        continue;
+                    }
+
+                    send_connecting(&ui_tx, None);
                     current_track_path = Some((*path).clone());
-                    load_file(path, &mut audio_engine_state, &mut decoder, 0);
-                    // TODO - Get total u64 track duration and send to Ui
+                    send_duration(&ui_tx, &audio_engine_state);
+                    send_track_format(&ui_tx, path, &audio_engine_state);
                     ui_tx
-                        .send(UiCommand::TotalTrackDuration(audio_engine_state.duration))
-                        .expect("Failed to send play to audio thread");
+                        .send(UiCommand::Chapters(read_chapters(
+                            path,
+                            &audio_engine_state,
+                        )))
+                        .expect("Failed to send chapters to ui thread");
+                    send_playback(&ui_tx, PlaybackEvent::Started((*path).clone()));
 
                     state = PlayerState::Playing;
                 }
+                PlayerState::TrackGap { until } => {
+                    // Any transport command replaces this state, which cancels the gap along with
+                    // the automatic advance.
+                    if std::time::Instant::now() >= until {
+                        state = PlayerState::Stopped;
+                        ui_tx
+                            .send(UiCommand::AudioFinished)
+                            .expect("Failed to send play to ui thread");
+                    } else {
+                        thread::sleep(std::time::Duration::from_millis(5));
+                    }
+                }
+                PlayerState::Connecting { ref path, retry_at } => {
+                    // Like the track gap, any command loading something else cancels the retry.
+                    if std::time::Instant::now() >= retry_at {
+                        state = PlayerState::LoadFile(path.clone());
+                    } else {
+                        thread::sleep(std::time::Duration::from_millis(20));
+                    }
+                }
                 PlayerState::Paused => {
                     // don't decode AND don't flush the buffer?
                 }
@@ -239,8 +812,14 @@ fn main() {
         }
     }); // Audio Thread end
 
+    // The window opens where it was left, it's moved back on screen once it's open if that's
+    // off every monitor now.
+    let viewport = match app.window {
+        Some(window) => window.viewport(egui::ViewportBuilder::default()),
+        None => egui::ViewportBuilder::default().with_inner_size(window_geometry::DEFAULT_SIZE),
+    };
     let native_options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default().with_inner_size([1024.0, 768.0]),
+        viewport,
         ..Default::default()
     };
 
@@ -265,6 +844,8 @@ fn main() {
                 .insert(0, "NotoSansSC".to_owned());
 
             cc.egui_ctx.set_fonts(fonts);
+            // Needs the event loop running, which it only is from here on.
+            app.tray = tray::Tray::start(&cc.egui_ctx);
 
             Ok(Box::new(app))
         }),
@@ -272,46 +853,242 @@ fn main() {
     .expect("eframe failed: I should change main to return a result and use anyhow");
 }
 
+// Handles the next command, if there is one. With a `wait`, blocks up to that long for it instead
+// of returning straight away.
 fn process_audio_cmd(
     audio_rx: &Receiver<AudioCommand>,
+    wait: Option<std::time::Duration>,
     state: &mut PlayerState,
-    volume: &mut f32,
+    audio_engine_state: &mut AudioEngineState,
     is_processing_ui_change: &Arc<AtomicBool>,
+    ui_tx: &Sender<UiCommand>,
 ) {
-    match audio_rx.try_recv() {
-        Ok(cmd) => {
+    let cmd = match wait {
+        Some(timeout) => audio_rx.recv_timeout(timeout).ok(),
+        None => audio_rx.try_recv().ok(),
+    };
+    match cmd {
+        Some(cmd) => {
+            let was_connecting = matches!(state, PlayerState::Connecting { .. });
+
             //Process Start
             match cmd {
                 AudioCommand::Seek(seconds) => {
                     tracing::info!("Processing SEEK command for {} seconds", seconds);
+                    audio_engine_state.crossfade = None;
                     *state = PlayerState::SeekTo(seconds);
                 }
                 AudioCommand::Stop => {
                     tracing::info!("Processing STOP command");
+                    audio_engine_state.crossfade = None;
                     *state = PlayerState::Stopped;
                 }
                 AudioCommand::Pause => {
                     tracing::info!("Processing PAUSE command");
+                    if *state == PlayerState::Playing {
+                        send_playback(ui_tx, PlaybackEvent::Paused);
+                    }
                     *state = PlayerState::Paused;
                 }
                 AudioCommand::Play => {
                     tracing::info!("Processing PLAY command");
+                    if *state == PlayerState::Paused {
+                        send_playback(ui_tx, PlaybackEvent::Resumed);
+                    }
+                    if *state != PlayerState::Playing {
+                        audio_engine_state.signal_chain.start_playback();
+                    }
                     *state = PlayerState::Playing;
                 }
                 AudioCommand::LoadFile(path) => {
                     tracing::info!("Processing LOAD FILE command for path: {:?}", &path);
+                    // Track numbers belong to the file they were picked in.
+                    audio_engine_state.track_num = None;
+                    audio_engine_state.next_track = None;
+                    audio_engine_state.crossfade = None;
+                    audio_engine_state.open_attempts = 0;
+                    audio_engine_state.has_failed = false;
                     *state = PlayerState::LoadFile(path);
                 }
                 AudioCommand::SetVolume(vol) => {
                     tracing::info!("Processing SET VOLUME command to: {:?}", &vol);
-                    *volume = vol;
+                    // Volumes restored from a hand-edited config can be out of range. The
+                    // volume only ever attenuates, it's applied to every sample the chain writes.
+                    audio_engine_state.signal_chain.volume = vol.clamp(0.0, 1.0);
                     is_processing_ui_change.store(false, Ordering::Relaxed);
                 }
-                _ => tracing::warn!("Unhandled case in audio command loop"),
+                AudioCommand::SetDucked(is_ducked) => {
+                    tracing::info!("Processing SET DUCKED command to: {}", is_ducked);
+                    audio_engine_state.signal_chain.is_ducked = is_ducked;
+                }
+                AudioCommand::SetNightMode(night_mode) => {
+                    tracing::info!("Processing SET NIGHT MODE command: {:?}", &night_mode);
+                    audio_engine_state.signal_chain.night_mode = night_mode;
+                }
+                AudioCommand::Reload => {
+                    tracing::info!("Processing RELOAD command");
+                    reload_in_place(state, audio_engine_state.position);
+                }
+                AudioCommand::SetTrackGain(gain) => {
+                    tracing::info!("Processing SET TRACK GAIN command to: {}", gain);
+                    audio_engine_state.signal_chain.track_gain = gain;
+                }
+                AudioCommand::SetEqBand { band, gain_db } => {
+                    tracing::info!("Processing SET EQ BAND command: {} to {}dB", band, gain_db);
+                    audio_engine_state
+                        .signal_chain
+                        .equalizer
+                        .set_band_gain(band, gain_db);
+                }
+                AudioCommand::SetFadeCurve(fade_curve) => {
+                    tracing::info!("Processing SET FADE CURVE command: {:?}", &fade_curve);
+                    audio_engine_state.signal_chain.fade_curve = fade_curve;
+                }
+                AudioCommand::SetLimiter(limiter) => {
+                    tracing::info!("Processing SET LIMITER command: {:?}", &limiter);
+                    audio_engine_state.signal_chain.limiter = limiter;
+                }
+                AudioCommand::SetNetworkRetry(network_retry) => {
+                    tracing::info!("Processing SET NETWORK RETRY command: {:?}", &network_retry);
+                    audio_engine_state.network_retry = network_retry;
+                }
+                AudioCommand::SetThreadPriority(priority) => {
+                    tracing::info!("Processing SET THREAD PRIORITY command: {:?}", &priority);
+                    set_audio_thread_priority(priority);
+                }
+                AudioCommand::SetBalance(balance) => {
+                    tracing::info!("Processing SET BALANCE command to: {:?}", &balance);
+                    audio_engine_state.signal_chain.balance = balance;
+                }
+                AudioCommand::SetPanLaw(pan_law) => {
+                    tracing::info!("Processing SET PAN LAW command to: {}", &pan_law);
+                    audio_engine_state.signal_chain.pan_law = pan_law;
+                }
+                AudioCommand::SetReaderOptions(reader_options) => {
+                    tracing::info!(
+                        "Processing SET READER OPTIONS command: {:?}",
+                        &reader_options
+                    );
+                    if reader_options != audio_engine_state.reader_options {
+                        audio_engine_state.reader_options = reader_options;
+
+                        // The open reader and decoder were built with the old options.
+                        reload_in_place(state, audio_engine_state.position);
+                    }
+                }
+                AudioCommand::SetTrackGap(gap_ms) => {
+                    tracing::info!("Processing SET TRACK GAP command to: {}ms", gap_ms);
+                    audio_engine_state.track_gap = std::time::Duration::from_millis(gap_ms);
+                }
+                AudioCommand::SetCrossfade(secs) => {
+                    tracing::info!("Processing SET CROSSFADE command to: {}s", secs);
+                    audio_engine_state.crossfade_secs = secs.max(0.0);
+                }
+                AudioCommand::SetCrossfadeCurve(curve) => {
+                    tracing::info!("Processing SET CROSSFADE CURVE command to: {:?}", curve);
+                    audio_engine_state.crossfade_curve = curve;
+                }
+                AudioCommand::SetSeekPrecision(seek_precision) => {
+                    tracing::info!(
+                        "Processing SET SEEK PRECISION command to: {}",
+                        seek_precision
+                    );
+                    audio_engine_state.seek_precision = seek_precision;
+                }
+                AudioCommand::SetScopeWindow(window_ms) => {
+                    tracing::info!("Processing SET SCOPE WINDOW command to: {}ms", window_ms);
+                    // The scope buffer is reallocated with the next decoded packet.
+                    audio_engine_state.scope_window_ms = window_ms;
+                }
+                AudioCommand::SetOutputHost(output_host) => {
+                    tracing::info!("Processing SET OUTPUT HOST command to: {:?}", &output_host);
+                    if output_host != audio_engine_state.output_host {
+                        audio_engine_state.output_host = output_host;
+
+                        // The output is opened on the next decoded packet after a reload.
+                        reload_in_place(state, audio_engine_state.position);
+                    }
+                }
+                AudioCommand::SetOutputDevice(output_device) => {
+                    tracing::info!(
+                        "Processing SET OUTPUT DEVICE command to: {:?}",
+                        &output_device
+                    );
+                    if output_device != audio_engine_state.output_device {
+                        audio_engine_state.output_device = output_device;
+                        reload_in_place(state, audio_engine_state.position);
+                    }
+                }
+                AudioCommand::EnqueueNext { path, gain } => {
+                    tracing::info!("Processing ENQUEUE NEXT command for path: {:?}", &path);
+                    // The track fading in is no longer the one that comes next.
+                    if audio_engine_state
+                        .crossfade
+                        .as_ref()
+                        .is_some_and(|crossfade| Some(&crossfade.path) != path.as_ref())
+                    {
+                        audio_engine_state.crossfade = None;
+                    }
+                    audio_engine_state.next_track = path.map(|path| NextTrack {
+                        file: open_in_background(&path),
+                        path,
+                        gain,
+                    });
+                }
+                AudioCommand::Select(track_num) => {
+                    tracing::info!("Processing SELECT command for track {}", track_num);
+                    audio_engine_state.track_num = Some(track_num);
+                    audio_engine_state.crossfade = None;
+
+                    // Stopped, the track is played once the file is started again.
+                    let paused = match state {
+                        PlayerState::Playing => false,
+                        PlayerState::Paused => true,
+                        _ => return,
+                    };
+                    *state = PlayerState::SelectTrack { paused };
+                }
+            }
+
+            // The retry was cancelled.
+            if was_connecting && !matches!(state, PlayerState::Connecting { .. }) {
+                send_connecting(ui_tx, None);
             }
         }
-        Err(_) => (), // When no commands are sent, this will evaluate. aka - it is the
-                      // common case. No need to print anything
+        None => {
+            // When no commands are sent, this will evaluate. aka - it is the common case. No
+            // need to print anything
+        }
+    }
+}
+
+// Reopens the playing track where it is so a change to how it's opened is heard right away.
+fn reload_in_place(state: &mut PlayerState, timestamp: u64) {
+    let paused = match state {
+        PlayerState::Playing => false,
+        PlayerState::Paused => true,
+        _ => return,
+    };
+
+    *state = PlayerState::Reload { timestamp, paused };
+}
+
+// Must be called from the audio thread. Raising the priority often needs privileges the user
+// doesn't have, so a refusal is only logged and playback carries on at the normal priority.
+fn set_audio_thread_priority(priority: AudioThreadPriority) {
+    use thread_priority::{set_current_thread_priority, ThreadPriority, ThreadPriorityValue};
+
+    let thread_priority = match priority {
+        AudioThreadPriority::Normal => return,
+        AudioThreadPriority::High => match ThreadPriorityValue::try_from(75u8) {
+            Ok(value) => ThreadPriority::Crossplatform(value),
+            Err(_) => return,
+        },
+        AudioThreadPriority::Highest => ThreadPriority::Max,
+    };
+
+    if let Err(err) = set_current_thread_priority(thread_priority) {
+        tracing::warn!("Couldn't raise the audio thread priority: {:?}", err);
     }
 }
 
@@ -333,8 +1110,26 @@ pub enum PlayerState {
     Paused,
     LoadFile(PathBuf),
     SeekTo(u64),
+    Reload {
+        timestamp: u64,
+        paused: bool,
+    },
+    // Switching to `AudioEngineState::track_num` in the open file, from its start.
+    SelectTrack {
+        paused: bool,
+    },
+    // Silence between a track ending on its own and the next one being requested.
+    TrackGap {
+        until: std::time::Instant,
+    },
+    // Waiting to try opening a track again after it timed out.
+    Connecting {
+        path: PathBuf,
+        retry_at: std::time::Instant,
+    },
 }
 
+#[derive(Default)]
 struct AudioEngineState {
     pub reader: Option<Box<dyn FormatReader>>,
     pub audio_output: Option<Box<dyn output::AudioOutput>>,
@@ -343,82 +1138,570 @@ struct AudioEngineState {
     pub decode_opts: Option<DecoderOptions>,
     pub track_info: Option<PlayTrackOptions>,
     pub duration: u64,
+    // Timestamp of the last packet handed to the decoder.
+    pub position: u64,
+    // Whole seconds into the track the UI was last told about, `None` until the first packet of
+    // a newly opened or swapped in reader.
+    pub sent_secs: Option<u64>,
+    pub reader_options: ReaderOptions,
+    pub signal_chain: SignalChain,
+    pub time_base: Option<TimeBase>,
+    // Audio backend to open outputs on, `None` for cpal's default.
+    pub output_host: Option<String>,
+    // Device on that backend, `None` for its default.
+    pub output_device: Option<String>,
+    // Whether any packet of the loaded track has decoded, to tell empty files from finished ones.
+    pub has_decoded_audio: bool,
+    pub track_gap: std::time::Duration,
+    // How long the end of a track overlaps the next one, in seconds. 0 turns crossfading off.
+    pub crossfade_secs: f32,
+    pub crossfade_curve: FadeCurve,
+    pub crossfade: Option<Crossfade>,
+    pub seek_precision: SeekPrecision,
+    pub scope_window_ms: u64,
+    // Size of the scope ring buffer the UI currently reads from.
+    pub scope_len: usize,
+    // What the open output was set up for.
+    pub output_spec: Option<OutputSpec>,
+    pub network_retry: NetworkRetry,
+    // Retries made so far opening the track being loaded.
+    pub open_attempts: u32,
+    // The loaded track couldn't be played.
+    pub has_failed: bool,
+    // What the UI was last told about the limiter.
+    pub is_limiting: bool,
+    // The UI hasn't heard about the stream being written yet.
+    pub is_stream_info_stale: bool,
+    pub next_track: Option<NextTrack>,
+}
+
+// The track announced to follow the current one. Its file is opened in the background as soon as
+// it's known, so a slow disk or network share doesn't hold up the switch.
+struct NextTrack {
+    path: PathBuf,
+    file: Receiver<std::io::Result<std::fs::File>>,
+    // Its loudness normalization, taken over by the signal chain once it plays.
+    gain: f32,
+}
+
+// What `load_opened_file` sets up to play a track, kept aside while a second track is loaded.
+#[derive(Default)]
+struct LoadedTrack {
+    reader: Option<Box<dyn FormatReader>>,
+    decoder: Option<Box<dyn symphonia::core::codecs::Decoder>>,
+    track_info: Option<PlayTrackOptions>,
+    duration: u64,
+    position: u64,
+    time_base: Option<TimeBase>,
+}
+
+fn swap_loaded_track(
+    audio_engine_state: &mut AudioEngineState,
+    decoder: &mut Option<Box<dyn symphonia::core::codecs::Decoder>>,
+    track: &mut LoadedTrack,
+) {
+    std::mem::swap(&mut audio_engine_state.reader, &mut track.reader);
+    std::mem::swap(decoder, &mut track.decoder);
+    std::mem::swap(&mut audio_engine_state.track_info, &mut track.track_info);
+    std::mem::swap(&mut audio_engine_state.duration, &mut track.duration);
+    std::mem::swap(&mut audio_engine_state.position, &mut track.position);
+    std::mem::swap(&mut audio_engine_state.time_base, &mut track.time_base);
+    audio_engine_state.sent_secs = None;
+}
+
+// The next track, decoded alongside the end of the playing one and mixed into it. It takes over
+// once the playing track ends.
+struct Crossfade {
+    path: PathBuf,
+    gain: f32,
+    track: LoadedTrack,
+    mixer: CrossfadeMixer,
+}
+
+impl Crossfade {
+    // Decodes the incoming track until `frames` are queued, or it runs out. Packets that don't
+    // match the output's spec stop it early, nothing more is mixed in from there.
+    fn fill(&mut self, frames: usize) -> Result<()> {
+        let (Some(reader), Some(decoder), Some(track_info)) = (
+            self.track.reader.as_mut(),
+            self.track.decoder.as_mut(),
+            self.track.track_info,
+        ) else {
+            return Ok(());
+        };
+
+        while self.mixer.queued_frames() < frames {
+            let packet = match reader.next_packet() {
+                Ok(packet) => packet,
+                Err(err) => return ignore_end_of_stream_error(Err(err)),
+            };
+            if packet.track_id() != track_info.track_id {
+                continue;
+            }
+
+            self.track.position = packet.ts();
+            match decoder.decode(&packet) {
+                Ok(decoded) if *decoded.spec() == self.mixer.spec() => self.mixer.push(&decoded),
+                Ok(_) => return Ok(()),
+                Err(Error::DecodeError(err)) => tracing::warn!("decode error: {}", err),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+
+    // The playing track's decoded audio, faded out with the next track mixed in.
+    fn mix(&mut self, decoded: &AudioBufferRef<'_>) -> AudioBuffer<f32> {
+        let mut outgoing = decoded.make_equivalent::<f32>();
+        decoded.convert(&mut outgoing);
+
+        if let Err(err) = self.fill(outgoing.frames()) {
+            tracing::warn!("couldn't decode {:?} to crossfade into: {}", self.path, err);
+        }
+        self.mixer.mix(&mut outgoing);
+        outgoing
+    }
+}
+
+// Frames left of the playing track at the output's rate, once it's within the crossfade of its
+// end and the next track is known. Streams of unknown length can't be crossfaded.
+fn crossfade_frames(audio_engine_state: &AudioEngineState) -> Option<usize> {
+    if audio_engine_state.crossfade_secs <= 0.0
+        || audio_engine_state.next_track.is_none()
+        || !audio_engine_state.has_decoded_audio
+        || audio_engine_state.duration == 0
+    {
+        return None;
+    }
+
+    let time_base = audio_engine_state.time_base?;
+    let output_spec = audio_engine_state.output_spec?;
+    let remaining = time_base.calc_time(
+        audio_engine_state
+            .duration
+            .saturating_sub(audio_engine_state.position),
+    );
+    let remaining_secs = remaining.seconds as f64 + remaining.frac;
+
+    (remaining_secs <= audio_engine_state.crossfade_secs as f64)
+        .then_some((remaining_secs * output_spec.spec.rate as f64) as usize)
+}
+
+// Opens the next track alongside the playing one and starts fading it in. A track that can't be
+// opened, or has another sample rate or channel layout than the output, isn't crossfaded. The UI
+// loads it as usual once the playing track ends.
+fn start_crossfade(
+    audio_engine_state: &mut AudioEngineState,
+    decoder: &mut Option<Box<dyn symphonia::core::codecs::Decoder>>,
+    fade_frames: usize,
+) {
+    let (Some(next_track), Some(output_spec)) = (
+        audio_engine_state.next_track.take(),
+        audio_engine_state.output_spec,
+    ) else {
+        return;
+    };
+    let timeout = std::time::Duration::from_secs(audio_engine_state.network_retry.timeout_secs);
+
+    // Loading sets up the engine's own reader and decoder, so the playing track steps aside
+    // meanwhile.
+    let mut track = LoadedTrack::default();
+    swap_loaded_track(audio_engine_state, decoder, &mut track);
+    let result = wait_for_file(&next_track.file, timeout)
+        .map_err(Error::IoError)
+        .and_then(|file| load_opened_file(file, audio_engine_state, decoder, 0));
+    swap_loaded_track(audio_engine_state, decoder, &mut track);
+
+    // The signal chain applies the playing track's gain to the mix, the incoming track makes up
+    // the difference to its own.
+    let incoming_gain =
+        next_track.gain / audio_engine_state.signal_chain.track_gain.max(f32::EPSILON);
+    let mut crossfade = Crossfade {
+        path: next_track.path,
+        gain: next_track.gain,
+        track,
+        mixer: CrossfadeMixer::new(
+            output_spec.spec,
+            fade_frames,
+            incoming_gain,
+            audio_engine_state.crossfade_curve,
+        ),
+    };
+    match result.and_then(|()| crossfade.fill(1)) {
+        Ok(()) if crossfade.mixer.queued_frames() > 0 => {
+            tracing::info!("Crossfading into {:?}", crossfade.path);
+            audio_engine_state.crossfade = Some(crossfade);
+        }
+        Ok(()) => tracing::info!(
+            "Not crossfading into {:?}, its format differs",
+            crossfade.path
+        ),
+        Err(err) => tracing::warn!("couldn't crossfade into {:?}: {}", crossfade.path, err),
+    }
+}
+
+/// The signal spec and largest buffer an output was opened for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct OutputSpec {
+    spec: SignalSpec,
+    duration: u64,
+}
+
+impl OutputSpec {
+    /// Whether a decoded buffer can be written to the output as is.
+    fn accepts(&self, spec: SignalSpec, duration: u64) -> bool {
+        self.spec == spec && duration <= self.duration
+    }
+}
+
+// Whether decoded audio of `spec`, in buffers of up to `duration` frames, needs another output
+// than the one open for `current`, if any.
+fn needs_reopen(current: Option<&OutputSpec>, spec: SignalSpec, duration: u64) -> bool {
+    !current.is_some_and(|current| current.accepts(spec, duration))
 }
 
 fn load_file(
-    path: &PathBuf,
+    path: &Path,
     audio_engine_state: &mut AudioEngineState,
     decoder: &mut Option<Box<dyn symphonia::core::codecs::Decoder>>,
     seek_timestamp: u64,
-) {
+) -> Result<()> {
+    let timeout = std::time::Duration::from_secs(audio_engine_state.network_retry.timeout_secs);
+    let file = open_with_timeout(path, timeout)?;
+    load_opened_file(file, audio_engine_state, decoder, seek_timestamp)
+}
+
+fn load_opened_file(
+    file: std::fs::File,
+    audio_engine_state: &mut AudioEngineState,
+    decoder: &mut Option<Box<dyn symphonia::core::codecs::Decoder>>,
+    seek_timestamp: u64,
+) -> Result<()> {
     let hint = Hint::new();
-    let source = Box::new(std::fs::File::open(path).expect("couldn't open file"));
-    let mss = MediaSourceStream::new(source, Default::default());
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
     let format_opts = FormatOptions {
-        enable_gapless: true,
+        enable_gapless: audio_engine_state.reader_options.gapless,
         ..Default::default()
     };
     let metadata_opts: MetadataOptions = Default::default();
     let seek = Some(SeekPosition::Timestamp(seek_timestamp));
+    audio_engine_state.position = seek_timestamp;
+    audio_engine_state.sent_secs = None;
 
-    match symphonia::default::get_probe().format(&hint, mss, &format_opts, &metadata_opts) {
-        Ok(probed) => {
-            // Set the decoder options.
-            let decode_opts = DecoderOptions { verify: true };
+    // Zero-byte files fail here since no format reader recognizes them.
+    let probed =
+        symphonia::default::get_probe().format(&hint, mss, &format_opts, &metadata_opts)?;
 
-            audio_engine_state.reader = Some(probed.format);
-            audio_engine_state.decode_opts = Some(decode_opts);
-            audio_engine_state.seek = seek;
+    // Set the decoder options.
+    let decode_opts = DecoderOptions {
+        verify: audio_engine_state.reader_options.verify,
+    };
 
-            // Configure everything for playback.
-            _ = setup_audio_reader(audio_engine_state);
+    audio_engine_state.reader = Some(probed.format);
+    audio_engine_state.decode_opts = Some(decode_opts);
+    audio_engine_state.seek = seek;
 
-            let reader = audio_engine_state.reader.as_mut().unwrap();
-            let play_opts = audio_engine_state.track_info.unwrap();
-            let decode_opts = audio_engine_state.decode_opts.unwrap();
+    setup_track(audio_engine_state, decoder)
+}
+
+// Picks the track to play from the open reader, seeks it and makes a decoder for it. Run again on
+// the same reader to switch to another track of the file.
+fn setup_track(
+    audio_engine_state: &mut AudioEngineState,
+    decoder: &mut Option<Box<dyn symphonia::core::codecs::Decoder>>,
+) -> Result<()> {
+    audio_engine_state.track_info = None;
+
+    // Configure everything for playback.
+    _ = setup_audio_reader(audio_engine_state);
+
+    let reader = audio_engine_state.reader.as_mut().unwrap();
+    let Some(play_opts) = audio_engine_state.track_info else {
+        return Err(Error::Unsupported("no supported audio track"));
+    };
+
+    let track = match reader
+        .tracks()
+        .iter()
+        .find(|track| track.id == play_opts.track_id)
+    {
+        Some(track) => track,
+        _ => {
+            tracing::warn!("Couldn't find track");
+            return Err(Error::Unsupported("no supported audio track"));
+        }
+    };
+
+    // A header that promises no frames at all has nothing to play.
+    if track.codec_params.n_frames == Some(0) {
+        return Err(Error::DecodeError("track has no audio frames"));
+    }
+
+    // Create a decoder for the track.
+    let decode_opts = audio_engine_state.decode_opts.unwrap_or_default();
+    *decoder = Some(symphonia::default::get_codecs().make(&track.codec_params, &decode_opts)?);
+
+    // Get the selected track's timebase and duration.
+    let tb = track.codec_params.time_base;
+    let dur = track
+        .codec_params
+        .n_frames
+        .map(|frames| track.codec_params.start_ts + frames);
+
+    // Zero for streams that don't declare their length, a track with no frames failed above.
+    audio_engine_state.duration = dur.unwrap_or(0);
+    audio_engine_state.time_base = tb;
+
+    tracing::info!("Track Duration: {}, TimeBase: {:?}", dur.unwrap_or(0), tb);
+
+    Ok(())
+}
+
+// Opening a file on an unresponsive network mount can block for minutes, so it happens on a
+// helper thread the audio thread stops waiting for after `timeout`. A helper that is stuck for
+// good is left behind, it ends by itself once the mount answers.
+fn open_with_timeout(path: &Path, timeout: std::time::Duration) -> std::io::Result<std::fs::File> {
+    wait_for_file(&open_in_background(path), timeout)
+}
+
+fn open_in_background(path: &Path) -> Receiver<std::io::Result<std::fs::File>> {
+    let (tx, rx) = channel();
+    let path = path.to_path_buf();
+    thread::spawn(move || {
+        let file = std::fs::File::open(&path).and_then(|file| file.metadata().map(|_| file));
+        _ = tx.send(file);
+    });
+
+    rx
+}
+
+fn wait_for_file(
+    file: &Receiver<std::io::Result<std::fs::File>>,
+    timeout: std::time::Duration,
+) -> std::io::Result<std::fs::File> {
+    file.recv_timeout(timeout).unwrap_or_else(|_| {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            format!("no response after {timeout:?}"),
+        ))
+    })
+}
+
+fn send_connecting(ui_tx: &Sender<UiCommand>, attempt: Option<u32>) {
+    ui_tx
+        .send(UiCommand::Connecting(attempt))
+        .expect("Failed to send connecting state to ui thread");
+}
+
+// The duration is in the track's timestamps, like the positions sent while playing.
+fn send_duration(ui_tx: &Sender<UiCommand>, audio_engine_state: &AudioEngineState) {
+    let cmd = match audio_engine_state.duration {
+        0 => UiCommand::UnknownDuration,
+        duration => UiCommand::TotalTrackDuration(duration),
+    };
+    ui_tx
+        .send(cmd)
+        .expect("Failed to send duration to ui thread");
+}
 
-            let track = match reader
+// What the loaded track of `path` is encoded as. The bitrate is the file's average, tags and cover
+// art included.
+fn send_track_format(
+    ui_tx: &Sender<UiCommand>,
+    path: &Path,
+    audio_engine_state: &AudioEngineState,
+) {
+    let Some(params) = audio_engine_state
+        .reader
+        .as_ref()
+        .zip(audio_engine_state.track_info)
+        .and_then(|(reader, track_info)| {
+            reader
                 .tracks()
                 .iter()
-                .find(|track| track.id == play_opts.track_id)
-            {
-                Some(track) => track,
-                _ => {
-                    tracing::warn!("Couldn't find track");
-                    return;
-                }
-            };
+                .find(|track| track.id == track_info.track_id)
+        })
+        .map(|track| &track.codec_params)
+    else {
+        return;
+    };
 
-            // Create a decoder for the track.
-            *decoder = Some(
-                symphonia::default::get_codecs()
-                    .make(&track.codec_params, &decode_opts)
-                    .expect("Failed to get decoder"),
-            );
+    let secs = audio_engine_state.time_base.map(|time_base| {
+        let time = time_base.calc_time(audio_engine_state.duration);
+        time.seconds as f64 + time.frac
+    });
+    let bitrate_kbps = std::fs::metadata(path)
+        .ok()
+        .zip(secs.filter(|secs| *secs > 0.0))
+        .map(|(metadata, secs)| (metadata.len() as f64 * 8.0 / secs / 1000.0).round() as u32);
+
+    ui_tx
+        .send(UiCommand::TrackFormat(TrackFormat {
+            sample_rate: params.sample_rate,
+            channels: params.channels.map(|channels| channels.count()),
+            bits_per_sample: params.bits_per_sample,
+            codec_name: symphonia::default::get_codecs()
+                .get_codec(params.codec)
+                .map(|codec| codec.short_name.to_uppercase()),
+            bitrate_kbps,
+        }))
+        .expect("Failed to send track format to ui thread");
+}
 
-            // Get the selected track's timebase and duration.
-            let _tb = track.codec_params.time_base;
-            let dur = track
-                .codec_params
-                .n_frames
-                .map(|frames| track.codec_params.start_ts + frames);
+// Probing and building the decoder can take a noticeable moment, especially for large or
+// network files, so the UI shows a spinner meanwhile.
+fn send_loading(ui_tx: &Sender<UiCommand>, is_loading: bool) {
+    ui_tx
+        .send(UiCommand::Loading(is_loading))
+        .expect("Failed to send loading state to ui thread");
+}
 
-            if let Some(duration) = dur {
-                audio_engine_state.duration = duration;
-            }
+fn send_playback(ui_tx: &Sender<UiCommand>, event: PlaybackEvent) {
+    ui_tx
+        .send(UiCommand::Playback(event))
+        .expect("Failed to send playback event to ui thread");
+}
 
-            tracing::info!(
-                "Track Duration: {}, TimeBase: {}",
-                dur.unwrap_or(0),
-                _tb.unwrap()
-            );
+// The output went on from the playing track into `path` without the UI loading it, gaplessly or
+// crossfading.
+fn send_continued(
+    ui_tx: &Sender<UiCommand>,
+    current_track_path: &mut Option<PathBuf>,
+    path: PathBuf,
+    audio_engine_state: &AudioEngineState,
+) {
+    let ended = current_track_path.replace(path.clone()).unwrap_or_default();
+    send_playback(ui_tx, PlaybackEvent::Ended(ended));
+    ui_tx
+        .send(UiCommand::ContinuedWith(path.clone()))
+        .expect("Failed to send next track to ui thread");
+    send_duration(ui_tx, audio_engine_state);
+    send_track_format(ui_tx, &path, audio_engine_state);
+    ui_tx
+        .send(UiCommand::Chapters(read_chapters(
+            &path,
+            audio_engine_state,
+        )))
+        .expect("Failed to send chapters to ui thread");
+    send_playback(ui_tx, PlaybackEvent::Started(path));
+}
+
+fn playback_status(
+    state: &PlayerState,
+    audio_engine_state: &AudioEngineState,
+    last_progress: std::time::Instant,
+) -> PlaybackStatus {
+    match state {
+        PlayerState::Playing if last_progress.elapsed() > BUFFERING_AFTER => {
+            PlaybackStatus::Buffering
         }
-        Err(err) => {
-            // The input was not supported by any format reader.
-            tracing::warn!("the audio format is not supported: {}", err);
-            // Err(err);
+        PlayerState::Playing => PlaybackStatus::Playing,
+        PlayerState::LoadFile(_)
+        | PlayerState::Connecting { .. }
+        | PlayerState::SeekTo(_)
+        | PlayerState::Reload { .. }
+        | PlayerState::SelectTrack { .. } => PlaybackStatus::Buffering,
+        PlayerState::Paused => PlaybackStatus::Paused,
+        _ if audio_engine_state.has_failed => PlaybackStatus::Error,
+        PlayerState::Unstarted | PlayerState::Stopped | PlayerState::TrackGap { .. } => {
+            PlaybackStatus::Stopped
+        }
+    }
+}
+
+// Leaves the engine idle after a track couldn't be played, and lets the UI skip past it.
+fn fail_track(
+    path: &Path,
+    err: &Error,
+    audio_engine_state: &mut AudioEngineState,
+    decoder: &mut Option<Box<dyn symphonia::core::codecs::Decoder>>,
+    ui_tx: &Sender<UiCommand>,
+) {
+    tracing::warn!("couldn't play {:?}: {}", path, err);
+    audio_engine_state.has_failed = true;
+    ui_tx
+        .send(UiCommand::PlaybackError(format!(
+            "Couldn't play {}: {}",
+            path.file_name()
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy(),
+            describe_failure(err)
+        )))
+        .expect("Failed to send playback error to ui thread");
+
+    if let Some(audio_output) = audio_engine_state.audio_output.as_mut() {
+        audio_output.flush()
+    }
+
+    audio_engine_state.audio_output = None;
+    audio_engine_state.reader = None;
+    audio_engine_state.track_info = None;
+    audio_engine_state.crossfade = None;
+    *decoder = None;
+
+    ui_tx
+        .send(UiCommand::TrackFailed(path.to_path_buf()))
+        .expect("Failed to send track failure to ui thread");
+}
+
+// Why a track failed, worded for the UI. Symphonia's own messages are kept for the rest.
+fn describe_failure(err: &Error) -> String {
+    match err {
+        Error::IoError(err) => match err.kind() {
+            std::io::ErrorKind::NotFound => "the file doesn't exist".to_string(),
+            std::io::ErrorKind::PermissionDenied => "the file can't be read".to_string(),
+            std::io::ErrorKind::TimedOut => "the file didn't load in time".to_string(),
+            _ => err.to_string(),
+        },
+        Error::Unsupported(what) => format!("{what} isn't supported"),
+        _ => err.to_string(),
+    }
+}
+
+// Chapter markers for the loaded track. Cue sheets (e.g. embedded in FLAC) are preferred, otherwise
+// ID3 CHAP frames are used.
+fn read_chapters(path: &Path, audio_engine_state: &AudioEngineState) -> Vec<Chapter> {
+    if let Some(reader) = &audio_engine_state.reader {
+        if !reader.cues().is_empty() {
+            return reader
+                .cues()
+                .iter()
+                .map(|cue| Chapter {
+                    title: cue
+                        .tags
+                        .iter()
+                        .find(|tag| tag.std_key == Some(StandardTagKey::TrackTitle))
+                        .map(|tag| tag.value.to_string()),
+                    start_ts: cue.start_ts,
+                })
+                .collect();
         }
     }
+
+    // CHAP frames are in milliseconds, so a time base is needed to turn them into timestamps.
+    let Some(time_base) = audio_engine_state.time_base else {
+        return Vec::new();
+    };
+
+    let mut chapters = match id3::Tag::read_from_path(path) {
+        Ok(tag) => tag
+            .chapters()
+            .map(|chapter| Chapter {
+                title: chapter
+                    .frames
+                    .iter()
+                    .find(|frame| frame.id() == "TIT2")
+                    .and_then(|frame| frame.content().text())
+                    .map(|title| title.to_string()),
+                start_ts: time_base.calc_timestamp(Time::from(chapter.start_time as f64 / 1000.0)),
+            })
+            .collect::<Vec<_>>(),
+        Err(_) => Vec::new(),
+    };
+    chapters.sort_by_key(|chapter| chapter.start_ts);
+
+    chapters
 }
 
 fn setup_audio_reader(audio_engine_state: &mut AudioEngineState) -> Result<i32> {
@@ -438,11 +1721,8 @@ fn setup_audio_reader(audio_engine_state: &mut AudioEngineState) -> Result<i32>
     };
 
     // If seeking, seek the reader to the time or timestamp specified and get the timestamp of the
-    // seeked position. All packets with a timestamp < the seeked position will not be played.
-    //
-    // Note: This is a half-baked approach to seeking! After seeking the reader, packets should be
-    // decoded and *samples* discarded up-to the exact *sample* indicated by required_ts. The
-    // current approach will discard excess samples if seeking to a sample within a packet.
+    // seeked position. The reader lands on a packet at or before it, the samples decoded ahead of
+    // the seeked position are dropped in the decode loop.
     let seek_ts = if let Some(seek) = seek {
         let seek_to = match seek {
             SeekPosition::Timestamp(ts) => SeekTo::TimeStamp { ts: *ts, track_id },
@@ -450,7 +1730,12 @@ fn setup_audio_reader(audio_engine_state: &mut AudioEngineState) -> Result<i32>
 
         // Attempt the seek. If the seek fails, ignore the error and return a seek timestamp of 0 so
         // that no samples are trimmed.
-        match reader.seek(SeekMode::Accurate, seek_to) {
+        let seek_mode = match audio_engine_state.seek_precision {
+            SeekPrecision::Accurate => SeekMode::Accurate,
+            SeekPrecision::Coarse => SeekMode::Coarse,
+        };
+
+        match reader.seek(seek_mode, seek_to) {
             Ok(seeked_to) => seeked_to.required_ts,
             Err(Error::ResetRequired) => {
                 tracing::warn!("reset required...");
@@ -476,6 +1761,34 @@ fn setup_audio_reader(audio_engine_state: &mut AudioEngineState) -> Result<i32>
     Ok(0)
 }
 
+// How many leading frames of a packet starting at `packet_ts` come before `seek_ts`. Timestamps
+// usually count frames, but not for every container.
+fn frames_before_seek(
+    packet_ts: u64,
+    seek_ts: u64,
+    time_base: Option<TimeBase>,
+    rate: u32,
+) -> usize {
+    let ts = seek_ts.saturating_sub(packet_ts);
+    let frames = match time_base {
+        Some(time_base) if time_base != TimeBase::new(1, rate) => {
+            let time = time_base.calc_time(ts);
+            time.seconds * rate as u64 + (time.frac * rate as f64).round() as u64
+        }
+        _ => ts,
+    };
+
+    usize::try_from(frames).unwrap_or(usize::MAX)
+}
+
+// A copy of `decoded` without its first `frames` frames.
+fn trim_start(decoded: &AudioBufferRef<'_>, frames: usize) -> AudioBuffer<f32> {
+    let mut trimmed = decoded.make_equivalent::<f32>();
+    decoded.convert(&mut trimmed);
+    trimmed.shift(frames);
+    trimmed
+}
+
 fn first_supported_track(tracks: &[Track]) -> Option<&Track> {
     tracks
         .iter()
@@ -508,3 +1821,158 @@ fn do_verification(finalization: FinalizeResult) -> Result<i32> {
         _ => Ok(0),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_temp_file(name: &str, contents: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("music-player-{}-{name}", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn try_load(path: &PathBuf) -> Result<()> {
+        let mut audio_engine_state = AudioEngineState::default();
+        let mut decoder = None;
+        let result = load_file(path, &mut audio_engine_state, &mut decoder, 0);
+        std::fs::remove_file(path).unwrap();
+
+        result
+    }
+
+    #[test]
+    fn empty_file_fails_to_load() {
+        let path = write_temp_file("empty.wav", &[]);
+
+        assert!(try_load(&path).is_err());
+    }
+
+    #[test]
+    fn missing_file_is_not_retried() {
+        let path = std::env::temp_dir().join("music-player-missing.mp3");
+        let err = open_with_timeout(&path, std::time::Duration::from_secs(5)).unwrap_err();
+
+        // Only timeouts are worth trying again.
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(
+            describe_failure(&Error::IoError(err)),
+            "the file doesn't exist"
+        );
+    }
+
+    #[test]
+    fn playing_without_progress_is_buffering() {
+        let mut audio_engine_state = AudioEngineState::default();
+        let now = std::time::Instant::now();
+        let stalled_since = now - 2 * BUFFERING_AFTER;
+
+        assert_eq!(
+            playback_status(&PlayerState::Playing, &audio_engine_state, now),
+            PlaybackStatus::Playing
+        );
+        assert_eq!(
+            playback_status(&PlayerState::Playing, &audio_engine_state, stalled_since),
+            PlaybackStatus::Buffering
+        );
+
+        audio_engine_state.has_failed = true;
+        assert_eq!(
+            playback_status(&PlayerState::Unstarted, &audio_engine_state, now),
+            PlaybackStatus::Error
+        );
+    }
+
+    // A 16-bit mono PCM WAV.
+    fn pcm_wav(rate: u32, samples: &[i16]) -> Vec<u8> {
+        let data_len = 2 * samples.len() as u32;
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        wav.extend_from_slice(&1u16.to_le_bytes()); // Channels
+        wav.extend_from_slice(&rate.to_le_bytes()); // Sample rate
+        wav.extend_from_slice(&(2 * rate).to_le_bytes()); // Byte rate
+        wav.extend_from_slice(&2u16.to_le_bytes()); // Block align
+        wav.extend_from_slice(&16u16.to_le_bytes()); // Bits per sample
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_len.to_le_bytes());
+        for sample in samples {
+            wav.extend_from_slice(&sample.to_le_bytes());
+        }
+        wav
+    }
+
+    #[test]
+    fn header_only_file_fails_to_load() {
+        // A header with an empty data chunk.
+        let path = write_temp_file("header-only.wav", &pcm_wav(44100, &[]));
+
+        assert!(try_load(&path).is_err());
+    }
+
+    #[test]
+    fn seeking_starts_at_the_exact_sample() {
+        // 20 seconds at 1kHz, every sample holds its own index.
+        let samples: Vec<i16> = (0..20_000).collect();
+        let path = write_temp_file("ramp.wav", &pcm_wav(1000, &samples));
+
+        let mut audio_engine_state = AudioEngineState::default();
+        let mut decoder = None;
+        let result = load_file(&path, &mut audio_engine_state, &mut decoder, 12_500);
+        std::fs::remove_file(&path).unwrap();
+        result.unwrap();
+
+        let seek_ts = audio_engine_state.track_info.unwrap().seek_ts;
+        let first_written = loop {
+            let reader = audio_engine_state.reader.as_mut().unwrap();
+            let packet = reader.next_packet().unwrap();
+            let decoded = decoder.as_mut().unwrap().decode(&packet).unwrap();
+            let skipped_frames = frames_before_seek(
+                packet.ts(),
+                seek_ts,
+                audio_engine_state.time_base,
+                decoded.spec().rate,
+            );
+            if skipped_frames < decoded.frames() {
+                break trim_start(&decoded, skipped_frames).chan(0)[0];
+            }
+        };
+
+        let index = (first_written * 32768.0).round();
+        assert!((index - 12_500.0).abs() <= 1.0, "started at sample {index}");
+    }
+
+    #[test]
+    fn output_is_reopened_when_the_stream_changes_spec() {
+        use symphonia::core::audio::Channels;
+
+        let stereo = Channels::FRONT_LEFT | Channels::FRONT_RIGHT;
+        let cd = SignalSpec::new(44100, stereo);
+        let open = OutputSpec {
+            spec: cd,
+            duration: 1152,
+        };
+
+        // Nothing open yet.
+        assert!(needs_reopen(None, cd, 1152));
+        // Same spec, in packets that fit.
+        assert!(!needs_reopen(Some(&open), cd, 1152));
+        assert!(!needs_reopen(Some(&open), cd, 576));
+        // Packets bigger than the output was opened for.
+        assert!(needs_reopen(Some(&open), cd, 4608));
+        // The stream switched to another rate, or to mono.
+        assert!(needs_reopen(
+            Some(&open),
+            SignalSpec::new(48000, stereo),
+            1152
+        ));
+        assert!(needs_reopen(
+            Some(&open),
+            SignalSpec::new(44100, Channels::FRONT_LEFT),
+            1152
+        ));
+    }
+}
@@ -0,0 +1,511 @@
+//! Playback controls from the desktop. On Linux the player is published on the session bus as
+//! an MPRIS media player, which media keys, the GNOME and KDE panels and tools like `playerctl`
+//! drive. Elsewhere there's nothing comparable wired up yet and updates are dropped.
+
+use std::path::PathBuf;
+
+/// A request from the desktop, run by the UI like the matching keyboard shortcut.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MediaCommand {
+    Play,
+    Pause,
+    PlayPause,
+    Stop,
+    Next,
+    Previous,
+    /// Seek relative to the current position, in seconds.
+    SeekBy(f64),
+    /// Seek to a position in the track, in seconds.
+    SeekTo(f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum MediaStatus {
+    #[default]
+    Stopped,
+    Playing,
+    Paused,
+}
+
+/// What the desktop is shown of playback.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MediaState {
+    pub status: MediaStatus,
+    pub track: Option<MediaTrack>,
+    /// Position in the track, in microseconds as MPRIS counts them.
+    pub position_us: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaTrack {
+    pub path: PathBuf,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// Length in microseconds, when known.
+    pub length_us: Option<i64>,
+}
+
+// What the UI thread hands the desktop's thread.
+enum Update {
+    State(MediaState),
+    // The position jumped, to the one given in microseconds.
+    Seeked(i64),
+}
+
+#[derive(Default)]
+pub struct MediaControls {
+    // What was last shown, `None` before the first update.
+    shown: Option<MediaState>,
+    is_seeked: bool,
+    // `None` until started, or where there's nothing to publish to.
+    service: Option<platform::Service>,
+}
+
+impl MediaControls {
+    /// Starts publishing playback to the desktop, on a thread of its own.
+    pub fn start() -> Self {
+        Self {
+            service: platform::Service::start(),
+            ..Default::default()
+        }
+    }
+
+    /// The position jumped rather than moving on with playback, e.g. after a seek. Told to the
+    /// desktop with the next update.
+    pub fn seeked(&mut self) {
+        self.is_seeked = true;
+    }
+
+    /// Call every frame from the UI thread. Returns the commands that came in since the last
+    /// call.
+    pub fn update(&mut self, state: MediaState) -> Vec<MediaCommand> {
+        let Some(service) = &self.service else {
+            return Vec::new();
+        };
+
+        let position_us = state.position_us;
+        if self.shown.as_ref() != Some(&state) {
+            self.shown = Some(state.clone());
+            service.send(Update::State(state));
+        }
+        if std::mem::take(&mut self.is_seeked) {
+            service.send(Update::Seeked(position_us));
+        }
+
+        service.commands()
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{MediaCommand, MediaState, MediaStatus, MediaTrack, Update};
+    use crate::app::cover_art::find_folder_cover;
+    use std::collections::HashMap;
+    use std::hash::{Hash, Hasher};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+    use std::sync::mpsc::{channel, Receiver, Sender};
+    use zbus::object_server::SignalEmitter;
+    use zbus::zvariant::{ObjectPath, Value};
+
+    const BUS_NAME: &str = "org.mpris.MediaPlayer2.music_player";
+    const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+    // Track id MPRIS reserves for when nothing is selected.
+    const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+    pub struct Service {
+        update_tx: Sender<Update>,
+        command_rx: Receiver<MediaCommand>,
+    }
+
+    impl Service {
+        pub fn start() -> Option<Self> {
+            let (update_tx, update_rx) = channel();
+            let (command_tx, command_rx) = channel();
+            let result = std::thread::Builder::new()
+                .name("mpris".to_string())
+                .spawn(move || serve(update_rx, command_tx));
+            if let Err(err) = result {
+                tracing::warn!("Couldn't start the media controls thread: {}", err);
+                return None;
+            }
+
+            Some(Self {
+                update_tx,
+                command_rx,
+            })
+        }
+
+        // Updates are dropped once the thread is gone, e.g. there's no session bus.
+        pub(super) fn send(&self, update: Update) {
+            let _ = self.update_tx.send(update);
+        }
+
+        pub fn commands(&self) -> Vec<MediaCommand> {
+            self.command_rx.try_iter().collect()
+        }
+    }
+
+    // Publishes the player and keeps it up to date until the UI goes away. D-Bus calls are
+    // answered on zbus' own threads and passed back to the UI as commands.
+    fn serve(update_rx: Receiver<Update>, command_tx: Sender<MediaCommand>) {
+        let connection = zbus::blocking::connection::Builder::session()
+            .and_then(|builder| builder.name(BUS_NAME))
+            .and_then(|builder| builder.serve_at(OBJECT_PATH, Root))
+            .and_then(|builder| {
+                builder.serve_at(
+                    OBJECT_PATH,
+                    PlayerInterface {
+                        state: MediaState::default(),
+                        art_url: None,
+                        command_tx,
+                    },
+                )
+            })
+            .and_then(|builder| builder.build());
+        // The connection has to outlive the loop for the player to stay published.
+        let player = connection
+            .as_ref()
+            .map_err(Clone::clone)
+            .and_then(|connection| {
+                connection
+                    .object_server()
+                    .interface::<_, PlayerInterface>(OBJECT_PATH)
+            });
+        let player = match player {
+            Ok(player) => player,
+            Err(err) => {
+                tracing::warn!("Media controls aren't available: {}", err);
+                return;
+            }
+        };
+
+        for update in update_rx {
+            let result = match update {
+                Update::State(state) => {
+                    let mut iface = player.get_mut();
+                    let is_status_changed = iface.state.status != state.status;
+                    let is_track_changed = iface.state.track != state.track;
+                    if is_track_changed {
+                        iface.art_url = state
+                            .track
+                            .as_ref()
+                            .and_then(|track| find_folder_cover(&track.path))
+                            .map(|cover| file_url(&cover));
+                    }
+                    iface.state = state;
+
+                    let emitter = player.signal_emitter();
+                    zbus::block_on(async {
+                        if is_status_changed {
+                            iface.playback_status_changed(emitter).await?;
+                        }
+                        if is_track_changed {
+                            iface.metadata_changed(emitter).await?;
+                            iface.can_play_changed(emitter).await?;
+                            iface.can_seek_changed(emitter).await?;
+                        }
+                        Ok::<_, zbus::Error>(())
+                    })
+                }
+                Update::Seeked(position_us) => zbus::block_on(PlayerInterface::seeked(
+                    player.signal_emitter(),
+                    position_us,
+                )),
+            };
+
+            if let Err(err) = result {
+                tracing::warn!("Couldn't update the media controls: {}", err);
+            }
+        }
+    }
+
+    // The `org.mpris.MediaPlayer2` interface, about the app rather than playback.
+    struct Root;
+
+    #[zbus::interface(name = "org.mpris.MediaPlayer2")]
+    impl Root {
+        fn raise(&self) {}
+
+        fn quit(&self) {}
+
+        #[zbus(property)]
+        fn can_raise(&self) -> bool {
+            false
+        }
+
+        #[zbus(property)]
+        fn can_quit(&self) -> bool {
+            false
+        }
+
+        #[zbus(property)]
+        fn has_track_list(&self) -> bool {
+            false
+        }
+
+        #[zbus(property)]
+        fn identity(&self) -> String {
+            "Music Player".to_string()
+        }
+
+        #[zbus(property)]
+        fn supported_uri_schemes(&self) -> Vec<String> {
+            Vec::new()
+        }
+
+        #[zbus(property)]
+        fn supported_mime_types(&self) -> Vec<String> {
+            Vec::new()
+        }
+    }
+
+    struct PlayerInterface {
+        state: MediaState,
+        // Cover of the track, looked up when it changes.
+        art_url: Option<String>,
+        command_tx: Sender<MediaCommand>,
+    }
+
+    impl PlayerInterface {
+        // Commands are dropped once the UI is gone.
+        fn send(&self, command: MediaCommand) {
+            let _ = self.command_tx.send(command);
+        }
+    }
+
+    #[zbus::interface(name = "org.mpris.MediaPlayer2.Player")]
+    impl PlayerInterface {
+        fn next(&self) {
+            self.send(MediaCommand::Next);
+        }
+
+        fn previous(&self) {
+            self.send(MediaCommand::Previous);
+        }
+
+        fn pause(&self) {
+            self.send(MediaCommand::Pause);
+        }
+
+        fn play_pause(&self) {
+            self.send(MediaCommand::PlayPause);
+        }
+
+        fn stop(&self) {
+            self.send(MediaCommand::Stop);
+        }
+
+        fn play(&self) {
+            self.send(MediaCommand::Play);
+        }
+
+        fn seek(&self, offset: i64) {
+            self.send(MediaCommand::SeekBy(offset as f64 / 1e6));
+        }
+
+        // Ignored when it's meant for another track than the one playing, or past its end.
+        fn set_position(&self, track_id: ObjectPath<'_>, position: i64) {
+            let Some(track) = &self.state.track else {
+                return;
+            };
+            let is_in_track = position >= 0
+                && track
+                    .length_us
+                    .is_some_and(|length_us| position <= length_us);
+            if track_id.as_str() == track_id_of(track) && is_in_track {
+                self.send(MediaCommand::SeekTo(position as f64 / 1e6));
+            }
+        }
+
+        fn open_uri(&self, _uri: &str) -> zbus::fdo::Result<()> {
+            Err(zbus::fdo::Error::NotSupported(
+                "Opening URIs isn't supported".to_string(),
+            ))
+        }
+
+        #[zbus(signal)]
+        async fn seeked(emitter: &SignalEmitter<'_>, position: i64) -> zbus::Result<()>;
+
+        #[zbus(property)]
+        fn playback_status(&self) -> String {
+            match self.state.status {
+                MediaStatus::Stopped => "Stopped",
+                MediaStatus::Playing => "Playing",
+                MediaStatus::Paused => "Paused",
+            }
+            .to_string()
+        }
+
+        #[zbus(property)]
+        fn rate(&self) -> f64 {
+            1.0
+        }
+
+        #[zbus(property)]
+        fn minimum_rate(&self) -> f64 {
+            1.0
+        }
+
+        #[zbus(property)]
+        fn maximum_rate(&self) -> f64 {
+            1.0
+        }
+
+        #[zbus(property)]
+        fn metadata(&self) -> HashMap<String, Value<'static>> {
+            metadata(self.state.track.as_ref(), self.art_url.as_deref())
+        }
+
+        // Not announced as it changes, clients ask for it and count on from there.
+        #[zbus(property(emits_changed_signal = "false"))]
+        fn position(&self) -> i64 {
+            self.state.position_us
+        }
+
+        #[zbus(property)]
+        fn can_go_next(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn can_go_previous(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn can_play(&self) -> bool {
+            self.state.track.is_some()
+        }
+
+        #[zbus(property)]
+        fn can_pause(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn can_seek(&self) -> bool {
+            self.state
+                .track
+                .as_ref()
+                .is_some_and(|track| track.length_us.is_some())
+        }
+
+        #[zbus(property(emits_changed_signal = "const"))]
+        fn can_control(&self) -> bool {
+            true
+        }
+    }
+
+    fn metadata(
+        track: Option<&MediaTrack>,
+        art_url: Option<&str>,
+    ) -> HashMap<String, Value<'static>> {
+        let mut metadata = HashMap::new();
+        let Some(track) = track else {
+            metadata.insert(
+                "mpris:trackid".to_string(),
+                Value::from(ObjectPath::from_static_str_unchecked(NO_TRACK)),
+            );
+            return metadata;
+        };
+
+        metadata.insert(
+            "mpris:trackid".to_string(),
+            Value::from(ObjectPath::try_from(track_id_of(track)).unwrap()),
+        );
+        metadata.insert("xesam:url".to_string(), Value::from(file_url(&track.path)));
+        if let Some(title) = &track.title {
+            metadata.insert("xesam:title".to_string(), Value::from(title.clone()));
+        }
+        if let Some(artist) = &track.artist {
+            metadata.insert(
+                "xesam:artist".to_string(),
+                Value::from(vec![artist.clone()]),
+            );
+        }
+        if let Some(album) = &track.album {
+            metadata.insert("xesam:album".to_string(), Value::from(album.clone()));
+        }
+        if let Some(length_us) = track.length_us {
+            metadata.insert("mpris:length".to_string(), Value::from(length_us));
+        }
+        if let Some(art_url) = art_url {
+            metadata.insert("mpris:artUrl".to_string(), Value::from(art_url.to_string()));
+        }
+        metadata
+    }
+
+    // Object paths only allow letters, digits and underscores, so the track is named by a hash
+    // of its path.
+    fn track_id_of(track: &MediaTrack) -> String {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        track.path.hash(&mut hasher);
+        format!("/org/mpris/MediaPlayer2/Track/_{:016x}", hasher.finish())
+    }
+
+    // `file://` URL of an absolute path, with everything but unreserved characters escaped.
+    fn file_url(path: &Path) -> String {
+        let mut url = "file://".to_string();
+        for byte in path.as_os_str().as_bytes() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                    url.push(*byte as char)
+                }
+                _ => url.push_str(&format!("%{byte:02X}")),
+            }
+        }
+        url
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::path::PathBuf;
+
+        #[test]
+        fn tracks_are_described_with_valid_ids_and_urls() {
+            let track = MediaTrack {
+                path: PathBuf::from("/music/AC DC/01 #1 Hit?.flac"),
+                title: Some("Hit".to_string()),
+                artist: Some("AC DC".to_string()),
+                album: None,
+                length_us: Some(180_000_000),
+            };
+
+            let described = metadata(Some(&track), None);
+            assert!(ObjectPath::try_from(track_id_of(&track)).is_ok());
+            assert_eq!(
+                described["xesam:url"],
+                Value::from("file:///music/AC%20DC/01%20%231%20Hit%3F.flac")
+            );
+            assert_eq!(described["xesam:artist"], Value::from(vec!["AC DC"]));
+            assert!(!described.contains_key("xesam:album"));
+
+            assert_eq!(
+                metadata(None, None)["mpris:trackid"],
+                Value::from(ObjectPath::try_from(NO_TRACK).unwrap())
+            );
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use super::{MediaCommand, Update};
+
+    pub struct Service;
+
+    impl Service {
+        pub fn start() -> Option<Self> {
+            None
+        }
+
+        pub(super) fn send(&self, _update: Update) {}
+
+        pub fn commands(&self) -> Vec<MediaCommand> {
+            Vec::new()
+        }
+    }
+}
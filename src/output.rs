@@ -12,15 +12,26 @@ use std::result;
 use symphonia::core::audio::{AudioBufferRef, SignalSpec};
 use symphonia::core::units::Duration;
 
+use crate::dsp::SignalChain;
+
 pub trait AudioOutput {
     //fn write(&mut self, decoded: AudioBufferRef<'_>) -> Result<()>;
     fn write(
         &mut self,
         decoded: AudioBufferRef<'_>,
         gui_ring_buf_producer: &rb::Producer<f32>,
-        volume: f32,
+        signal_chain: &mut SignalChain,
     ) -> Result<()>;
     fn flush(&mut self);
+    /// Blocks until everything written so far has been handed to the device, or the timeout
+    /// elapses. Unlike `flush`, the stream keeps running.
+    fn drain(&mut self, timeout: std::time::Duration);
+    /// Name of the audio backend the output was opened on.
+    fn host_name(&self) -> &str;
+    /// Name of the device the output plays on.
+    fn device_name(&self) -> &str;
+    /// Sample rate and channel count the device is actually fed with.
+    fn device_config(&self) -> (u32, usize);
 }
 
 #[allow(dead_code)]
@@ -175,54 +186,116 @@ mod pulseaudio {
 
 #[cfg(not(target_os = "linux"))]
 mod cpal {
+    use crate::dsp::SignalChain;
     use crate::resampler::Resampler;
 
     use super::{AudioOutput, AudioOutputError, Result};
 
     use symphonia::core::audio::{AudioBufferRef, RawSample, SampleBuffer, SignalSpec};
-    use symphonia::core::conv::{ConvertibleSample, IntoSample};
+    use symphonia::core::conv::{ConvertibleSample, FromSample, IntoSample};
     use symphonia::core::units::Duration;
 
     use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
     use rb::*;
 
-    use log::{error, info};
+    use log::{error, info, warn};
 
     pub struct CpalAudioOutput;
 
     trait AudioOutputSample:
-        cpal::Sample + ConvertibleSample + IntoSample<f32> + RawSample + std::marker::Send + 'static
+        cpal::Sample
+        + ConvertibleSample
+        + IntoSample<f32>
+        + FromSample<f32>
+        + RawSample
+        + std::marker::Send
+        + 'static
     {
-        fn mul(&self, n: f32) -> Self;
     }
 
-    impl AudioOutputSample for f32 {
-        fn mul(&self, n: f32) -> Self {
-            self * n
+    impl AudioOutputSample for f32 {}
+
+    impl AudioOutputSample for i16 {}
+
+    impl AudioOutputSample for u16 {}
+
+    pub fn available_hosts() -> Vec<&'static str> {
+        cpal::available_hosts()
+            .into_iter()
+            .map(|host_id| host_id.name())
+            .collect()
+    }
+
+    pub fn default_host() -> &'static str {
+        cpal::default_host().id().name()
+    }
+
+    pub fn available_devices(host_name: Option<&str>) -> Vec<String> {
+        match open_host(host_name).output_devices() {
+            Ok(devices) => devices.filter_map(|device| device.name().ok()).collect(),
+            Err(err) => {
+                warn!("failed to list audio output devices: {}", err);
+                Vec::new()
+            }
         }
     }
 
-    // TODO - I don't think this will actually work as intended due to truncation?
-    impl AudioOutputSample for i16 {
-        fn mul(&self, n: f32) -> Self {
-            (*self as f32 * n) as i16
+    // Falls back to the host's default device when the named one isn't connected.
+    fn open_device(host: &cpal::Host, device_name: Option<&str>) -> Option<cpal::Device> {
+        if let Some(device_name) = device_name {
+            let device = host.output_devices().ok().and_then(|mut devices| {
+                devices.find(|device| device.name().is_ok_and(|name| name == device_name))
+            });
+
+            if device.is_some() {
+                return device;
+            }
+            warn!(
+                "audio device {} is not available, using the default",
+                device_name
+            );
         }
+
+        host.default_output_device()
     }
 
-    // TODO - I don't think this will actually work as intended due to truncation?
-    impl AudioOutputSample for u16 {
-        fn mul(&self, n: f32) -> Self {
-            (*self as f32 * n) as u16
+    // Falls back to the default host when the named one isn't available on this machine.
+    fn open_host(host_name: Option<&str>) -> cpal::Host {
+        let Some(host_name) = host_name else {
+            return cpal::default_host();
+        };
+
+        let host_id = cpal::available_hosts()
+            .into_iter()
+            .find(|host_id| host_id.name() == host_name);
+
+        match host_id.map(cpal::host_from_id) {
+            Some(Ok(host)) => host,
+            Some(Err(err)) => {
+                warn!("failed to open audio host {}: {}", host_name, err);
+                cpal::default_host()
+            }
+            None => {
+                warn!(
+                    "audio host {} is not available, using the default",
+                    host_name
+                );
+                cpal::default_host()
+            }
         }
     }
 
     impl CpalAudioOutput {
-        pub fn try_open(spec: SignalSpec, duration: Duration) -> Result<Box<dyn AudioOutput>> {
-            // Get default host.
-            let host = cpal::default_host();
+        pub fn try_open(
+            spec: SignalSpec,
+            duration: Duration,
+            host_name: Option<&str>,
+            device_name: Option<&str>,
+        ) -> Result<Box<dyn AudioOutput>> {
+            let host = open_host(host_name);
+            info!("using audio host {}", host.id().name());
 
-            // Get the default audio output device.
-            let device = match host.default_output_device() {
+            let device = match open_device(&host, device_name) {
                 Some(device) => device,
                 _ => {
                     error!("failed to get default audio output device");
@@ -241,13 +314,13 @@ mod cpal {
             // Select proper playback routine based on sample format.
             match config.sample_format() {
                 cpal::SampleFormat::F32 => {
-                    CpalAudioOutputImpl::<f32>::try_open(spec, duration, &device)
+                    CpalAudioOutputImpl::<f32>::try_open(spec, duration, &host, &device)
                 }
                 cpal::SampleFormat::I16 => {
-                    CpalAudioOutputImpl::<i16>::try_open(spec, duration, &device)
+                    CpalAudioOutputImpl::<i16>::try_open(spec, duration, &host, &device)
                 }
                 cpal::SampleFormat::U16 => {
-                    CpalAudioOutputImpl::<u16>::try_open(spec, duration, &device)
+                    CpalAudioOutputImpl::<u16>::try_open(spec, duration, &host, &device)
                 }
                 _ => panic!("Unsupported sample format"),
             }
@@ -258,10 +331,17 @@ mod cpal {
     where
         T: AudioOutputSample,
     {
+        ring_buf: rb::SpscRb<T>,
         ring_buf_producer: rb::Producer<T>,
         sample_buf: SampleBuffer<T>,
         stream: cpal::Stream,
         resampler: Option<Resampler<T>>,
+        num_channels: usize,
+        // Scratch space for running the signal chain in f32, reused between writes.
+        processed: Vec<f32>,
+        host_name: &'static str,
+        device_name: String,
+        device_config: (u32, usize),
     }
 
     impl<T: cpal::SizedSample + AudioOutputSample> CpalAudioOutputImpl<T>
@@ -271,6 +351,7 @@ mod cpal {
         pub fn try_open(
             spec: SignalSpec,
             duration: Duration,
+            host: &cpal::Host,
             device: &cpal::Device,
         ) -> Result<Box<dyn AudioOutput>> {
             let num_channels = spec.channels.count();
@@ -341,10 +422,16 @@ mod cpal {
             };
 
             Ok(Box::new(CpalAudioOutputImpl {
+                ring_buf,
                 ring_buf_producer,
                 sample_buf,
                 stream,
                 resampler,
+                num_channels,
+                processed: Vec::new(),
+                host_name: host.id().name(),
+                device_name: device.name().unwrap_or_default(),
+                device_config: (config.sample_rate.0, config.channels as usize),
             }))
         }
     }
@@ -357,14 +444,14 @@ mod cpal {
             &mut self,
             decoded: AudioBufferRef<'_>,
             gui_ring_buf_producer: &rb::Producer<f32>,
-            volume: f32,
+            signal_chain: &mut SignalChain,
         ) -> Result<()> {
             // Do nothing if there are no audio frames.
             if decoded.frames() == 0 {
                 return Ok(());
             }
 
-            let mut samples = if let Some(resampler) = &mut self.resampler {
+            let samples = if let Some(resampler) = &mut self.resampler {
                 // Resampling is required. The resampler will return interleaved samples in the
                 // correct sample format.
                 match resampler.resample(decoded) {
@@ -377,20 +464,25 @@ mod cpal {
                 self.sample_buf.samples()
             };
 
+            self.processed.clear();
+            self.processed
+                .extend(samples.iter().map(|s| s.to_sample::<f32>()));
+
             // Write all samples to the ring buffer.
-            let _written_count_to_scope = gui_ring_buf_producer.write(
-                &samples
-                    .iter()
-                    .map(|s| s.to_sample::<f32>())
-                    .collect::<Vec<f32>>(),
-            );
+            let _written_count_to_scope = gui_ring_buf_producer.write(&self.processed);
+
+            signal_chain.process(&mut self.processed, self.num_channels, self.device_config.0);
+
+            let output_samples = self
+                .processed
+                .iter()
+                .map(|s| <T as FromSample<f32>>::from_sample(*s))
+                .collect::<Vec<T>>();
+            let mut output_samples = &output_samples[..];
 
             // Write all samples to the ring buffer.
-            while let Some(written) = self
-                .ring_buf_producer
-                .write_blocking(&samples.iter().map(|s| s.mul(volume)).collect::<Vec<_>>())
-            {
-                samples = &samples[written..];
+            while let Some(written) = self.ring_buf_producer.write_blocking(output_samples) {
+                output_samples = &output_samples[written..];
             }
 
             Ok(())
@@ -410,6 +502,34 @@ mod cpal {
             // Flush is best-effort, ignore the returned result.
             let _ = self.stream.pause();
         }
+
+        fn drain(&mut self, timeout: std::time::Duration) {
+            if let Some(resampler) = &mut self.resampler {
+                let mut remaining_samples = resampler.flush().unwrap_or_default();
+
+                while let Some(written) = self.ring_buf_producer.write_blocking(remaining_samples) {
+                    remaining_samples = &remaining_samples[written..];
+                }
+            }
+
+            let deadline = std::time::Instant::now() + timeout;
+
+            while !self.ring_buf.is_empty() && std::time::Instant::now() < deadline {
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            }
+        }
+
+        fn host_name(&self) -> &str {
+            self.host_name
+        }
+
+        fn device_name(&self) -> &str {
+            &self.device_name
+        }
+
+        fn device_config(&self) -> (u32, usize) {
+            self.device_config
+        }
     }
 }
 
@@ -421,6 +541,28 @@ pub fn try_open(spec: SignalSpec, duration: Duration) -> Result<Box<dyn AudioOut
 */
 
 #[cfg(not(target_os = "linux"))]
-pub fn try_open(spec: SignalSpec, duration: Duration) -> Result<Box<dyn AudioOutput>> {
-    cpal::CpalAudioOutput::try_open(spec, duration)
+pub fn try_open(
+    spec: SignalSpec,
+    duration: Duration,
+    host_name: Option<&str>,
+    device_name: Option<&str>,
+) -> Result<Box<dyn AudioOutput>> {
+    cpal::CpalAudioOutput::try_open(spec, duration, host_name, device_name)
+}
+
+/// Names of the audio backends that can be selected on this machine.
+#[cfg(not(target_os = "linux"))]
+pub fn available_hosts() -> Vec<&'static str> {
+    cpal::available_hosts()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn default_host() -> &'static str {
+    cpal::default_host()
+}
+
+/// Names of the output devices on an audio backend, `None` for the default backend.
+#[cfg(not(target_os = "linux"))]
+pub fn available_devices(host_name: Option<&str>) -> Vec<String> {
+    cpal::available_devices(host_name)
 }
@@ -0,0 +1,138 @@
+//! Auditioning a track on a second output while the main one keeps playing.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use rb::{SpscRb, RB};
+use symphonia::core::codecs::CODEC_TYPE_NULL;
+use symphonia::core::errors::{Error, Result};
+use symphonia::core::formats::{SeekMode, SeekTo};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::probe::Hint;
+
+use crate::dsp::SignalChain;
+use crate::output;
+
+/// A few seconds of a track, decoded and played on its own output stream in the background.
+pub struct Preview {
+    pub path: PathBuf,
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl Preview {
+    pub fn start(path: PathBuf, length: std::time::Duration, output_host: Option<String>) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let handle = {
+            let path = path.clone();
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                if let Err(err) = play_preview(&path, length, output_host.as_deref(), &stop) {
+                    tracing::warn!("couldn't preview {:?}: {}", path, err);
+                }
+            })
+        };
+
+        Self { path, stop, handle }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Preview {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn play_preview(
+    path: &PathBuf,
+    length: std::time::Duration,
+    output_host: Option<&str>,
+    stop: &AtomicBool,
+) -> Result<()> {
+    let source = Box::new(std::fs::File::open(path)?);
+    let mss = MediaSourceStream::new(source, Default::default());
+    let mut reader = symphonia::default::get_probe()
+        .format(&Hint::new(), mss, &Default::default(), &Default::default())?
+        .format;
+
+    let track = reader
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or(Error::Unsupported("no supported audio tracks"))?;
+    let track_id = track.id;
+    let codec_params = track.codec_params.clone();
+
+    let mut decoder = symphonia::default::get_codecs().make(&codec_params, &Default::default())?;
+
+    // Start a third of the way in, where most tracks have gotten going.
+    if let Some(n_frames) = codec_params.n_frames {
+        let ts = codec_params.start_ts + n_frames / 3;
+        reader.seek(SeekMode::Coarse, SeekTo::TimeStamp { ts, track_id })?;
+        decoder.reset();
+    }
+
+    // The preview doesn't show up in the scope, its samples are just dropped.
+    let scope_ring_buf = SpscRb::new(1);
+    let scope_producer = scope_ring_buf.producer();
+    let mut signal_chain = SignalChain::default();
+
+    let mut audio_output = None;
+    let started = std::time::Instant::now();
+
+    while !stop.load(Ordering::Relaxed) && started.elapsed() < length {
+        let packet = match reader.next_packet() {
+            Ok(packet) => packet,
+            // End of the track.
+            Err(_) => break,
+        };
+
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(Error::DecodeError(err)) => {
+                tracing::warn!("preview decode error: {}", err);
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+
+        if audio_output.is_none() {
+            let spec = *decoded.spec();
+            let duration = decoded.capacity() as u64;
+            audio_output = Some(
+                output::try_open(spec, duration, output_host, None)
+                    .map_err(|_| Error::Unsupported("couldn't open an output for the preview"))?,
+            );
+        }
+
+        if let Some(audio_output) = audio_output.as_mut() {
+            if audio_output
+                .write(decoded, &scope_producer, &mut signal_chain)
+                .is_err()
+            {
+                break;
+            }
+        }
+    }
+
+    if let Some(audio_output) = audio_output.as_mut() {
+        audio_output.flush();
+    }
+
+    Ok(())
+}
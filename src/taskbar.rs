@@ -0,0 +1,179 @@
+//! Playback progress on the window's taskbar button on Windows, or its dock icon on macOS.
+//! Elsewhere there's nothing comparable and updates are dropped.
+
+/// What the taskbar button or dock icon shows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TaskbarState {
+    Hidden,
+    /// Fraction of the track played so far.
+    Playing(f32),
+    Paused(f32),
+}
+
+impl TaskbarState {
+    // Progress in steps of 0.1%, so the OS isn't called every frame for changes nobody sees.
+    fn rounded(self) -> Self {
+        let round = |progress: f32| (progress.clamp(0.0, 1.0) * 1000.0).round() / 1000.0;
+        match self {
+            TaskbarState::Hidden => TaskbarState::Hidden,
+            TaskbarState::Playing(progress) => TaskbarState::Playing(round(progress)),
+            TaskbarState::Paused(progress) => TaskbarState::Paused(round(progress)),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TaskbarProgress {
+    // What was last shown, `None` before the first update.
+    shown: Option<TaskbarState>,
+    platform: platform::Taskbar,
+}
+
+impl TaskbarProgress {
+    /// Call every frame from the UI thread.
+    pub fn update(&mut self, frame: &eframe::Frame, state: TaskbarState) {
+        let state = state.rounded();
+        if self.shown == Some(state) {
+            return;
+        }
+
+        self.shown = Some(state);
+        self.platform.show(frame, state);
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::TaskbarState;
+    use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::System::Com::{CoCreateInstance, CLSCTX_INPROC_SERVER};
+    use windows::Win32::UI::Shell::{
+        ITaskbarList3, TaskbarList, TBPF_NOPROGRESS, TBPF_NORMAL, TBPF_PAUSED,
+    };
+
+    #[derive(Default)]
+    pub struct Taskbar {
+        list: Option<ITaskbarList3>,
+        is_unavailable: bool,
+    }
+
+    impl Taskbar {
+        pub fn show(&mut self, frame: &eframe::Frame, state: TaskbarState) {
+            let Ok(window_handle) = frame.window_handle() else {
+                return;
+            };
+            let RawWindowHandle::Win32(window_handle) = window_handle.as_raw() else {
+                return;
+            };
+            let hwnd = HWND(window_handle.hwnd.get() as _);
+
+            let Some(list) = self.list() else {
+                return;
+            };
+
+            // Safety: the window outlives the frame it was handed to us with, and COM calls
+            // are made from the UI thread winit initialized COM on.
+            let result = unsafe {
+                match state {
+                    TaskbarState::Hidden => list.SetProgressState(hwnd, TBPF_NOPROGRESS),
+                    TaskbarState::Playing(progress) | TaskbarState::Paused(progress) => {
+                        let flags = if matches!(state, TaskbarState::Paused(_)) {
+                            TBPF_PAUSED
+                        } else {
+                            TBPF_NORMAL
+                        };
+                        list.SetProgressState(hwnd, flags).and_then(|_| {
+                            list.SetProgressValue(hwnd, (progress * 1000.0) as u64, 1000)
+                        })
+                    }
+                }
+            };
+
+            if let Err(err) = result {
+                tracing::warn!("Couldn't update the taskbar progress: {}", err);
+            }
+        }
+
+        // Created on first use. Some shells (e.g. Windows Server Core) have no taskbar, that's
+        // only logged once.
+        fn list(&mut self) -> Option<&ITaskbarList3> {
+            if self.list.is_none() && !self.is_unavailable {
+                let list = unsafe {
+                    CoCreateInstance::<_, ITaskbarList3>(&TaskbarList, None, CLSCTX_INPROC_SERVER)
+                        .and_then(|list| list.HrInit().map(|_| list))
+                };
+                match list {
+                    Ok(list) => self.list = Some(list),
+                    Err(err) => {
+                        tracing::warn!("Taskbar progress isn't available: {}", err);
+                        self.is_unavailable = true;
+                    }
+                }
+            }
+
+            self.list.as_ref()
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::TaskbarState;
+    use objc2_app_kit::NSApplication;
+    use objc2_foundation::{MainThreadMarker, NSString};
+
+    #[derive(Default)]
+    pub struct Taskbar;
+
+    impl Taskbar {
+        // The dock has no progress bar of its own, the badge shows the percentage instead.
+        #[allow(unused_unsafe)]
+        pub fn show(&mut self, _frame: &eframe::Frame, state: TaskbarState) {
+            let Some(mtm) = MainThreadMarker::new() else {
+                return;
+            };
+
+            let badge = match state {
+                TaskbarState::Hidden => None,
+                TaskbarState::Playing(progress) => {
+                    Some(NSString::from_str(&format!("{:.0}%", progress * 100.0)))
+                }
+                TaskbarState::Paused(_) => Some(NSString::from_str("Paused")),
+            };
+
+            let app = NSApplication::sharedApplication(mtm);
+            // Safety: called on the main thread, as `mtm` proves.
+            unsafe { app.dockTile().setBadgeLabel(badge.as_deref()) };
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+mod platform {
+    use super::TaskbarState;
+
+    #[derive(Default)]
+    pub struct Taskbar;
+
+    impl Taskbar {
+        pub fn show(&mut self, _frame: &eframe::Frame, _state: TaskbarState) {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_is_rounded_to_tenths_of_a_percent() {
+        assert_eq!(
+            TaskbarState::Playing(0.12345).rounded(),
+            TaskbarState::Playing(0.123)
+        );
+        assert_eq!(
+            TaskbarState::Paused(1.5).rounded(),
+            TaskbarState::Paused(1.0)
+        );
+    }
+}
@@ -0,0 +1,316 @@
+//! An icon in the system tray with a menu to control playback and show or hide the window, so
+//! the player can keep going with its window closed. Only built with the `tray` feature, which
+//! on Linux needs GTK and a desktop with app indicator support.
+
+use eframe::egui;
+
+/// Something picked from the tray menu, or a click on the icon.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrayCommand {
+    PlayPause,
+    Next,
+    Previous,
+    ToggleWindow,
+    Quit,
+}
+
+#[derive(Default)]
+pub struct Tray {
+    // Whether the menu offers to play or to pause, `None` before the first update.
+    shown_playing: Option<bool>,
+    // `None` until started, or when the tray isn't available.
+    menu: Option<platform::TrayMenu>,
+}
+
+impl Tray {
+    /// Adds the icon to the tray. Call on the main thread once the event loop runs, e.g. when
+    /// the app is created.
+    pub fn start(ctx: &egui::Context) -> Self {
+        Self {
+            shown_playing: None,
+            menu: platform::TrayMenu::start(ctx),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.menu.is_some()
+    }
+
+    /// Call every frame from the UI thread. Returns the commands that came in since the last
+    /// call.
+    pub fn update(&mut self, is_playing: bool) -> Vec<TrayCommand> {
+        let Some(menu) = &self.menu else {
+            return Vec::new();
+        };
+
+        if self.shown_playing != Some(is_playing) {
+            self.shown_playing = Some(is_playing);
+            menu.set_playing(is_playing);
+        }
+
+        menu.commands()
+    }
+}
+
+#[cfg(feature = "tray")]
+mod menu {
+    use super::TrayCommand;
+    use eframe::egui;
+    use std::sync::mpsc::{channel, Receiver};
+    use tray_icon::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+    use tray_icon::{
+        Icon, MouseButton, MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent,
+    };
+
+    const PLAY_PAUSE: &str = "play_pause";
+    const NEXT: &str = "next";
+    const PREVIOUS: &str = "previous";
+    const TOGGLE_WINDOW: &str = "toggle_window";
+    const QUIT: &str = "quit";
+
+    // Size of the icon drawn for the tray, in pixels.
+    const ICON_SIZE: u32 = 32;
+
+    pub fn play_pause_label(is_playing: bool) -> &'static str {
+        if is_playing {
+            "Pause"
+        } else {
+            "Play"
+        }
+    }
+
+    /// Builds the icon and its menu. Returns the play/pause item, to keep its label up to date.
+    pub fn build() -> Result<(TrayIcon, MenuItem), Box<dyn std::error::Error>> {
+        let play_pause = MenuItem::with_id(PLAY_PAUSE, play_pause_label(false), true, None);
+        let menu = Menu::new();
+        menu.append_items(&[
+            &play_pause,
+            &MenuItem::with_id(NEXT, "Next", true, None),
+            &MenuItem::with_id(PREVIOUS, "Previous", true, None),
+            &PredefinedMenuItem::separator(),
+            &MenuItem::with_id(TOGGLE_WINDOW, "Show / Hide Window", true, None),
+            &PredefinedMenuItem::separator(),
+            &MenuItem::with_id(QUIT, "Quit", true, None),
+        ])?;
+
+        let tray = TrayIconBuilder::new()
+            .with_menu(Box::new(menu))
+            .with_menu_on_left_click(false)
+            .with_tooltip("Music Player")
+            .with_icon(icon())
+            .build()?;
+        Ok((tray, play_pause))
+    }
+
+    /// Passes menu picks and clicks on the icon to the UI, waking it up as they come in: with
+    /// the window hidden, nothing else would.
+    pub fn forward_events(ctx: &egui::Context) -> Receiver<TrayCommand> {
+        let (command_tx, command_rx) = channel();
+
+        let menu_tx = command_tx.clone();
+        let menu_ctx = ctx.clone();
+        MenuEvent::set_event_handler(Some(move |event: MenuEvent| {
+            let command = match event.id.0.as_str() {
+                PLAY_PAUSE => TrayCommand::PlayPause,
+                NEXT => TrayCommand::Next,
+                PREVIOUS => TrayCommand::Previous,
+                TOGGLE_WINDOW => TrayCommand::ToggleWindow,
+                QUIT => TrayCommand::Quit,
+                _ => return,
+            };
+            let _ = menu_tx.send(command);
+            menu_ctx.request_repaint();
+        }));
+
+        // Clicks aren't reported on Linux, the menu's show/hide item stands in there.
+        let ctx = ctx.clone();
+        TrayIconEvent::set_event_handler(Some(move |event: TrayIconEvent| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                ..
+            } = event
+            {
+                let _ = command_tx.send(TrayCommand::ToggleWindow);
+                ctx.request_repaint();
+            }
+        }));
+
+        command_rx
+    }
+
+    // A white play triangle on a round dark badge, there's no image file to load it from.
+    fn icon() -> Icon {
+        let size = ICON_SIZE as f32;
+        let center = size / 2.0;
+        let mut rgba = Vec::with_capacity((ICON_SIZE * ICON_SIZE * 4) as usize);
+        for y in 0..ICON_SIZE {
+            for x in 0..ICON_SIZE {
+                let (x, y) = (x as f32 + 0.5, y as f32 + 0.5);
+                let is_in_circle = (x - center).powi(2) + (y - center).powi(2) <= center.powi(2);
+                // Pointing right, a third of the icon wide.
+                let is_in_triangle = x >= size * 0.38
+                    && x <= size * 0.72
+                    && (y - center).abs() <= (size * 0.72 - x) * 0.6;
+
+                rgba.extend_from_slice(match (is_in_circle, is_in_triangle) {
+                    (true, true) => &[255, 255, 255, 255],
+                    (true, false) => &[40, 40, 48, 255],
+                    (false, _) => &[0, 0, 0, 0],
+                });
+            }
+        }
+
+        Icon::from_rgba(rgba, ICON_SIZE, ICON_SIZE).expect("Tray icon has the size it says")
+    }
+}
+
+// GTK isn't thread safe and winit doesn't run it, so the icon lives on a thread of its own with
+// a GTK main loop.
+#[cfg(all(feature = "tray", target_os = "linux"))]
+mod platform {
+    use super::menu;
+    use super::TrayCommand;
+    use eframe::egui;
+    use gtk::glib;
+    use std::sync::mpsc::{channel, Receiver, Sender};
+
+    // How often the GTK thread checks for label changes.
+    const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+    pub struct TrayMenu {
+        is_playing_tx: Sender<bool>,
+        command_rx: Receiver<TrayCommand>,
+    }
+
+    impl TrayMenu {
+        pub fn start(ctx: &egui::Context) -> Option<Self> {
+            let (is_playing_tx, is_playing_rx) = channel::<bool>();
+            let (is_started_tx, is_started_rx) = channel();
+            let result = std::thread::Builder::new()
+                .name("tray".to_string())
+                .spawn(move || {
+                    if let Err(err) = gtk::init() {
+                        tracing::warn!("Tray icon isn't available: {}", err);
+                        let _ = is_started_tx.send(false);
+                        return;
+                    }
+                    let (tray, play_pause) = match menu::build() {
+                        Ok(built) => built,
+                        Err(err) => {
+                            tracing::warn!("Tray icon isn't available: {}", err);
+                            let _ = is_started_tx.send(false);
+                            return;
+                        }
+                    };
+                    let _ = is_started_tx.send(true);
+
+                    glib::timeout_add_local(POLL_INTERVAL, move || {
+                        // Kept alive for as long as the loop runs.
+                        let _tray = &tray;
+                        loop {
+                            match is_playing_rx.try_recv() {
+                                Ok(is_playing) => {
+                                    play_pause.set_text(menu::play_pause_label(is_playing))
+                                }
+                                Err(std::sync::mpsc::TryRecvError::Empty) => {
+                                    return glib::ControlFlow::Continue
+                                }
+                                // The app is going away.
+                                Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                                    gtk::main_quit();
+                                    return glib::ControlFlow::Break;
+                                }
+                            }
+                        }
+                    });
+                    gtk::main();
+                });
+            if let Err(err) = result {
+                tracing::warn!("Couldn't start the tray thread: {}", err);
+                return None;
+            }
+
+            // Menu events are only forwarded once the icon is there to send them.
+            if !is_started_rx.recv().unwrap_or(false) {
+                return None;
+            }
+
+            Some(Self {
+                is_playing_tx,
+                command_rx: menu::forward_events(ctx),
+            })
+        }
+
+        pub fn set_playing(&self, is_playing: bool) {
+            let _ = self.is_playing_tx.send(is_playing);
+        }
+
+        pub fn commands(&self) -> Vec<TrayCommand> {
+            self.command_rx.try_iter().collect()
+        }
+    }
+}
+
+// On Windows and macOS the icon belongs to the main thread, where winit already runs the
+// platform's event loop.
+#[cfg(all(feature = "tray", not(target_os = "linux")))]
+mod platform {
+    use super::menu;
+    use super::TrayCommand;
+    use eframe::egui;
+    use std::sync::mpsc::Receiver;
+    use tray_icon::menu::MenuItem;
+    use tray_icon::TrayIcon;
+
+    pub struct TrayMenu {
+        // Removed from the tray when dropped.
+        _tray: TrayIcon,
+        play_pause: MenuItem,
+        command_rx: Receiver<TrayCommand>,
+    }
+
+    impl TrayMenu {
+        pub fn start(ctx: &egui::Context) -> Option<Self> {
+            match menu::build() {
+                Ok((tray, play_pause)) => Some(Self {
+                    _tray: tray,
+                    play_pause,
+                    command_rx: menu::forward_events(ctx),
+                }),
+                Err(err) => {
+                    tracing::warn!("Tray icon isn't available: {}", err);
+                    None
+                }
+            }
+        }
+
+        pub fn set_playing(&self, is_playing: bool) {
+            self.play_pause.set_text(menu::play_pause_label(is_playing));
+        }
+
+        pub fn commands(&self) -> Vec<TrayCommand> {
+            self.command_rx.try_iter().collect()
+        }
+    }
+}
+
+#[cfg(not(feature = "tray"))]
+mod platform {
+    use super::TrayCommand;
+    use eframe::egui;
+
+    pub struct TrayMenu;
+
+    impl TrayMenu {
+        pub fn start(_ctx: &egui::Context) -> Option<Self> {
+            None
+        }
+
+        pub fn set_playing(&self, _is_playing: bool) {}
+
+        pub fn commands(&self) -> Vec<TrayCommand> {
+            Vec::new()
+        }
+    }
+}